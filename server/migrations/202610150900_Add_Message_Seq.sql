@@ -0,0 +1,43 @@
+/* A single sequence shared by all message tables gives a total ingest order across them,
+   used to break ties between messages logged with the same time_stamp. */
+CREATE SEQUENCE IF NOT EXISTS message_seq;
+
+ALTER TABLE requests ADD COLUMN seq BIGINT;
+ALTER TABLE responses ADD COLUMN seq BIGINT;
+ALTER TABLE notifications ADD COLUMN seq BIGINT;
+
+/* Number the existing rows chronologically so they sort the same way new rows will. */
+CREATE TEMPORARY TABLE message_order AS
+SELECT ordered.table_name
+    , ordered.id
+    , ROW_NUMBER() OVER (ORDER BY ordered.time_stamp, ordered.table_name, ordered.id) AS seq
+FROM (
+    SELECT 'requests' AS table_name, id, time_stamp FROM requests
+    UNION ALL
+    SELECT 'responses' AS table_name, id, time_stamp FROM responses
+    UNION ALL
+    SELECT 'notifications' AS table_name, id, time_stamp FROM notifications
+) ordered;
+
+UPDATE requests SET seq = message_order.seq
+FROM message_order
+WHERE message_order.table_name = 'requests' AND message_order.id = requests.id;
+
+UPDATE responses SET seq = message_order.seq
+FROM message_order
+WHERE message_order.table_name = 'responses' AND message_order.id = responses.id;
+
+UPDATE notifications SET seq = message_order.seq
+FROM message_order
+WHERE message_order.table_name = 'notifications' AND message_order.id = notifications.id;
+
+SELECT setval('message_seq', COALESCE(MAX(seq), 0) + 1, false) FROM message_order;
+
+DROP TABLE message_order;
+
+ALTER TABLE requests ALTER COLUMN seq SET DEFAULT nextval('message_seq');
+ALTER TABLE requests ALTER COLUMN seq SET NOT NULL;
+ALTER TABLE responses ALTER COLUMN seq SET DEFAULT nextval('message_seq');
+ALTER TABLE responses ALTER COLUMN seq SET NOT NULL;
+ALTER TABLE notifications ALTER COLUMN seq SET DEFAULT nextval('message_seq');
+ALTER TABLE notifications ALTER COLUMN seq SET NOT NULL;
@@ -0,0 +1,9 @@
+/* Collectors that proxy traffic can report when a message left its sender and when it was
+   delivered to its receiver. These are optional; time_stamp remains the time lls received it. */
+ALTER TABLE requests
+    ADD COLUMN sent_time_stamp TIMESTAMPTZ,
+    ADD COLUMN delivered_time_stamp TIMESTAMPTZ;
+
+ALTER TABLE responses
+    ADD COLUMN sent_time_stamp TIMESTAMPTZ,
+    ADD COLUMN delivered_time_stamp TIMESTAMPTZ;
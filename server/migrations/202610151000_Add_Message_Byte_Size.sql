@@ -0,0 +1,5 @@
+/* The size in bytes of each message's JSON content as it was received, excluding the
+   Content-Length framing. Messages logged before this was tracked have no size. */
+ALTER TABLE requests ADD COLUMN byte_size INTEGER;
+ALTER TABLE responses ADD COLUMN byte_size INTEGER;
+ALTER TABLE notifications ADD COLUMN byte_size INTEGER;
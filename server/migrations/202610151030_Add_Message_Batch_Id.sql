@@ -0,0 +1,7 @@
+/* Messages that arrived together in one JSON-RPC batch share a batch_id, drawn from its own
+   sequence so ids are unique across sessions. Messages sent on their own have no batch_id. */
+CREATE SEQUENCE IF NOT EXISTS message_batch_seq;
+
+ALTER TABLE requests ADD COLUMN batch_id BIGINT;
+ALTER TABLE responses ADD COLUMN batch_id BIGINT;
+ALTER TABLE notifications ADD COLUMN batch_id BIGINT;
@@ -0,0 +1,12 @@
+/* Compacting a session frees its message payloads while keeping the metadata needed for
+   stats: timestamps, methods, ids, sizes and errors. */
+ALTER TABLE sessions ADD COLUMN compacted BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE requests ALTER COLUMN params DROP NOT NULL;
+
+/* Compacted responses keep no result, so a successful response may not have one. */
+ALTER TABLE responses DROP CONSTRAINT responses_check;
+
+ALTER TABLE notifications DROP CONSTRAINT notifications_params_check;
+ALTER TABLE notifications ADD CONSTRAINT notifications_params_check
+    CHECK (params IS NULL OR (params IS JSON ARRAY OR params IS JSON object));
@@ -0,0 +1,4 @@
+/* Sessions ingested in sampling mode only store one in every sample_rate notifications of the
+   sampled methods. Sessions stored in full have neither. */
+ALTER TABLE sessions ADD COLUMN sample_rate INTEGER;
+ALTER TABLE sessions ADD COLUMN sampled_methods TEXT[];
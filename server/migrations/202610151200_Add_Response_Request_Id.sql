@@ -0,0 +1,5 @@
+/* Responses were paired with their requests by sharing a row id, but the two tables draw ids
+   from independent sequences. Each response now stores the id of the request it answers, in
+   the same form as requests.request_id. Responses logged before this have no request_id. */
+ALTER TABLE responses ADD COLUMN request_id TEXT;
+ALTER TABLE responses DROP CONSTRAINT responses_id_fkey;
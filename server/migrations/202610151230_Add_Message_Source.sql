@@ -0,0 +1,7 @@
+/* Which side of the connection sent each message, as reported by the collector. Messages from
+   collectors that don't report it have no source. Notifications were previously all recorded as
+   sent by the client, whichever side actually sent them, so those guesses are cleared. */
+ALTER TABLE requests ADD COLUMN source INTEGER REFERENCES sources(id);
+ALTER TABLE responses ADD COLUMN source INTEGER REFERENCES sources(id);
+ALTER TABLE notifications ALTER COLUMN source DROP NOT NULL;
+UPDATE notifications SET source = NULL;
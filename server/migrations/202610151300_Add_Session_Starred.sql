@@ -0,0 +1,2 @@
+/* Sessions a user has flagged as interesting while reviewing them. */
+ALTER TABLE sessions ADD COLUMN starred BOOLEAN NOT NULL DEFAULT FALSE;
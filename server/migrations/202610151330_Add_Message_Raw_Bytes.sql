@@ -0,0 +1,6 @@
+/* The content of each message exactly as it was received, kept when LLS_STORE_RAW is set so
+   recent sessions can be replayed byte for byte. Retention clears it before the parsed
+   payloads. */
+ALTER TABLE requests ADD COLUMN raw BYTEA;
+ALTER TABLE responses ADD COLUMN raw BYTEA;
+ALTER TABLE notifications ADD COLUMN raw BYTEA;
@@ -0,0 +1,7 @@
+/* Who was talking in each session, from the initialize handshake. Sessions that never completed
+   it have none. */
+ALTER TABLE sessions ADD COLUMN client_name TEXT;
+ALTER TABLE sessions ADD COLUMN client_version TEXT;
+ALTER TABLE sessions ADD COLUMN server_name TEXT;
+ALTER TABLE sessions ADD COLUMN server_version TEXT;
+ALTER TABLE sessions ADD COLUMN workspace_root TEXT;
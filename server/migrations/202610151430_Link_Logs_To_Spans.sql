@@ -0,0 +1,4 @@
+/* Each span is stored once, under the span it was entered within, and every log points at the
+   innermost span it was emitted in. The index is the span's depth below its root. */
+ALTER TABLE log_spans ADD COLUMN parent_id BIGINT REFERENCES log_spans(id);
+ALTER TABLE logs ADD COLUMN span_id BIGINT REFERENCES log_spans(id);
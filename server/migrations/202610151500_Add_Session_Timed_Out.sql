@@ -0,0 +1,3 @@
+/* Sessions whose connection stopped answering pings, rather than being closed. Their end time is
+   the last time a frame was received. */
+ALTER TABLE sessions ADD COLUMN timed_out BOOLEAN NOT NULL DEFAULT FALSE;
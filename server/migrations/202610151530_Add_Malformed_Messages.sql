@@ -0,0 +1,13 @@
+/* Frames that couldn't be parsed as LSP messages, kept byte for byte so that clients sending
+   invalid JSON-RPC can be debugged. They share the message sequence so they can be shown in
+   order with the messages around them. The bytes are cleared when the session is compacted. */
+CREATE TABLE IF NOT EXISTS malformed_messages (
+    id BIGSERIAL PRIMARY KEY
+    , session_id BIGINT NOT NULL REFERENCES sessions(id)
+    , raw_bytes BYTEA
+    , time_stamp TIMESTAMPTZ NOT NULL
+    , parse_error TEXT NOT NULL
+    , seq BIGINT NOT NULL DEFAULT nextval('message_seq')
+);
+
+CREATE INDEX ON malformed_messages (session_id);
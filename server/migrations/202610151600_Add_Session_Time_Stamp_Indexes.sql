@@ -0,0 +1,22 @@
+/* A session's messages and logs are read in order of their time stamps, so they're indexed by
+   session and time stamp. The message tables were already indexed by session alone, which these
+   replace since they cover it, but logs weren't indexed at all and were read by a Seq Scan.
+
+   The expected plans, for a session whose rows are spread among many others':
+   - Its messages are a Bitmap Index Scan on each table's index, appended and then sorted, which
+     only sorts that session's rows. Every table is then read from the heap, since the fetch
+     selects the params or result of each row. When the session's rows sit close together, each
+     table is read by an Index Scan.
+   - Its logs are a Bitmap Index Scan followed by a sort, or an Index Scan that needs no sort
+     when its rows sit close together.
+
+   The message tables, logs and malformed_messages already reference sessions(id). log_spans has no
+   session of its own, since a span is shared by the logs that point at it. */
+CREATE INDEX ON requests (session_id, time_stamp, seq);
+CREATE INDEX ON responses (session_id, time_stamp, seq);
+CREATE INDEX ON notifications (session_id, time_stamp, seq);
+CREATE INDEX ON logs (session_id, time_stamp, id);
+
+DROP INDEX IF EXISTS requests_session_id_idx;
+DROP INDEX IF EXISTS responses_session_id_idx;
+DROP INDEX IF EXISTS notifications_session_id_idx;
@@ -0,0 +1,7 @@
+/* Large request and notification params and response results may be stored gzipped, when
+   LLS_COMPRESS_PAYLOADS is set. The compressed JSON is kept here in place of params or result,
+   which are left NULL, so a row's payload is compressed exactly when this is set. Error data is
+   never compressed. The bytes are cleared when the session is compacted. */
+ALTER TABLE requests ADD COLUMN IF NOT EXISTS compressed_payload BYTEA;
+ALTER TABLE responses ADD COLUMN IF NOT EXISTS compressed_payload BYTEA;
+ALTER TABLE notifications ADD COLUMN IF NOT EXISTS compressed_payload BYTEA;
@@ -0,0 +1,159 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{AppState, api::messages::LoggedMessage, error::AppError};
+
+const DEFAULT_CONTEXT: usize = 5;
+const MAX_CONTEXT: usize = 100;
+
+#[derive(Deserialize)]
+pub(crate) struct GetMessageAtParams {
+    #[serde(with = "time::serde::rfc3339")]
+    t: OffsetDateTime,
+    /// How many messages to include on either side of the nearest one. Defaults to
+    /// `DEFAULT_CONTEXT`.
+    context: Option<usize>,
+}
+
+/// Where the requested time falls relative to the session's messages.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TimePosition {
+    BeforeFirstMessage,
+    Within,
+    AfterLastMessage,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MessagesAt {
+    /// The `seq` of the message logged nearest to the requested time, or `None` when the session
+    /// has no messages.
+    nearest_seq: Option<i64>,
+    position: Option<TimePosition>,
+    /// The nearest message with the messages logged around it, in the order they were logged.
+    messages: Vec<LoggedMessage>,
+}
+
+/// Finds the message logged nearest to a wall-clock time, so that other logs can be lined up
+/// with the session.
+pub(crate) async fn get_messages_at(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+    Query(request): Query<GetMessageAtParams>,
+) -> Result<Json<MessagesAt>, AppError> {
+    sqlx::query!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let conversation =
+        crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    let messages = conversation.messages();
+    let (Some(first), Some(last)) = (messages.first(), messages.last()) else {
+        return Ok(Json(MessagesAt {
+            nearest_seq: None,
+            position: None,
+            messages: Vec::new(),
+        }));
+    };
+
+    let position = if request.t < first.time_stamp {
+        TimePosition::BeforeFirstMessage
+    } else if request.t > last.time_stamp {
+        TimePosition::AfterLastMessage
+    } else {
+        TimePosition::Within
+    };
+
+    // The first message at or after the time, and the one before it, are the candidates. Ties
+    // go to the earlier message.
+    let after = messages.partition_point(|message| message.time_stamp < request.t);
+    let nearest = match (after.checked_sub(1), messages.get(after)) {
+        (Some(before), Some(at_or_after))
+            if request.t - messages[before].time_stamp <= at_or_after.time_stamp - request.t =>
+        {
+            before
+        }
+        (Some(before), None) => before,
+        _ => after,
+    };
+
+    let context = request.context.unwrap_or(DEFAULT_CONTEXT).min(MAX_CONTEXT);
+    let start = nearest.saturating_sub(context);
+    let end = (nearest + context + 1).min(messages.len());
+
+    Ok(Json(MessagesAt {
+        nearest_seq: Some(messages[nearest].seq),
+        position: Some(position),
+        messages: messages[start..end]
+            .iter()
+            .cloned()
+            .map(LoggedMessage::from)
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn the_nearest_message_is_found_for_a_mid_session_time(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO sessions (start_time_stamp) VALUES ($1) RETURNING id;",
+            OffsetDateTime::UNIX_EPOCH
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let mut seqs = Vec::new();
+        for millis in [0, 100, 1000, 1100] {
+            let seq = sqlx::query_scalar!(
+                "INSERT INTO notifications (session_id, method, params, time_stamp) VALUES ($1, 'textDocument/didSave', '{}', $2) RETURNING seq;",
+                session_id,
+                OffsetDateTime::UNIX_EPOCH + time::Duration::milliseconds(millis)
+            )
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+            seqs.push(seq);
+        }
+
+        let Json(at) = get_messages_at(
+            State(state),
+            Path(session_id),
+            Query(GetMessageAtParams {
+                t: OffsetDateTime::UNIX_EPOCH + time::Duration::milliseconds(800),
+                context: Some(1),
+            }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(at.nearest_seq, Some(seqs[2]));
+        assert!(matches!(at.position, Some(TimePosition::Within)));
+        let context_seqs = at
+            .messages
+            .iter()
+            .map(|message| serde_json::to_value(message).unwrap()["seq"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            context_seqs,
+            seqs[1..]
+                .iter()
+                .map(|seq| Value::from(*seq))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use crate::{
+    AppState, auth::has_bearer_token, error::AppError, retention::delete_sessions_ended_before,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct DeleteSessionsParams {
+    #[serde(with = "time::serde::rfc3339")]
+    before: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+pub(crate) struct DeletedSessions {
+    deleted_sessions: u64,
+}
+
+/// Deletes the sessions that ended before a cutoff, with all of their messages and logs, to
+/// reclaim space. Sessions that are still live are never deleted. Requires the configured admin
+/// token as a bearer token.
+pub(crate) async fn delete_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<DeleteSessionsParams>,
+) -> Result<Json<DeletedSessions>, AppError> {
+    let Some(admin_token) = &state.config.admin_token else {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "no admin token is configured",
+        ));
+    };
+    if !has_bearer_token(&headers, admin_token) {
+        return Err(AppError::new(
+            StatusCode::UNAUTHORIZED,
+            "the admin token is required as a bearer token",
+        ));
+    }
+
+    let deleted_sessions = delete_sessions_ended_before(&state.db, request.before)
+        .await
+        .map_err(|err| {
+            error!(
+                "Failed to delete the sessions that ended before {}. Error: {err}",
+                request.before
+            );
+            AppError::internal(format!(
+                "failed to delete the sessions that ended before {}",
+                request.before
+            ))
+        })?;
+    info!(
+        "Deleted {deleted_sessions} sessions that ended before {}",
+        request.before
+    );
+
+    Ok(Json(DeletedSessions { deleted_sessions }))
+}
@@ -0,0 +1,193 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
+use sqlx::PgConnection;
+use tracing::error;
+
+use crate::{AppState, auth::has_bearer_token, error::AppError};
+
+/// Frees the bulky payloads of an ended session (request and notification params, response
+/// results and error data, and raw bytes) while keeping the timestamps, methods, ids and sizes
+/// that stats are built from. Sessions that are still live can't be compacted, since new
+/// messages would still arrive with payloads. Requires the configured admin token as a bearer
+/// token, since the payloads can't be brought back.
+pub(crate) async fn compact_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(session_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let Some(admin_token) = &state.config.admin_token else {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "no admin token is configured",
+        ));
+    };
+    if !has_bearer_token(&headers, admin_token) {
+        return Err(AppError::new(
+            StatusCode::UNAUTHORIZED,
+            "the admin token is required as a bearer token",
+        ));
+    }
+
+    let mut transaction = state.db.begin().await.map_err(|err| {
+        error!("Failed to start a transaction. Error: {err}");
+        AppError::internal(format!("failed to compact session {session_id}"))
+    })?;
+
+    let session = sqlx::query!(
+        "SELECT end_time_stamp FROM sessions WHERE id = $1 LIMIT 1 FOR UPDATE;",
+        session_id
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    if session.end_time_stamp.is_none() {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!("session {session_id} is still live and can't be compacted"),
+        ));
+    }
+
+    let compact_result = compact_payloads(&mut transaction, &[session_id]).await;
+
+    if let Err(err) = compact_result {
+        error!("Failed to compact session {session_id}. Error: {err}");
+        return Err(AppError::internal(format!(
+            "failed to compact session {session_id}"
+        )));
+    }
+
+    transaction.commit().await.map_err(|err| {
+        error!("Failed to commit the compaction of session {session_id}. Error: {err}");
+        AppError::internal(format!("failed to compact session {session_id}"))
+    })?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Frees the payloads of the given sessions and marks them as compacted. Callers are responsible
+/// for only passing sessions that have ended.
+pub(crate) async fn compact_payloads(
+    connection: &mut PgConnection,
+    session_ids: &[i64],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE requests SET params = NULL, compressed_payload = NULL, raw = NULL WHERE session_id = ANY($1);",
+        session_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+    sqlx::query!(
+        "UPDATE responses SET result = NULL, error_data = NULL, compressed_payload = NULL, raw = NULL WHERE session_id = ANY($1);",
+        session_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+    sqlx::query!(
+        "UPDATE notifications SET params = NULL, compressed_payload = NULL, raw = NULL WHERE session_id = ANY($1);",
+        session_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+    sqlx::query!(
+        "UPDATE malformed_messages SET raw_bytes = NULL WHERE session_id = ANY($1);",
+        session_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+    sqlx::query!(
+        "UPDATE sessions SET compacted = TRUE WHERE id = ANY($1);",
+        session_ids
+    )
+    .execute(&mut *connection)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{HeaderValue, header::AUTHORIZATION};
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn compaction_frees_payloads_but_keeps_metadata(db: PgPool) {
+        let state = AppState::for_tests(db, |config| {
+            config.admin_token = Some("secret".to_string());
+        });
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO sessions (start_time_stamp, end_time_stamp) VALUES (NOW(), NOW()) RETURNING id;"
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO requests (request_id, session_id, method, params, time_stamp, byte_size) VALUES ('1', $1, 'textDocument/hover', '{\"position\":{}}', NOW(), 120);",
+            session_id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO responses (request_id, session_id, is_error, result, time_stamp, byte_size) VALUES ('1', $1, false, '{\"contents\":\"docs\"}', NOW(), 80);",
+            session_id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO notifications (session_id, method, params, time_stamp, byte_size) VALUES ($1, 'initialized', '{}', NOW(), 40);",
+            session_id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+        let status = compact_session(State(state.clone()), headers, Path(session_id))
+            .await
+            .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let request = sqlx::query!(
+            "SELECT method, params, byte_size FROM requests WHERE session_id = $1;",
+            session_id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(request.method, "textDocument/hover");
+        assert_eq!(request.params, None);
+        assert_eq!(request.byte_size, Some(120));
+        let response = sqlx::query!(
+            "SELECT request_id, result, byte_size FROM responses WHERE session_id = $1;",
+            session_id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(response.request_id, Some("1".to_string()));
+        assert_eq!(response.result, None);
+        assert_eq!(response.byte_size, Some(80));
+        let notification = sqlx::query!(
+            "SELECT method, params, byte_size FROM notifications WHERE session_id = $1;",
+            session_id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(notification.method, "initialized");
+        assert_eq!(notification.params, None);
+        assert_eq!(notification.byte_size, Some(40));
+        let compacted =
+            sqlx::query_scalar!("SELECT compacted FROM sessions WHERE id = $1;", session_id)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert!(compacted);
+    }
+}
@@ -0,0 +1,61 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use lsp_server::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState, error::AppError, message::classify, session::MessageSource, utils::get_rfc3339_string,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct GetSessionJsonParams {
+    session_id: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ConversationMessage {
+    timestamp: String,
+    /// `None` when the direction couldn't be determined.
+    source: Option<MessageSource>,
+    /// The category the session view files the message under.
+    kind: &'static str,
+    message: Message,
+}
+
+/// The whole of a session's conversation, classified the same way as the session view.
+pub(crate) async fn get_session_json(
+    State(state): State<AppState>,
+    Query(request): Query<GetSessionJsonParams>,
+) -> Result<Json<Vec<ConversationMessage>>, AppError> {
+    sqlx::query!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    Ok(Json(
+        conversation
+            .messages()
+            .iter()
+            .map(|message| ConversationMessage {
+                timestamp: get_rfc3339_string(&message.time_stamp),
+                source: message.get_source(&conversation),
+                kind: classify(&message.message, &conversation)
+                    .map(|kind| kind.as_str())
+                    .unwrap_or("uncategorized"),
+                message: message.message.clone(),
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,56 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, document::reconstruct_document, error::AppError};
+
+#[derive(Deserialize)]
+pub(crate) struct GetDocumentParams {
+    uri: String,
+    /// Only replay messages up to and including this `seq`. Defaults to the whole session.
+    seq: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct DocumentSnapshot {
+    uri: String,
+    language_id: Option<String>,
+    version: i32,
+    /// The `seq` of the last message that changed the document.
+    seq: i64,
+    text: String,
+}
+
+pub(crate) async fn get_document(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+    Query(request): Query<GetDocumentParams>,
+) -> Result<Json<DocumentSnapshot>, AppError> {
+    sqlx::query!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let conversation =
+        crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    let document =
+        reconstruct_document(&conversation, &request.uri, request.seq).ok_or_else(|| {
+            AppError::not_found(format!(
+                "no document {} was opened in session {session_id}",
+                request.uri
+            ))
+        })?;
+
+    Ok(Json(DocumentSnapshot {
+        uri: request.uri,
+        language_id: document.language_id,
+        version: document.version,
+        seq: document.seq,
+        text: document.text,
+    }))
+}
@@ -0,0 +1,126 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use lsp_server::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AppState,
+    error::AppError,
+    message::classify,
+    session::{MessageSource, ProxyTimeStamps},
+    utils::get_rfc3339_string,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct ExportSessionParams {
+    session_id: i64,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SessionExport {
+    session: ExportedSession,
+    messages: Vec<ExportedMessage>,
+}
+
+#[derive(Serialize)]
+struct ExportedSession {
+    id: i64,
+    start_time_stamp: String,
+    end_time_stamp: Option<String>,
+    client_name: Option<String>,
+    client_version: Option<String>,
+    server_name: Option<String>,
+    server_version: Option<String>,
+    workspace_root: Option<String>,
+    /// Set when only one in this many notifications of some methods was stored.
+    sample_rate: Option<i32>,
+    /// Whether the session was ended because its connection stopped responding.
+    timed_out: bool,
+    /// Whether the payloads of the session's messages have been freed.
+    compacted: bool,
+}
+
+/// A message with everything `POST /log` needs to log it again, plus the time it was received
+/// and its category for readers of the file.
+#[derive(Serialize)]
+struct ExportedMessage {
+    time_stamp: String,
+    /// The category the session view files the message under.
+    kind: &'static str,
+    /// `None` when the direction couldn't be determined.
+    source: Option<MessageSource>,
+    #[serde(flatten)]
+    proxy_time_stamps: ProxyTimeStamps,
+    #[serde(flatten)]
+    message: Message,
+}
+
+/// Downloads a session as a JSON file to attach to bug reports. Each of its messages can be
+/// posted to `/log` with a `session_id` to move the trace to another instance, although the
+/// messages are then stamped with the time they're logged again.
+pub(crate) async fn export_session(
+    State(state): State<AppState>,
+    Query(request): Query<ExportSessionParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let session = sqlx::query!(
+        "SELECT id, start_time_stamp, end_time_stamp, client_name, client_version, server_name, server_version, workspace_root, sample_rate, timed_out, compacted
+        FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let messages = conversation
+        .messages()
+        .iter()
+        .map(|message| ExportedMessage {
+            time_stamp: get_rfc3339_string(&message.time_stamp),
+            kind: classify(&message.message, &conversation)
+                .map(|kind| kind.as_str())
+                .unwrap_or("uncategorized"),
+            source: message.get_source(&conversation),
+            proxy_time_stamps: message.proxy_time_stamps,
+            message: message.message.clone(),
+        })
+        .collect();
+
+    let export = SessionExport {
+        session: ExportedSession {
+            id: session.id,
+            start_time_stamp: get_rfc3339_string(&session.start_time_stamp),
+            end_time_stamp: session.end_time_stamp.as_ref().map(get_rfc3339_string),
+            client_name: session.client_name,
+            client_version: session.client_version,
+            server_name: session.server_name,
+            server_version: session.server_version,
+            workspace_root: session.workspace_root,
+            sample_rate: session.sample_rate,
+            timed_out: session.timed_out,
+            compacted: session.compacted,
+        },
+        messages,
+    };
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"session-{}.json\"",
+                request.session_id
+            ),
+        )],
+        Json(export),
+    ))
+}
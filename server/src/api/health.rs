@@ -0,0 +1,12 @@
+use axum::{extract::State, http::StatusCode};
+
+use crate::AppState;
+
+/// A probe for load balancers and orchestrators that answers `200 OK` while the database can be
+/// reached and `503 Service Unavailable` otherwise.
+pub(crate) async fn get_health(State(state): State<AppState>) -> StatusCode {
+    match sqlx::query!("SELECT 1 AS one").fetch_one(&state.db).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
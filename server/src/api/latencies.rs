@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use lsp_server::Message;
+use serde::Deserialize;
+use time::Duration;
+
+use crate::{AppState, error::AppError, session::get_stored_request_id, utils::get_rfc3339_string};
+
+#[derive(Deserialize)]
+pub(crate) struct LatencyExportParams {
+    /// Only export requests for this method.
+    method: Option<String>,
+    /// Also export requests that never received a response, with empty latency columns.
+    include_unanswered: Option<bool>,
+}
+
+/// Exports every request of a session with its latency as CSV, for charting outside the app.
+/// Think time is only filled in when a proxying collector reported the time stamps it needs.
+pub(crate) async fn get_session_latencies_csv(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+    Query(request): Query<LatencyExportParams>,
+) -> Result<impl IntoResponse, AppError> {
+    sqlx::query_scalar!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let conversation =
+        crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    let include_unanswered = request.include_unanswered.unwrap_or(false);
+
+    let mut csv = String::new();
+    csv.push_str("request_id,method,sent_at,latency_ms,think_time_ms\r\n");
+    for pair in conversation.pair_requests_with_responses() {
+        let Message::Request(lsp_request) = &pair.request.message else {
+            continue;
+        };
+        if request
+            .method
+            .as_ref()
+            .is_some_and(|method| method != &lsp_request.method)
+        {
+            continue;
+        }
+        if pair.response.is_none() && !include_unanswered {
+            continue;
+        }
+
+        push_csv_field(&mut csv, &get_stored_request_id(&lsp_request.id));
+        csv.push(',');
+        push_csv_field(&mut csv, &lsp_request.method);
+        csv.push(',');
+        csv.push_str(&get_rfc3339_string(&pair.request.time_stamp));
+        csv.push(',');
+        if let Some(latency) = pair.latency() {
+            csv.push_str(&get_milliseconds(latency));
+        }
+        csv.push(',');
+        if let Some(think_time) = pair.think_time() {
+            csv.push_str(&get_milliseconds(think_time));
+        }
+        csv.push_str("\r\n");
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"session-{session_id}-latencies.csv\""),
+            ),
+        ],
+        csv,
+    ))
+}
+
+fn get_milliseconds(duration: Duration) -> String {
+    format!("{:.3}", duration.as_seconds_f64() * 1000.0)
+}
+
+/// Writes a field, quoting it when it contains characters that are special in CSV.
+fn push_csv_field(csv: &mut String, field: &str) {
+    if field.contains([',', '"', '\r', '\n']) {
+        csv.push('"');
+        csv.push_str(&field.replace('"', "\"\""));
+        csv.push('"');
+    } else {
+        csv.push_str(field);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use sqlx::PgPool;
+    use time::OffsetDateTime;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn there_is_one_row_per_answered_request(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO sessions (start_time_stamp) VALUES (NOW()) RETURNING id;"
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        for (request_id, is_answered) in [("1", true), ("2", false), ("3", true)] {
+            sqlx::query!(
+                "INSERT INTO requests (request_id, session_id, method, params, time_stamp) VALUES ($1, $2, 'textDocument/hover', '{}', $3);",
+                request_id,
+                session_id,
+                OffsetDateTime::UNIX_EPOCH
+            )
+            .execute(&state.db)
+            .await
+            .unwrap();
+            if is_answered {
+                sqlx::query!(
+                    "INSERT INTO responses (request_id, session_id, is_error, result, time_stamp) VALUES ($1, $2, false, 'null', $3);",
+                    request_id,
+                    session_id,
+                    OffsetDateTime::UNIX_EPOCH + Duration::milliseconds(5)
+                )
+                .execute(&state.db)
+                .await
+                .unwrap();
+            }
+        }
+
+        let response = get_session_latencies_csv(
+            State(state),
+            Path(session_id),
+            Query(LatencyExportParams {
+                method: None,
+                include_unanswered: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let csv = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let csv = str::from_utf8(&csv).unwrap();
+
+        let rows = csv.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("1,textDocument/hover,"));
+        assert!(rows[1].starts_with("3,textDocument/hover,"));
+        assert!(rows.iter().all(|row| row.contains(",5.000,")));
+    }
+}
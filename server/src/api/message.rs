@@ -0,0 +1,134 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use lsp_server::Message;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    AppState,
+    error::AppError,
+    message::Conversation,
+    session::{MessageSource, MessageWithTimeStamp},
+};
+
+/// The session a message's `POST /log` body is logged to. No session has this id, so the body is
+/// refused until it's replaced with one from `POST /sessions` on the instance it's sent to.
+const PLACEHOLDER_SESSION_ID: i64 = 0;
+
+#[derive(Deserialize)]
+pub(crate) struct GetMessageReplayParams {
+    format: ReplayFormat,
+}
+
+/// How a message is written out to be sent again.
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ReplayFormat {
+    /// The frame to write to a server's stdin, `Content-Length` header included.
+    Stdio,
+    /// A `POST /log` body, to log the message to another instance.
+    Log,
+}
+
+/// The body `POST /log` takes to log a message again.
+#[derive(Serialize)]
+struct LogMessageBody<'a> {
+    session_id: i64,
+    source: Option<MessageSource>,
+    #[serde(flatten)]
+    message: &'a Message,
+}
+
+/// Returns one message of a session as pretty-printed JSON, so that its exact payload can be
+/// copied out of the chat without every bubble carrying a second copy of it.
+pub(crate) async fn get_message_json(
+    State(state): State<AppState>,
+    Path((session_id, seq)): Path<(i64, i64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let conversation = get_conversation(&state, session_id).await?;
+    let message = find_message(&conversation, session_id, seq)?;
+
+    let json = serde_json::to_string_pretty(&message.message).map_err(|err| {
+        error!("Failed to serialize message {seq} as JSON. Error: {err}");
+        AppError::internal(format!("failed to serialize message {seq}"))
+    })?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "application/json; charset=utf-8".to_string(),
+        )],
+        json,
+    ))
+}
+
+/// Writes a request or notification out so that it can be sent again by hand. Responses can't
+/// be, since there's no request for them to answer.
+pub(crate) async fn get_message_replay(
+    State(state): State<AppState>,
+    Path((session_id, seq)): Path<(i64, i64)>,
+    Query(request): Query<GetMessageReplayParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let conversation = get_conversation(&state, session_id).await?;
+    let message_with_time_stamp = find_message(&conversation, session_id, seq)?;
+    let message = &message_with_time_stamp.message;
+    if let Message::Response(_) = message {
+        return Err(AppError::bad_request(format!(
+            "message {seq} is a response, which can't be replayed"
+        )));
+    }
+
+    let snippet = match request.format {
+        ReplayFormat::Stdio => {
+            let mut frame = Vec::new();
+            message
+                .write(&mut frame)
+                .map(|()| String::from_utf8_lossy(&frame).into_owned())
+                .map_err(|err| err.to_string())
+        }
+        ReplayFormat::Log => serde_json::to_string_pretty(&LogMessageBody {
+            session_id: PLACEHOLDER_SESSION_ID,
+            source: message_with_time_stamp.get_source(&conversation),
+            message,
+        })
+        .map_err(|err| err.to_string()),
+    }
+    .map_err(|err| {
+        error!("Failed to serialize message {seq} for replay. Error: {err}");
+        AppError::internal(format!("failed to serialize message {seq} for replay"))
+    })?;
+
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; charset=utf-8".to_string(),
+        )],
+        snippet,
+    ))
+}
+
+async fn get_conversation(state: &AppState, session_id: i64) -> Result<Conversation, AppError> {
+    sqlx::query_scalar!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+        .await
+        .map_err(|_| AppError::session_messages(session_id))
+}
+
+fn find_message(
+    conversation: &Conversation,
+    session_id: i64,
+    seq: i64,
+) -> Result<&MessageWithTimeStamp, AppError> {
+    conversation
+        .messages()
+        .iter()
+        .find(|message| message.seq == seq)
+        .ok_or_else(|| AppError::not_found(format!("session {session_id} has no message {seq}")))
+}
@@ -0,0 +1,91 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use lsp_server::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, error::AppError, session::MessageWithTimeStamp, utils::get_rfc3339_string};
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+
+#[derive(Deserialize)]
+pub(crate) struct GetMessagesParams {
+    /// Only return the messages that follow the message with this `seq`, in the order of their
+    /// time stamps.
+    after_seq: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct MessagePage {
+    messages: Vec<LoggedMessage>,
+    /// The `after_seq` to request the next page with, or `None` if this was the last page.
+    next_after_seq: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LoggedMessage {
+    seq: i64,
+    time_stamp: String,
+    message: Message,
+}
+
+impl From<MessageWithTimeStamp> for LoggedMessage {
+    fn from(message: MessageWithTimeStamp) -> Self {
+        LoggedMessage {
+            seq: message.seq,
+            time_stamp: get_rfc3339_string(&message.time_stamp),
+            message: message.message,
+        }
+    }
+}
+
+/// Pages through a session's messages in the order they were logged, without loading the
+/// whole session.
+pub(crate) async fn get_messages(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+    Query(request): Query<GetMessagesParams>,
+) -> Result<Json<MessagePage>, AppError> {
+    sqlx::query!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let after = match request.after_seq {
+        Some(after_seq) => {
+            let time_stamp =
+                crate::session::get_message_time_stamp(&state.db, session_id, after_seq)
+                    .await
+                    .map_err(|_| AppError::session_messages(session_id))?
+                    .ok_or_else(|| {
+                        AppError::bad_request(format!(
+                            "{after_seq} is not the seq of a message in session {session_id}"
+                        ))
+                    })?;
+            Some((time_stamp, after_seq))
+        }
+        None => None,
+    };
+    let window =
+        crate::session::get_message_window_for_session(&state.db, session_id, after, limit)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    let next_after_seq = if window.len() as i64 == limit {
+        window.last().map(|message| message.seq)
+    } else {
+        None
+    };
+
+    Ok(Json(MessagePage {
+        messages: window.into_iter().map(LoggedMessage::from).collect(),
+        next_after_seq,
+    }))
+}
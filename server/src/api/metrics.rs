@@ -0,0 +1,11 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use crate::AppState;
+
+/// Exposes the server's counters for Prometheus to scrape.
+pub(crate) async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.to_prometheus_text(),
+    )
+}
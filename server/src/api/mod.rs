@@ -0,0 +1,15 @@
+pub(crate) mod at;
+pub(crate) mod cleanup;
+pub(crate) mod compact;
+pub(crate) mod conversation;
+pub(crate) mod document;
+pub(crate) mod export;
+pub(crate) mod health;
+pub(crate) mod latencies;
+pub(crate) mod message;
+pub(crate) mod messages;
+pub(crate) mod metrics;
+pub(crate) mod replay;
+pub(crate) mod stream;
+pub(crate) mod summary;
+pub(crate) mod test_case;
@@ -0,0 +1,338 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io::BufReader,
+    process::Stdio,
+    time::Duration,
+};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use lsp_server::{Message, RequestId, Response};
+use lsp_types::request::{Initialize, Request};
+use serde::Deserialize;
+use serde_json::Value;
+use time::OffsetDateTime;
+use tokio::{
+    io::AsyncWriteExt,
+    process::{ChildStdin, Command},
+    sync::mpsc,
+    time::{Instant, sleep, sleep_until},
+};
+use tracing::{Instrument, error, info, info_span};
+
+use crate::{
+    AppState,
+    auth::has_bearer_token,
+    error::AppError,
+    ingest::{CreatedSession, LoggingSession, MessageMetadata, read_frame},
+    message::Conversation,
+    session::{MessageSource, ProxyTimeStamps},
+};
+
+/// How long the server may stay silent once there's nothing left to send before the replay is
+/// finished, since a recording that doesn't end with `exit` leaves the server running.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+pub(crate) struct ReplaySessionParams {
+    session_id: i64,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ReplaySessionBody {
+    /// The language server to replay to, as a program followed by its arguments. It's run
+    /// directly rather than through a shell.
+    command: Vec<String>,
+    /// Wait as long between messages as the client did when they were recorded, instead of
+    /// sending each one as soon as possible.
+    #[serde(default)]
+    preserve_timing: bool,
+}
+
+/// What the client of a recorded session sent, to be sent again.
+struct ReplayScript {
+    /// The client's requests and notifications, with how long after its first message each
+    /// was sent.
+    messages: Vec<(Duration, Message)>,
+    /// The client's answers to the server's requests, by the method they answered, in the order
+    /// they were given. The server being replayed to is answered with them, since it numbers
+    /// its requests on its own.
+    responses: HashMap<String, VecDeque<Response>>,
+}
+
+impl ReplayScript {
+    fn new(conversation: &Conversation) -> Self {
+        let mut messages = Vec::new();
+        let mut responses = HashMap::<String, VecDeque<Response>>::new();
+        let mut first_time_stamp = None;
+
+        for message_with_time_stamp in conversation.messages() {
+            if message_with_time_stamp.get_source(conversation) != Some(MessageSource::Client) {
+                continue;
+            }
+            let first_time_stamp =
+                *first_time_stamp.get_or_insert(message_with_time_stamp.time_stamp);
+
+            match &message_with_time_stamp.message {
+                Message::Request(_) | Message::Notification(_) => {
+                    let offset = (message_with_time_stamp.time_stamp - first_time_stamp)
+                        .try_into()
+                        .unwrap_or_default();
+                    messages.push((offset, message_with_time_stamp.message.clone()));
+                }
+                Message::Response(response) => {
+                    if let Some(request) = conversation.requests().get(&response.id) {
+                        responses
+                            .entry(request.method.clone())
+                            .or_default()
+                            .push_back(response.clone());
+                    }
+                }
+            }
+        }
+
+        Self {
+            messages,
+            responses,
+        }
+    }
+}
+
+/// Replays the client side of a recorded session to a freshly started language server, logging
+/// the exchange as a new session so that it can be compared with the original. The server is
+/// run as a child process that's spoken to over stdio, and the replay carries on in the
+/// background once it has started. Since it runs a command, it requires the configured admin
+/// token as a bearer token.
+pub(crate) async fn replay_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(request): Query<ReplaySessionParams>,
+    Json(body): Json<ReplaySessionBody>,
+) -> Result<(StatusCode, Json<CreatedSession>), AppError> {
+    let Some(admin_token) = &state.config.admin_token else {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "no admin token is configured",
+        ));
+    };
+    if !has_bearer_token(&headers, admin_token) {
+        return Err(AppError::new(
+            StatusCode::UNAUTHORIZED,
+            "the admin token is required as a bearer token",
+        ));
+    }
+    let Some((program, args)) = body.command.split_first() else {
+        return Err(AppError::bad_request("the command to replay to is empty"));
+    };
+
+    sqlx::query!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+    let script = ReplayScript::new(&conversation);
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| {
+            error!("Failed to start {program} to replay to. Error: {err}");
+            AppError::bad_request(format!("failed to start {program}: {err}"))
+        })?;
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return Err(AppError::internal(format!(
+            "failed to connect to the stdio of {program}"
+        )));
+    };
+
+    let session = LoggingSession::start(&state).await.map_err(|err| {
+        error!("Failed to get a session_id. Error: {err}");
+        AppError::internal("failed to start a session to log the replay to")
+    })?;
+    let session_id = session.id;
+    info!(
+        session_id,
+        "Replaying session {} to {program}", request.session_id
+    );
+
+    // Frames are read on their own task, since reading one can't be abandoned partway through
+    // while waiting for the next message to send.
+    let (frame_sender, frames) = mpsc::unbounded_channel();
+    let max_frame_size = state.config.max_frame_size;
+    tokio::spawn(async move {
+        let mut stdout = tokio::io::BufReader::new(stdout);
+        while let Ok(Some(frame)) = read_frame(&mut stdout, max_frame_size).await {
+            if frame_sender
+                .send((frame, OffsetDateTime::now_utc()))
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(
+        async move {
+            replay(session, script, stdin, frames, body.preserve_timing).await;
+            // Stops the server if it didn't exit on its own.
+            drop(child);
+        }
+        .instrument(info_span!("session", session_id)),
+    );
+
+    Ok((StatusCode::CREATED, Json(CreatedSession { session_id })))
+}
+
+/// Sends the script to the server while logging everything it sends back, until the server
+/// closes its output or has nothing more to say.
+async fn replay(
+    mut session: LoggingSession,
+    script: ReplayScript,
+    mut stdin: ChildStdin,
+    mut frames: mpsc::UnboundedReceiver<(Vec<u8>, OffsetDateTime)>,
+    preserve_timing: bool,
+) {
+    let start = Instant::now();
+    let mut messages = script.messages.into_iter().peekable();
+    let mut responses = script.responses;
+    // Nothing may be sent after `initialize` until the server has answered it.
+    let mut unanswered_initialize = None::<RequestId>;
+
+    loop {
+        let next_send_time = match messages.peek() {
+            Some(_) if unanswered_initialize.is_some() => None,
+            Some((offset, _)) if preserve_timing => Some(start + *offset),
+            Some(_) => Some(Instant::now()),
+            None => None,
+        };
+
+        tokio::select! {
+            () = sleep_until(next_send_time.unwrap_or(start)), if next_send_time.is_some() => {
+                let Some((_, message)) = messages.next() else {
+                    continue;
+                };
+                if let Message::Request(request) = &message
+                    && request.method == Initialize::METHOD
+                {
+                    unanswered_initialize = Some(request.id.clone());
+                }
+                if !send(&mut session, &mut stdin, message).await {
+                    break;
+                }
+            }
+            frame = frames.recv() => {
+                let Some((frame, received_time)) = frame else {
+                    info!("The server closed its output");
+                    break;
+                };
+                let message = match Message::read(&mut BufReader::new(frame.as_slice())) {
+                    Ok(Some(message)) => message,
+                    Ok(None) => continue,
+                    Err(err) => {
+                        session.log_malformed(&frame, received_time, err.to_string()).await;
+                        continue;
+                    }
+                };
+
+                // The server's requests are answered like the client answered the recorded
+                // server's, or with null when it didn't.
+                let answer = match &message {
+                    Message::Request(request) => {
+                        let response = responses
+                            .get_mut(&request.method)
+                            .and_then(VecDeque::pop_front)
+                            .unwrap_or_else(|| Response::new_ok(request.id.clone(), Value::Null));
+                        Some(Response {
+                            id: request.id.clone(),
+                            ..response
+                        })
+                    }
+                    Message::Response(response)
+                        if unanswered_initialize.as_ref() == Some(&response.id) =>
+                    {
+                        unanswered_initialize = None;
+                        None
+                    }
+                    Message::Response(_) | Message::Notification(_) => None,
+                };
+
+                let content_length = frame
+                    .windows(4)
+                    .position(|window| window == b"\r\n\r\n")
+                    .map(|header_length| frame.len() - header_length - 4);
+                session
+                    .log(
+                        message,
+                        MessageMetadata {
+                            received_time,
+                            source: Some(MessageSource::Server),
+                            proxy_time_stamps: ProxyTimeStamps::default(),
+                            byte_size: content_length.and_then(|length| i32::try_from(length).ok()),
+                            raw: None,
+                            batch_id: None,
+                        },
+                    )
+                    .await;
+
+                if let Some(answer) = answer
+                    && !send(&mut session, &mut stdin, Message::Response(answer)).await
+                {
+                    break;
+                }
+            }
+            () = sleep(IDLE_TIMEOUT), if next_send_time.is_none() => {
+                info!("Stopped waiting for the server after {IDLE_TIMEOUT:?} without output");
+                break;
+            }
+        }
+    }
+}
+
+/// Writes a message to the server, framed by its `Content-Length`, and logs it as sent by the
+/// client. Returns whether the server could still be written to.
+async fn send(session: &mut LoggingSession, stdin: &mut ChildStdin, message: Message) -> bool {
+    let content = match serde_json::to_vec(&message) {
+        Ok(content) => content,
+        Err(err) => {
+            error!("Failed to serialize a message to replay. Error: {err}");
+            return true;
+        }
+    };
+    let mut frame = format!("Content-Length: {}\r\n\r\n", content.len()).into_bytes();
+    frame.extend_from_slice(&content);
+    if let Err(err) = stdin.write_all(&frame).await {
+        error!("Failed to write to the server. Error: {err}");
+        return false;
+    }
+
+    session
+        .log(
+            message,
+            MessageMetadata {
+                received_time: OffsetDateTime::now_utc(),
+                source: Some(MessageSource::Client),
+                proxy_time_stamps: ProxyTimeStamps::default(),
+                byte_size: i32::try_from(content.len()).ok(),
+                raw: None,
+                batch_id: None,
+            },
+        )
+        .await;
+    true
+}
@@ -0,0 +1,69 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{AppState, api::messages::LoggedMessage, error::AppError, live::LiveEvent};
+
+#[derive(Deserialize)]
+pub(crate) struct GetSessionStreamParams {
+    session_id: i64,
+}
+
+/// Streams the messages of a live session as Server-Sent Events while they're logged, ending
+/// once the session does. Each message is sent as a `LoggedMessage`. Subscribers that fall too
+/// far behind are sent a `lagged` event with the number of messages they missed.
+pub(crate) async fn get_session_stream(
+    State(state): State<AppState>,
+    Query(request): Query<GetSessionStreamParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    // Subscribe before checking the session, so that nothing logged in between is missed.
+    let receiver = state.live_events.subscribe();
+
+    let session = sqlx::query!(
+        "SELECT end_time_stamp FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    if session.end_time_stamp.is_some() {
+        return Err(AppError::new(
+            StatusCode::CONFLICT,
+            format!(
+                "session {} has already ended and can't be streamed",
+                request.session_id
+            ),
+        ));
+    }
+
+    let session_id = request.session_id;
+    let stream = futures::stream::unfold(receiver, move |mut receiver| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(LiveEvent::Logged {
+                    session_id: logged_session_id,
+                    message,
+                }) if logged_session_id == session_id => {
+                    Event::default().json_data(LoggedMessage::from(message.as_ref().clone()))
+                }
+                Ok(LiveEvent::Ended {
+                    session_id: ended_session_id,
+                }) if ended_session_id == session_id => return None,
+                Ok(_) => continue,
+                Err(RecvError::Lagged(missed)) => {
+                    Ok(Event::default().event("lagged").data(missed.to_string()))
+                }
+                Err(RecvError::Closed) => return None,
+            };
+            return Some((event, receiver));
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use lsp_server::Message;
+use lsp_types::{
+    ClientInfo, ServerInfo,
+    notification::{Exit, Initialized, Notification},
+    request::{Initialize, Request, Shutdown},
+};
+use serde::Serialize;
+use time::OffsetDateTime;
+
+use crate::{
+    AppState,
+    error::AppError,
+    message::{Conversation, classify},
+    utils::get_rfc3339_string,
+};
+
+#[derive(Serialize)]
+pub(crate) struct SessionSummary {
+    session_id: i64,
+    start_time_stamp: String,
+    end_time_stamp: Option<String>,
+    duration_ms: Option<f64>,
+    request_count: usize,
+    response_count: usize,
+    notification_count: usize,
+    /// Message counts keyed by `MessageKind`, with "uncategorized" for unclassified messages.
+    kind_counts: BTreeMap<&'static str, usize>,
+    error_count: usize,
+    unanswered_request_count: usize,
+    client: Option<ClientInfo>,
+    server: Option<ServerInfo>,
+    lifecycle: LifecycleHealth,
+}
+
+/// Flags describing how much of the LSP lifecycle handshake and teardown the session went through.
+#[derive(Default, Serialize)]
+pub(crate) struct LifecycleHealth {
+    initialize_requested: bool,
+    initialize_succeeded: bool,
+    initialized_sent: bool,
+    shutdown_requested: bool,
+    exit_sent: bool,
+    /// Whether `shutdown` was answered before `exit` arrived, as the spec requires.
+    clean_exit: bool,
+}
+
+pub(crate) async fn get_session_summary(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+) -> Result<Json<SessionSummary>, AppError> {
+    let session = sqlx::query!(
+        "SELECT id, start_time_stamp, end_time_stamp FROM sessions WHERE id = $1 LIMIT 1;",
+        session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let conversation =
+        crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    Ok(Json(summarize(
+        session.id,
+        session.start_time_stamp,
+        session.end_time_stamp,
+        &conversation,
+    )))
+}
+
+fn summarize(
+    session_id: i64,
+    start_time_stamp: OffsetDateTime,
+    end_time_stamp: Option<OffsetDateTime>,
+    conversation: &Conversation,
+) -> SessionSummary {
+    let mut request_count = 0;
+    let mut response_count = 0;
+    let mut notification_count = 0;
+    let mut error_count = 0;
+    let mut kind_counts = BTreeMap::new();
+
+    for message in conversation {
+        match &message.message {
+            Message::Request(_) => request_count += 1,
+            Message::Response(response) => {
+                response_count += 1;
+                if response.error.is_some() {
+                    error_count += 1;
+                }
+            }
+            Message::Notification(_) => notification_count += 1,
+        }
+
+        let kind = classify(&message.message, conversation)
+            .map(|kind| kind.as_str())
+            .unwrap_or("uncategorized");
+        *kind_counts.entry(kind).or_insert(0) += 1;
+    }
+
+    let pairs = conversation.pair_requests_with_responses();
+    let unanswered_request_count = pairs.iter().filter(|pair| pair.response.is_none()).count();
+
+    let mut lifecycle = LifecycleHealth::default();
+    let mut shutdown_answered_at = None;
+    for pair in pairs.iter() {
+        let Message::Request(request) = &pair.request.message else {
+            continue;
+        };
+
+        let successful_response = pair.response.filter(|response| {
+            matches!(&response.message, Message::Response(response) if response.error.is_none())
+        });
+
+        match request.method.as_str() {
+            Initialize::METHOD => {
+                lifecycle.initialize_requested = true;
+                lifecycle.initialize_succeeded |= successful_response.is_some();
+            }
+            Shutdown::METHOD => {
+                lifecycle.shutdown_requested = true;
+                shutdown_answered_at = shutdown_answered_at
+                    .or(successful_response.map(|response| response.time_stamp));
+            }
+            _ => {}
+        }
+    }
+
+    for message in conversation {
+        let Message::Notification(notification) = &message.message else {
+            continue;
+        };
+
+        match notification.method.as_str() {
+            Initialized::METHOD => lifecycle.initialized_sent = true,
+            Exit::METHOD => {
+                lifecycle.exit_sent = true;
+                lifecycle.clean_exit = shutdown_answered_at
+                    .is_some_and(|answered_at| answered_at <= message.time_stamp);
+            }
+            _ => {}
+        }
+    }
+
+    // Sessions that are still open are measured up to their most recent message.
+    let last_time_stamp = end_time_stamp.or_else(|| {
+        conversation
+            .messages()
+            .last()
+            .map(|message| message.time_stamp)
+    });
+
+    SessionSummary {
+        session_id,
+        start_time_stamp: get_rfc3339_string(&start_time_stamp),
+        end_time_stamp: end_time_stamp.as_ref().map(get_rfc3339_string),
+        duration_ms: last_time_stamp
+            .map(|last_time_stamp| (last_time_stamp - start_time_stamp).as_seconds_f64() * 1000.0),
+        request_count,
+        response_count,
+        notification_count,
+        kind_counts,
+        error_count,
+        unanswered_request_count,
+        client: conversation.get_client_info(),
+        server: conversation.get_server_info(),
+        lifecycle,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::MessageWithTimeStamp;
+
+    #[test]
+    fn summarizes_a_full_session() {
+        let conversation = Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "initialize",
+                    "params": { "capabilities": {}, "clientInfo": { "name": "editor" } },
+                }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                10,
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "result": { "capabilities": {}, "serverInfo": { "name": "server" } },
+                }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                20,
+                json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                4,
+                30,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                5,
+                40,
+                json!({ "jsonrpc": "2.0", "id": 2, "error": { "code": -32601, "message": "no" } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                6,
+                50,
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "shutdown" }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                7,
+                60,
+                json!({ "jsonrpc": "2.0", "id": 3, "result": null }),
+            ),
+            MessageWithTimeStamp::for_tests(8, 70, json!({ "jsonrpc": "2.0", "method": "exit" })),
+        ]);
+
+        let summary = summarize(
+            42,
+            OffsetDateTime::UNIX_EPOCH,
+            Some(OffsetDateTime::UNIX_EPOCH + time::Duration::milliseconds(100)),
+            &conversation,
+        );
+
+        assert_eq!(summary.session_id, 42);
+        assert_eq!(summary.duration_ms, Some(100.0));
+        assert_eq!(summary.request_count, 3);
+        assert_eq!(summary.response_count, 3);
+        assert_eq!(summary.notification_count, 2);
+        assert_eq!(summary.error_count, 1);
+        assert_eq!(summary.unanswered_request_count, 0);
+        assert_eq!(summary.kind_counts["hover"], 2);
+        assert_eq!(summary.client.unwrap().name, "editor");
+        assert_eq!(summary.server.unwrap().name, "server");
+        let lifecycle = summary.lifecycle;
+        assert!(lifecycle.initialize_requested);
+        assert!(lifecycle.initialize_succeeded);
+        assert!(lifecycle.initialized_sent);
+        assert!(lifecycle.shutdown_requested);
+        assert!(lifecycle.exit_sent);
+        assert!(lifecycle.clean_exit);
+    }
+}
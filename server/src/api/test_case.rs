@@ -0,0 +1,219 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+};
+use lsp_server::Message;
+use tracing::error;
+
+use crate::{
+    AppState,
+    error::AppError,
+    message::{Conversation, classify, get_source},
+};
+
+/// Exports a session as a Rust test module that embeds its messages and asserts how each one is
+/// currently classified and which side it's attributed to. A session that exposed a
+/// classification bug can be dropped into the test suite as a regression fixture, with the
+/// expectations corrected by hand.
+pub(crate) async fn get_session_test_case(
+    State(state): State<AppState>,
+    Path(session_id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    sqlx::query_scalar!("SELECT id FROM sessions WHERE id = $1 LIMIT 1;", session_id)
+        .fetch_one(&state.db)
+        .await
+        .map_err(|err| AppError::session_lookup(session_id, err))?;
+
+    let conversation =
+        crate::session::get_all_messages_for_session_in_chronological_order(&state.db, session_id)
+            .await
+            .map_err(|_| AppError::session_messages(session_id))?;
+
+    let test_case = generate_test_case(session_id, &conversation).map_err(|err| {
+        error!("Failed to export session {session_id} as a test case. Error: {err}");
+        AppError::internal(format!(
+            "failed to export session {session_id} as a test case"
+        ))
+    })?;
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                "text/x-rust; charset=utf-8".to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"session_{session_id}_regression.rs\""),
+            ),
+        ],
+        test_case,
+    ))
+}
+
+fn generate_test_case(
+    session_id: i64,
+    conversation: &Conversation,
+) -> Result<String, serde_json::Error> {
+    let messages = conversation
+        .messages()
+        .iter()
+        .map(|message| message.message.clone())
+        .collect::<Vec<Message>>();
+    let messages_json = serde_json::to_string_pretty(&messages)?;
+
+    // The raw string needs more `#`s than any run that follows a quote in the JSON.
+    let mut hashes = "#".to_string();
+    while messages_json.contains(&format!("\"{hashes}")) {
+        hashes.push('#');
+    }
+
+    let mut rust = String::new();
+    rust.push_str("// Exported from session ");
+    rust.push_str(&session_id.to_string());
+    rust.push_str(". The expectations record how the session was classified when it was\n");
+    rust.push_str(
+        "// exported; correct any that are wrong before adding this to the test suite.\n",
+    );
+    rust.push_str("#[cfg(test)]\n");
+    rust.push_str("mod session_");
+    rust.push_str(&session_id.to_string());
+    rust.push_str("_regression {\n");
+    rust.push_str("    use lsp_server::Message;\n");
+    rust.push_str("    use time::OffsetDateTime;\n");
+    rust.push('\n');
+    rust.push_str("    use crate::{\n");
+    rust.push_str("        message::{Conversation, MessageKind, classify, get_source},\n");
+    rust.push_str("        session::{MessageSource, MessageWithTimeStamp, ProxyTimeStamps},\n");
+    rust.push_str("    };\n");
+    rust.push('\n');
+    rust.push_str("    const MESSAGES: &str = r");
+    rust.push_str(&hashes);
+    rust.push('"');
+    rust.push_str(&messages_json);
+    rust.push('"');
+    rust.push_str(&hashes);
+    rust.push_str(";\n");
+    rust.push('\n');
+    rust.push_str("    #[test]\n");
+    rust.push_str("    fn classifies_messages_like_when_exported() {\n");
+    rust.push_str("        let messages = serde_json::from_str::<Vec<Message>>(MESSAGES)\n");
+    rust.push_str("            .expect(\"the embedded messages to parse\");\n");
+    rust.push_str("        let conversation = Conversation::from(\n");
+    rust.push_str("            messages\n");
+    rust.push_str("                .into_iter()\n");
+    rust.push_str("                .zip(1..)\n");
+    rust.push_str("                .map(|(message, seq)| MessageWithTimeStamp {\n");
+    rust.push_str("                    time_stamp: OffsetDateTime::UNIX_EPOCH,\n");
+    rust.push_str("                    seq,\n");
+    rust.push_str("                    proxy_time_stamps: ProxyTimeStamps::default(),\n");
+    rust.push_str("                    byte_size: None,\n");
+    rust.push_str("                    batch_id: None,\n");
+    rust.push_str("                    source: None,\n");
+    rust.push_str("                    message,\n");
+    rust.push_str("                })\n");
+    rust.push_str("                .collect::<Vec<_>>(),\n");
+    rust.push_str("        );\n");
+    rust.push('\n');
+    rust.push_str("        let expected: [(Option<MessageKind>, Option<MessageSource>); ");
+    rust.push_str(&messages.len().to_string());
+    rust.push_str("] = [\n");
+    for message in conversation {
+        let kind = classify(&message.message, conversation);
+        let source = get_source(&message.message, conversation);
+        rust.push_str("            (");
+        match kind {
+            Some(kind) => rust.push_str(&format!("Some(MessageKind::{kind:?})")),
+            None => rust.push_str("None"),
+        }
+        rust.push_str(", ");
+        match source {
+            Some(source) => rust.push_str(&format!("Some(MessageSource::{source:?})")),
+            None => rust.push_str("None"),
+        }
+        rust.push_str("),\n");
+    }
+    rust.push_str("        ];\n");
+    rust.push('\n');
+    rust.push_str("        assert_eq!(conversation.messages().len(), expected.len());\n");
+    rust.push_str("        for (index, (message, (kind, source))) in\n");
+    rust.push_str("            conversation.messages().iter().zip(expected).enumerate()\n");
+    rust.push_str("        {\n");
+    rust.push_str("            assert_eq!(classify(&message.message, &conversation), kind, \"kind of message {index}\");\n");
+    rust.push_str("            assert_eq!(get_source(&message.message, &conversation), source, \"source of message {index}\");\n");
+    rust.push_str("        }\n");
+    rust.push_str("    }\n");
+    rust.push_str("}\n");
+
+    Ok(rust)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::MessageWithTimeStamp;
+
+    fn get_fixture_conversation() -> Conversation {
+        Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                100,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": { "capabilities": { "hoverProvider": true } } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                200,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": { "textDocument": { "uri": "file:///main.rs" }, "position": { "line": 0, "character": 3 } } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                4,
+                300,
+                json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics", "params": { "uri": "file:///main.rs", "diagnostics": [] } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                5,
+                400,
+                json!({ "jsonrpc": "2.0", "method": "custom/\"#notification", "params": {} }),
+            ),
+        ])
+    }
+
+    // The checked in export of the fixture conversation, compiled and run as part of the suite.
+    include!("test_case/session_1_regression.rs");
+
+    #[test]
+    fn the_generated_test_case_is_the_compiled_fixture() {
+        let test_case = generate_test_case(1, &get_fixture_conversation()).unwrap();
+
+        assert_eq!(test_case, include_str!("test_case/session_1_regression.rs"));
+    }
+
+    #[test]
+    fn the_generated_test_case_embeds_the_messages() {
+        let conversation = get_fixture_conversation();
+        let test_case = generate_test_case(1, &conversation).unwrap();
+
+        let (_, embedded) = test_case.split_once("r##\"").unwrap();
+        let (embedded, _) = embedded.split_once("\"##;").unwrap();
+        let messages = serde_json::from_str::<Vec<Message>>(embedded).unwrap();
+        assert_eq!(
+            serde_json::to_value(messages).unwrap(),
+            serde_json::to_value(
+                conversation
+                    .messages()
+                    .iter()
+                    .map(|message| &message.message)
+                    .collect::<Vec<_>>()
+            )
+            .unwrap()
+        );
+    }
+}
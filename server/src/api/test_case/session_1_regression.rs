@@ -0,0 +1,91 @@
+// Exported from session 1. The expectations record how the session was classified when it was
+// exported; correct any that are wrong before adding this to the test suite.
+#[cfg(test)]
+mod session_1_regression {
+    use lsp_server::Message;
+    use time::OffsetDateTime;
+
+    use crate::{
+        message::{Conversation, MessageKind, classify, get_source},
+        session::{MessageSource, MessageWithTimeStamp, ProxyTimeStamps},
+    };
+
+    const MESSAGES: &str = r##"[
+  {
+    "id": 1,
+    "method": "initialize",
+    "params": {
+      "capabilities": {}
+    }
+  },
+  {
+    "id": 1,
+    "result": {
+      "capabilities": {
+        "hoverProvider": true
+      }
+    }
+  },
+  {
+    "id": 2,
+    "method": "textDocument/hover",
+    "params": {
+      "position": {
+        "character": 3,
+        "line": 0
+      },
+      "textDocument": {
+        "uri": "file:///main.rs"
+      }
+    }
+  },
+  {
+    "method": "textDocument/publishDiagnostics",
+    "params": {
+      "diagnostics": [],
+      "uri": "file:///main.rs"
+    }
+  },
+  {
+    "method": "custom/\"#notification",
+    "params": {}
+  }
+]"##;
+
+    #[test]
+    fn classifies_messages_like_when_exported() {
+        let messages = serde_json::from_str::<Vec<Message>>(MESSAGES)
+            .expect("the embedded messages to parse");
+        let conversation = Conversation::from(
+            messages
+                .into_iter()
+                .zip(1..)
+                .map(|(message, seq)| MessageWithTimeStamp {
+                    time_stamp: OffsetDateTime::UNIX_EPOCH,
+                    seq,
+                    proxy_time_stamps: ProxyTimeStamps::default(),
+                    byte_size: None,
+                    batch_id: None,
+                    source: None,
+                    message,
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let expected: [(Option<MessageKind>, Option<MessageSource>); 5] = [
+            (Some(MessageKind::Lifecycle), Some(MessageSource::Client)),
+            (Some(MessageKind::Lifecycle), Some(MessageSource::Server)),
+            (Some(MessageKind::Hover), Some(MessageSource::Client)),
+            (None, None),
+            (None, None),
+        ];
+
+        assert_eq!(conversation.messages().len(), expected.len());
+        for (index, (message, (kind, source))) in
+            conversation.messages().iter().zip(expected).enumerate()
+        {
+            assert_eq!(classify(&message.message, &conversation), kind, "kind of message {index}");
+            assert_eq!(get_source(&message.message, &conversation), source, "source of message {index}");
+        }
+    }
+}
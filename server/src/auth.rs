@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{AppState, error::AppError};
+
+/// Requires the viewer token, when one is configured, on the routes that read logged sessions.
+pub(crate) async fn require_viewer_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_token(state.config.auth_token.as_deref(), request, next).await
+}
+
+/// Requires the ingest token, when one is configured, on the routes that collectors log to.
+pub(crate) async fn require_ingest_token(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_token(state.config.ingest_token.as_deref(), request, next).await
+}
+
+async fn require_token(
+    token: Option<&str>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    match token {
+        Some(token) if !has_bearer_token(request.headers(), token) => Err(AppError::new(
+            StatusCode::UNAUTHORIZED,
+            "a valid bearer token is required",
+        )),
+        _ => Ok(next.run(request).await),
+    }
+}
+
+/// Whether the request's `Authorization` header holds `token` as a bearer token.
+pub(crate) fn has_bearer_token(headers: &HeaderMap, token: &str) -> bool {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|bearer_token| tokens_match(bearer_token, token))
+}
+
+/// Compares every byte of two tokens, so that how long a comparison takes doesn't reveal how
+/// much of a guess was right.
+fn tokens_match(token1: &str, token2: &str) -> bool {
+    token1.len() == token2.len()
+        && token1
+            .bytes()
+            .zip(token2.bytes())
+            .fold(0, |difference, (byte1, byte2)| difference | (byte1 ^ byte2))
+            == 0
+}
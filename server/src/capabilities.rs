@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use lsp_server::Message;
+use lsp_types::request::{
+    CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, CallHierarchyPrepare,
+    DocumentDiagnosticRequest, InlayHintRequest, InlineValueRequest, LinkedEditingRange,
+    MonikerRequest, Request as LspRequest, SemanticTokensFullDeltaRequest,
+    SemanticTokensFullRequest, SemanticTokensRangeRequest, TypeHierarchyPrepare,
+    TypeHierarchySubtypes, TypeHierarchySupertypes, WorkspaceDiagnosticRequest,
+};
+use serde_json::Value;
+
+use crate::message::Conversation;
+
+/// The `textDocument/inlineCompletion` request is only defined by `lsp_types` behind its
+/// `proposed` feature.
+const INLINE_COMPLETION_METHOD: &str = "textDocument/inlineCompletion";
+
+/// What a server has to advertise before a client may send it a request.
+#[derive(Clone, Copy)]
+pub(crate) struct MethodRequirement {
+    /// The LSP version that introduced the method.
+    pub(crate) since: &'static str,
+    /// The server capability that advertises support for the method.
+    pub(crate) capability: &'static str,
+}
+
+/// The requirement for client requests added to the protocol after 3.15. Older methods are left
+/// out, since servers commonly answer them without advertising anything.
+pub(crate) fn get_method_requirement(method: &str) -> Option<MethodRequirement> {
+    let (since, capability) = match method {
+        CallHierarchyPrepare::METHOD
+        | CallHierarchyIncomingCalls::METHOD
+        | CallHierarchyOutgoingCalls::METHOD => ("3.16", "callHierarchyProvider"),
+        SemanticTokensFullRequest::METHOD
+        | SemanticTokensFullDeltaRequest::METHOD
+        | SemanticTokensRangeRequest::METHOD => ("3.16", "semanticTokensProvider"),
+        LinkedEditingRange::METHOD => ("3.16", "linkedEditingRangeProvider"),
+        MonikerRequest::METHOD => ("3.16", "monikerProvider"),
+        TypeHierarchyPrepare::METHOD
+        | TypeHierarchySupertypes::METHOD
+        | TypeHierarchySubtypes::METHOD => ("3.17", "typeHierarchyProvider"),
+        InlineValueRequest::METHOD => ("3.17", "inlineValueProvider"),
+        InlayHintRequest::METHOD => ("3.17", "inlayHintProvider"),
+        DocumentDiagnosticRequest::METHOD | WorkspaceDiagnosticRequest::METHOD => {
+            ("3.17", "diagnosticProvider")
+        }
+        INLINE_COMPLETION_METHOD => ("3.18", "inlineCompletionProvider"),
+        _ => return None,
+    };
+    Some(MethodRequirement { since, capability })
+}
+
+/// A method the client used without the server having advertised support for it.
+pub(crate) struct UnadvertisedMethod<'a> {
+    pub(crate) method: &'a str,
+    pub(crate) requirement: MethodRequirement,
+    /// How many times the method was requested.
+    pub(crate) count: usize,
+}
+
+/// Finds the requests made for methods the server's `initialize` response didn't advertise,
+/// ordered by method. Returns `None` when the response wasn't logged, since there's nothing to
+/// check the requests against.
+pub(crate) fn find_unadvertised_methods(
+    conversation: &Conversation,
+) -> Option<Vec<UnadvertisedMethod<'_>>> {
+    let capabilities = conversation.get_server_capabilities()?;
+
+    let mut counts = BTreeMap::<&str, (MethodRequirement, usize)>::new();
+    for message in conversation {
+        let Message::Request(request) = &message.message else {
+            continue;
+        };
+        let Some(requirement) = get_method_requirement(&request.method) else {
+            continue;
+        };
+        // A capability that's present is advertised unless it's explicitly turned off.
+        let is_advertised = !matches!(
+            capabilities.get(requirement.capability),
+            None | Some(Value::Null) | Some(Value::Bool(false))
+        );
+        if !is_advertised {
+            counts.entry(&request.method).or_insert((requirement, 0)).1 += 1;
+        }
+    }
+
+    Some(
+        counts
+            .into_iter()
+            .map(|(method, (requirement, count))| UnadvertisedMethod {
+                method,
+                requirement,
+                count,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::MessageWithTimeStamp;
+
+    #[test]
+    fn an_inline_value_request_to_a_server_that_did_not_advertise_it_is_flagged() {
+        let conversation = Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                100,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": { "capabilities": { "hoverProvider": true, "inlayHintProvider": true } } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                200,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/inlineValue", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                4,
+                300,
+                json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/inlayHint", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                5,
+                400,
+                json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/inlineValue", "params": {} }),
+            ),
+        ]);
+
+        let unadvertised = find_unadvertised_methods(&conversation).unwrap();
+
+        assert_eq!(unadvertised.len(), 1);
+        assert_eq!(unadvertised[0].method, "textDocument/inlineValue");
+        assert_eq!(unadvertised[0].requirement.since, "3.17");
+        assert_eq!(
+            unadvertised[0].requirement.capability,
+            "inlineValueProvider"
+        );
+        assert_eq!(unadvertised[0].count, 2);
+    }
+}
@@ -0,0 +1,69 @@
+use std::io::{self, Read, Write};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde_json::Value;
+
+/// Gzips a payload's JSON when it's longer than `threshold` bytes. Smaller payloads are left for
+/// Postgres to store as they are, since compressing them saves little and costs every read.
+pub(crate) fn compress_payload(payload: &Value, threshold: usize) -> io::Result<Option<Vec<u8>>> {
+    let json = serde_json::to_vec(payload)?;
+    if json.len() <= threshold {
+        return Ok(None);
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&json)?;
+    encoder.finish().map(Some)
+}
+
+/// Reads back a payload stored by `compress_payload`.
+pub(crate) fn decompress_payload(compressed: &[u8]) -> io::Result<Value> {
+    let mut json = Vec::new();
+    GzDecoder::new(compressed).read_to_end(&mut json)?;
+    Ok(serde_json::from_slice(&json)?)
+}
+
+/// Whether a frame starts with the magic bytes of gzip.
+pub(crate) fn is_gzipped(frame: &[u8]) -> bool {
+    frame.starts_with(&[0x1f, 0x8b])
+}
+
+/// Gunzips a frame, refusing to inflate it past `max_size` bytes so that a small frame can't
+/// expand to exhaust memory.
+pub(crate) fn decompress_frame(compressed: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+    let mut frame = Vec::new();
+    GzDecoder::new(compressed)
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut frame)?;
+    if frame.len() > max_size {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the frame is larger than {max_size} bytes once decompressed"),
+        ));
+    }
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn large_payloads_round_trip_through_compression() {
+        let payload = json!({ "data": vec![1; 1000] });
+
+        let compressed = compress_payload(&payload, 100).unwrap().unwrap();
+
+        assert_eq!(&compressed[..2], [0x1f, 0x8b]);
+        assert_eq!(decompress_payload(&compressed).unwrap(), payload);
+    }
+
+    #[test]
+    fn payloads_within_the_threshold_are_left_uncompressed() {
+        let payload = json!({ "data": [1, 2, 3] });
+
+        assert!(compress_payload(&payload, 100).unwrap().is_none());
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv6Addr},
+    path::PathBuf,
+    time::Duration,
+};
+
+use crate::html::theme::Theme;
+
+/// Server settings read from the environment at startup.
+pub(crate) struct Config {
+    /// The color theme used when a page doesn't request one. Set with `LLS_THEME`.
+    pub(crate) default_theme: Theme,
+    /// Which methods are stored at ingest. Set with either `LLS_INGEST_ALLOW` or
+    /// `LLS_INGEST_DENY` as a comma separated list of method names.
+    pub(crate) method_filter: MethodFilter,
+    /// A Unix domain socket to accept framed LSP messages on, alongside the WebSocket endpoint.
+    /// Set with `LLS_UDS_PATH`.
+    pub(crate) uds_path: Option<PathBuf>,
+    /// Ends sessions left open by clients that vanished without closing their connection.
+    /// Enabled by setting `LLS_SESSION_IDLE_TIMEOUT_SECS`.
+    pub(crate) session_sweep: Option<SessionSweepConfig>,
+    /// Inline the stylesheets into every page instead of linking to `/static`, for deployments
+    /// that need each page to be self contained. Set with `LLS_INLINE_CSS`.
+    pub(crate) inline_css: bool,
+    /// How many of the preceding sessions to search for the requests of responses that don't
+    /// have one in their own session. Disabled unless `LLS_CROSS_SESSION_LOOKUP_SESSIONS` is set.
+    pub(crate) cross_session_lookup_sessions: Option<i64>,
+    /// Stores only a sample of the notifications of chatty methods. Enabled by setting
+    /// `LLS_SAMPLE_RATE`.
+    pub(crate) sampling: Option<SamplingConfig>,
+    /// Store the bytes of each message exactly as they were received, alongside the parsed
+    /// payload. Set with `LLS_STORE_RAW`.
+    pub(crate) store_raw: bool,
+    /// Gzip the params and results whose JSON is longer than this many bytes before storing
+    /// them. Enabled by setting `LLS_COMPRESS_PAYLOADS`, with the length set by
+    /// `LLS_COMPRESSION_THRESHOLD`, defaulting to 4 KiB.
+    pub(crate) compression_threshold: Option<usize>,
+    /// Also write every log to stdout as a line of JSON, alongside storing it in Postgres. Set
+    /// with `LLS_STDOUT_LOGS`.
+    pub(crate) stdout_logs: bool,
+    /// Frees the payloads of old messages on a schedule. Enabled by setting either
+    /// `LLS_RAW_RETENTION_DAYS` or `LLS_PAYLOAD_RETENTION_DAYS`.
+    pub(crate) retention: Option<RetentionConfig>,
+    /// The deepest page of the sessions list that may be requested by page number, since each
+    /// page skips all of the sessions before it. Pages reached through the next and previous
+    /// links aren't limited. Set with `LLS_MAX_SESSION_PAGE`.
+    pub(crate) max_session_page: Option<usize>,
+    /// The bearer token that destructive endpoints, like deleting or compacting sessions, require.
+    /// Those endpoints are disabled unless `LLS_ADMIN_TOKEN` is set.
+    pub(crate) admin_token: Option<String>,
+    /// The bearer token required to view logged sessions, through the HTML pages and the
+    /// query and export endpoints. They're open to anyone unless `LLS_AUTH_TOKEN` is set.
+    pub(crate) auth_token: Option<String>,
+    /// The bearer token collectors need to log messages over `/ws`, `/log` and `POST /sessions`.
+    /// Logging is open to anyone unless `LLS_INGEST_TOKEN` is set.
+    pub(crate) ingest_token: Option<String>,
+    /// The address to serve HTTP on. Set with `LLS_BIND_ADDR`, defaulting to every interface.
+    pub(crate) bind_addr: IpAddr,
+    /// The port to serve HTTP on. Set with `LLS_PORT`, defaulting to 8080.
+    pub(crate) port: u16,
+    /// The most connections to hold open to Postgres at once. Set with `LLS_MAX_DB_CONNECTIONS`,
+    /// defaulting to 10.
+    pub(crate) max_db_connections: u32,
+    /// How many logs may wait to be written to Postgres before new ones are dropped. Set with
+    /// `LLS_LOG_QUEUE_CAPACITY`, defaulting to 10000.
+    pub(crate) log_queue_capacity: usize,
+    /// How many tasks write queued logs to Postgres, each holding at most one connection. Set
+    /// with `LLS_LOG_WRITERS`, defaulting to 2.
+    pub(crate) log_writers: usize,
+    /// The largest `Content-Length` framed message read from the Unix domain socket or from a
+    /// replayed server, headers included, so that one bad header can't exhaust memory. Gzipped
+    /// WebSocket frames are also refused once they inflate past it. Set with
+    /// `LLS_MAX_FRAME_SIZE` in bytes, defaulting to 64 MiB like WebSocket messages.
+    pub(crate) max_frame_size: usize,
+    /// How WebSocket connections that stop sending frames without closing are detected.
+    pub(crate) websocket_liveness: WebSocketLivenessConfig,
+}
+
+pub(crate) struct WebSocketLivenessConfig {
+    /// How often connections are pinged. Set with `LLS_WS_PING_INTERVAL_SECS`, defaulting to 20
+    /// seconds.
+    pub(crate) ping_interval: Duration,
+    /// How long a connection may go without sending any frame, pongs included, before its
+    /// session is ended as timed out. Set with `LLS_WS_TIMEOUT_SECS`, defaulting to a minute.
+    pub(crate) timeout: Duration,
+}
+
+pub(crate) struct SessionSweepConfig {
+    /// How long a session may go without messages before it's ended.
+    pub(crate) idle_timeout: Duration,
+    /// How often to look for idle sessions. Set with `LLS_SESSION_SWEEP_INTERVAL_SECS`,
+    /// defaulting to a minute.
+    pub(crate) interval: Duration,
+}
+
+pub(crate) struct RetentionConfig {
+    /// How long the raw bytes of a message are kept. Set with `LLS_RAW_RETENTION_DAYS`.
+    pub(crate) raw_max_age: Option<Duration>,
+    /// How long after a session ends its parsed payloads are kept, before it's compacted. Set
+    /// with `LLS_PAYLOAD_RETENTION_DAYS`.
+    pub(crate) payload_max_age: Option<Duration>,
+}
+
+pub(crate) struct SamplingConfig {
+    /// One in this many notifications of a sampled method is stored.
+    pub(crate) rate: u32,
+    /// The notification methods to sample. Set with `LLS_SAMPLE_METHODS` as a comma separated
+    /// list of method names, defaulting to `$/progress`.
+    pub(crate) methods: HashSet<String>,
+}
+
+impl SamplingConfig {
+    pub(crate) fn samples(&self, method: &str) -> bool {
+        self.methods.contains(method)
+    }
+}
+
+/// Restricts which messages are written to the database, by method.
+#[derive(Default)]
+pub(crate) enum MethodFilter {
+    #[default]
+    All,
+    Allow(HashSet<String>),
+    Deny(HashSet<String>),
+}
+
+impl MethodFilter {
+    pub(crate) fn allows(&self, method: &str) -> bool {
+        match self {
+            MethodFilter::All => true,
+            MethodFilter::Allow(methods) => methods.contains(method),
+            MethodFilter::Deny(methods) => !methods.contains(method),
+        }
+    }
+}
+
+impl Config {
+    pub(crate) fn from_env() -> Result<Self, String> {
+        let allow = parse_env("LLS_INGEST_ALLOW", parse_method_list)?;
+        let deny = parse_env("LLS_INGEST_DENY", parse_method_list)?;
+        let method_filter = match (allow, deny) {
+            (Some(_), Some(_)) => {
+                return Err("LLS_INGEST_ALLOW and LLS_INGEST_DENY cannot both be set".to_string());
+            }
+            (Some(allow), None) => MethodFilter::Allow(allow),
+            (None, Some(deny)) => MethodFilter::Deny(deny),
+            (None, None) => MethodFilter::All,
+        };
+
+        let session_sweep = match parse_env("LLS_SESSION_IDLE_TIMEOUT_SECS", parse_seconds)? {
+            Some(idle_timeout) => Some(SessionSweepConfig {
+                idle_timeout,
+                interval: parse_env("LLS_SESSION_SWEEP_INTERVAL_SECS", parse_seconds)?
+                    .unwrap_or(Duration::from_secs(60)),
+            }),
+            None => None,
+        };
+
+        let sampling = match parse_env("LLS_SAMPLE_RATE", |value| {
+            value.parse::<u32>().ok().filter(|rate| *rate > 1)
+        })? {
+            Some(rate) => Some(SamplingConfig {
+                rate,
+                methods: parse_env("LLS_SAMPLE_METHODS", parse_method_list)?
+                    .unwrap_or_else(|| HashSet::from(["$/progress".to_string()])),
+            }),
+            None => None,
+        };
+
+        let compression_threshold =
+            match parse_env("LLS_COMPRESS_PAYLOADS", parse_bool)?.unwrap_or(false) {
+                true => Some(
+                    parse_env("LLS_COMPRESSION_THRESHOLD", |value| value.parse().ok())?
+                        .unwrap_or(4 << 10),
+                ),
+                false => None,
+            };
+
+        let raw_max_age = parse_env("LLS_RAW_RETENTION_DAYS", parse_days)?;
+        let payload_max_age = parse_env("LLS_PAYLOAD_RETENTION_DAYS", parse_days)?;
+        let retention = match (raw_max_age, payload_max_age) {
+            (None, None) => None,
+            // Raw bytes are only useful alongside the parsed view, so they can't outlive it.
+            (Some(raw), Some(payload)) if raw >= payload => {
+                return Err(
+                    "LLS_RAW_RETENTION_DAYS must be less than LLS_PAYLOAD_RETENTION_DAYS"
+                        .to_string(),
+                );
+            }
+            (raw_max_age, payload_max_age) => Some(RetentionConfig {
+                raw_max_age,
+                payload_max_age,
+            }),
+        };
+
+        let websocket_liveness = WebSocketLivenessConfig {
+            ping_interval: parse_env("LLS_WS_PING_INTERVAL_SECS", parse_seconds)?
+                .unwrap_or(Duration::from_secs(20)),
+            timeout: parse_env("LLS_WS_TIMEOUT_SECS", parse_seconds)?
+                .unwrap_or(Duration::from_secs(60)),
+        };
+        // Otherwise a connection could time out before it was ever pinged.
+        if websocket_liveness.ping_interval >= websocket_liveness.timeout {
+            return Err(
+                "LLS_WS_PING_INTERVAL_SECS must be less than LLS_WS_TIMEOUT_SECS".to_string(),
+            );
+        }
+
+        let max_db_connections = parse_env("LLS_MAX_DB_CONNECTIONS", |value| {
+            value
+                .parse::<u32>()
+                .ok()
+                .filter(|connections| *connections > 0)
+        })?
+        .unwrap_or(10);
+        let log_writers = parse_env("LLS_LOG_WRITERS", |value| {
+            value.parse::<usize>().ok().filter(|writers| *writers > 0)
+        })?
+        .unwrap_or(2);
+        // Otherwise a burst of logs could take every connection from the requests being logged.
+        if log_writers >= max_db_connections as usize {
+            return Err("LLS_LOG_WRITERS must be less than LLS_MAX_DB_CONNECTIONS".to_string());
+        }
+
+        Ok(Self {
+            default_theme: parse_env("LLS_THEME", Theme::try_parse_str)?.unwrap_or_default(),
+            method_filter,
+            uds_path: parse_env("LLS_UDS_PATH", |value| Some(PathBuf::from(value)))?,
+            session_sweep,
+            inline_css: parse_env("LLS_INLINE_CSS", parse_bool)?.unwrap_or(false),
+            cross_session_lookup_sessions: parse_env(
+                "LLS_CROSS_SESSION_LOOKUP_SESSIONS",
+                |value| value.parse::<i64>().ok().filter(|sessions| *sessions > 0),
+            )?,
+            sampling,
+            store_raw: parse_env("LLS_STORE_RAW", parse_bool)?.unwrap_or(false),
+            compression_threshold,
+            stdout_logs: parse_env("LLS_STDOUT_LOGS", parse_bool)?.unwrap_or(false),
+            retention,
+            max_session_page: parse_env("LLS_MAX_SESSION_PAGE", |value| value.parse().ok())?,
+            admin_token: parse_env("LLS_ADMIN_TOKEN", |value| Some(value.to_string()))?,
+            auth_token: parse_env("LLS_AUTH_TOKEN", |value| Some(value.to_string()))?,
+            ingest_token: parse_env("LLS_INGEST_TOKEN", |value| Some(value.to_string()))?,
+            bind_addr: parse_env("LLS_BIND_ADDR", |value| value.parse().ok())?
+                .unwrap_or(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+            port: parse_env("LLS_PORT", |value| value.parse().ok())?.unwrap_or(8080),
+            max_db_connections,
+            log_queue_capacity: parse_env("LLS_LOG_QUEUE_CAPACITY", |value| {
+                value.parse::<usize>().ok().filter(|capacity| *capacity > 0)
+            })?
+            .unwrap_or(10_000),
+            log_writers,
+            max_frame_size: parse_env("LLS_MAX_FRAME_SIZE", |value| {
+                value.parse::<usize>().ok().filter(|size| *size > 0)
+            })?
+            .unwrap_or(64 << 20),
+            websocket_liveness,
+        })
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_seconds(value: &str) -> Option<Duration> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+}
+
+fn parse_days(value: &str) -> Option<Duration> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|days| *days > 0)
+        .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+}
+
+fn parse_method_list(value: &str) -> Option<HashSet<String>> {
+    Some(
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// Reads and parses an environment variable. Unset or empty variables produce `None`, while
+/// values that fail to parse are reported as an error rather than silently ignored.
+fn parse_env<T>(name: &str, parse: impl Fn(&str) -> Option<T>) -> Result<Option<T>, String> {
+    match std::env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => parse(value.trim())
+            .map(Some)
+            .ok_or_else(|| format!("{name} has an invalid value: {value}")),
+        Err(std::env::VarError::NotPresent) => Ok(None),
+        Err(err) => Err(format!("{name} could not be read. Error: {err}")),
+    }
+}
@@ -0,0 +1,214 @@
+use lsp_server::Message;
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, Position,
+    PositionEncodingKind, TextDocumentContentChangeEvent,
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification,
+    },
+};
+
+use crate::message::Conversation;
+
+/// The contents of a text document as the server would have seen them at some point in a
+/// conversation.
+pub(crate) struct DocumentState {
+    pub(crate) language_id: Option<String>,
+    pub(crate) version: i32,
+    pub(crate) text: String,
+    /// The `seq` of the last message that changed the document.
+    pub(crate) seq: i64,
+}
+
+/// Replays the `didOpen`, `didChange` and `didClose` notifications for `uri` up to and including
+/// the message with `up_to_seq` (or the whole conversation when it's `None`). Returns `None` if
+/// the document wasn't open at that point.
+///
+/// A document that was opened before logging began can still be reconstructed once a full
+/// document change arrives. Incremental changes to a document we haven't seen are skipped.
+pub(crate) fn reconstruct_document(
+    conversation: &Conversation,
+    uri: &str,
+    up_to_seq: Option<i64>,
+) -> Option<DocumentState> {
+    let encoding = conversation.get_position_encoding();
+    let mut document: Option<DocumentState> = None;
+
+    for message in conversation {
+        if up_to_seq.is_some_and(|up_to_seq| message.seq > up_to_seq) {
+            break;
+        }
+
+        let Message::Notification(notification) = &message.message else {
+            continue;
+        };
+
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(
+                    notification.params.clone(),
+                ) else {
+                    continue;
+                };
+                if params.text_document.uri.as_str() != uri {
+                    continue;
+                }
+
+                document = Some(DocumentState {
+                    language_id: Some(params.text_document.language_id),
+                    version: params.text_document.version,
+                    text: params.text_document.text,
+                    seq: message.seq,
+                });
+            }
+            DidChangeTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidChangeTextDocumentParams>(
+                    notification.params.clone(),
+                ) else {
+                    continue;
+                };
+                if params.text_document.uri.as_str() != uri {
+                    continue;
+                }
+
+                for change in params.content_changes {
+                    match (&mut document, change.range) {
+                        (Some(document), Some(_)) => {
+                            apply_incremental_change(&mut document.text, &change, &encoding);
+                        }
+                        (Some(document), None) => document.text = change.text,
+                        (None, Some(_)) => {}
+                        (None, None) => {
+                            document = Some(DocumentState {
+                                language_id: None,
+                                version: params.text_document.version,
+                                text: change.text,
+                                seq: message.seq,
+                            });
+                        }
+                    }
+                }
+
+                if let Some(document) = &mut document {
+                    document.version = params.text_document.version;
+                    document.seq = message.seq;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Ok(params) = serde_json::from_value::<DidCloseTextDocumentParams>(
+                    notification.params.clone(),
+                ) else {
+                    continue;
+                };
+                if params.text_document.uri.as_str() == uri {
+                    document = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    document
+}
+
+fn apply_incremental_change(
+    text: &mut String,
+    change: &TextDocumentContentChangeEvent,
+    encoding: &PositionEncodingKind,
+) {
+    let Some(range) = change.range else {
+        return;
+    };
+
+    let start = get_byte_offset(text, &range.start, encoding);
+    let end = get_byte_offset(text, &range.end, encoding).max(start);
+    text.replace_range(start..end, &change.text);
+}
+
+/// Converts an LSP position into a byte offset into `text`. Positions past the end of a line
+/// resolve to the end of that line, and lines past the end of the document resolve to the end of
+/// the document, as the spec requires.
+fn get_byte_offset(text: &str, position: &Position, encoding: &PositionEncodingKind) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        let rest = &text[line_start..];
+        match rest.find(['\n', '\r']) {
+            Some(index) => {
+                line_start += index + 1;
+                if rest[index..].starts_with("\r\n") {
+                    line_start += 1;
+                }
+            }
+            None => return text.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (index, char) in text[line_start..].char_indices() {
+        if units >= position.character as usize || char == '\n' || char == '\r' {
+            return line_start + index;
+        }
+
+        units += if *encoding == PositionEncodingKind::UTF8 {
+            char.len_utf8()
+        } else if *encoding == PositionEncodingKind::UTF32 {
+            1
+        } else {
+            char.len_utf16()
+        };
+    }
+
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::MessageWithTimeStamp;
+
+    #[test]
+    fn an_incremental_change_is_applied_to_the_open_document() {
+        let uri = "file:///main.rs";
+        let conversation = Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": { "textDocument": {
+                        "uri": uri,
+                        "languageId": "rust",
+                        "version": 1,
+                        "text": "fn main() {\n    let x = 1;\n}\n",
+                    } },
+                }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                100,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": { "uri": uri, "version": 2 },
+                        "contentChanges": [{
+                            "range": {
+                                "start": { "line": 1, "character": 8 },
+                                "end": { "line": 1, "character": 9 },
+                            },
+                            "text": "answer",
+                        }],
+                    },
+                }),
+            ),
+        ]);
+
+        let document = reconstruct_document(&conversation, uri, None).unwrap();
+
+        assert_eq!(document.text, "fn main() {\n    let answer = 1;\n}\n");
+        assert_eq!(document.version, 2);
+        assert_eq!(document.seq, 2);
+    }
+}
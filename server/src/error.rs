@@ -0,0 +1,141 @@
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{StatusCode, header::ACCEPT},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use serde_json::json;
+
+use crate::{AppState, html::stylesheet::Stylesheet};
+
+/// An error that a handler fails with, saying what went wrong. On its own it responds with its
+/// message as plain text, and `render_errors` renders it for whoever asked instead.
+#[derive(Clone, Debug)]
+pub(crate) struct AppError {
+    status: StatusCode,
+    message: String,
+}
+
+impl AppError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub(crate) fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub(crate) fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    /// The error for a session that failed to be looked up by its id, which is a 404 when
+    /// there's no such session.
+    pub(crate) fn session_lookup(session_id: i64, err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => Self::not_found(format!("session {session_id} not found")),
+            _ => Self::internal(format!("failed to look up session {session_id}")),
+        }
+    }
+
+    /// The error for the messages of a session that failed to be read.
+    pub(crate) fn session_messages(session_id: i64) -> Self {
+        Self::internal(format!(
+            "failed to read the messages of session {session_id}"
+        ))
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let mut response = (self.status, self.message.clone()).into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// The formats that an error can be rendered in.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ErrorFormat {
+    Html,
+    Json,
+}
+
+/// Picks the first of the formats an error can be rendered in that the `Accept` header lists.
+/// Quality values are ignored, since clients list what they prefer first.
+fn get_error_format(accept: &str) -> Option<ErrorFormat> {
+    accept.split(',').find_map(|media_range| {
+        let media_type = media_range.split(';').next().unwrap_or_default().trim();
+        if media_type.eq_ignore_ascii_case("application/json") {
+            Some(ErrorFormat::Json)
+        } else if media_type.eq_ignore_ascii_case("text/html") {
+            Some(ErrorFormat::Html)
+        } else {
+            None
+        }
+    })
+}
+
+/// Renders the `AppError`s that handlers fail with as an HTML page for browsers, or as
+/// `{ "error": message }` for clients that accept JSON. Clients that accept neither get the
+/// plain text message.
+pub(crate) async fn render_errors(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let format = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .and_then(get_error_format);
+    let mut response = next.run(request).await;
+    let Some(format) = format else {
+        return response;
+    };
+    let Some(error) = response.extensions_mut().remove::<AppError>() else {
+        return response;
+    };
+
+    match format {
+        ErrorFormat::Json => {
+            (error.status, Json(json!({ "error": error.message }))).into_response()
+        }
+        ErrorFormat::Html => {
+            let mut html = String::new();
+            html.push_str("<!DOCTYPE=html>");
+            html.push_str("<html>");
+
+            html.push_str("<head>");
+            html.push_str("<meta charset=\"UTF-8\"/>");
+            html.push_str("<title>LSP Analyzer</title>");
+            html.push_str("</head>");
+
+            html.push_str("<body>");
+            Stylesheet::Sessions.append_to(&mut html, &state.config);
+            html.push_str("<h1>");
+            html.push_str(error.status.as_str());
+            if let Some(reason) = error.status.canonical_reason() {
+                html.push(' ');
+                html.push_str(reason);
+            }
+            html.push_str("</h1>");
+            html.push_str("<p>");
+            html.push_str(&html_escape::encode_text(&error.message));
+            html.push_str("</p>");
+            html.push_str("<a href=\"/\">Back to sessions</a>");
+            html.push_str("</body>");
+            html.push_str("</html>");
+
+            (error.status, Html(html)).into_response()
+        }
+    }
+}
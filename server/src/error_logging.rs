@@ -1,21 +1,212 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
 use serde_json::{Map, Value};
 use sqlx::PgPool;
+use tokio::sync::{Mutex, OnceCell, mpsc};
 use tracing::{Event, Level, field::Visit, span};
-use tracing_subscriber::{Layer, layer::Context};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{
+    config::Config,
+    metrics::Metrics,
+    tasks::{RunningTask, TaskTracker},
+    utils::get_iso_string,
+};
+
+/// How many times a log's insert is attempted before the log is dropped.
+const WRITE_ATTEMPTS: u32 = 3;
+/// How long to wait before retrying a failed insert, doubled after each attempt.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Stores logs in Postgres. Logs are queued for a few writer tasks rather than each inserted by
+/// a task of its own, so that a burst of them takes a bounded amount of memory and connections.
+pub struct PostgresLayer {
+    queue: mpsc::Sender<QueuedLog>,
+    /// Tracks the insert of each queued log, so that they can finish before the server exits.
+    tasks: TaskTracker,
+    metrics: Arc<Metrics>,
+}
+
+impl PostgresLayer {
+    /// Starts the configured number of writer tasks.
+    pub(crate) fn new(
+        pool: PgPool,
+        tasks: TaskTracker,
+        metrics: Arc<Metrics>,
+        config: &Config,
+    ) -> Self {
+        let (queue, receiver) = mpsc::channel(config.log_queue_capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..config.log_writers {
+            tokio::spawn(write_queued_logs(
+                pool.clone(),
+                receiver.clone(),
+                metrics.clone(),
+            ));
+        }
+        Self {
+            queue,
+            tasks,
+            metrics,
+        }
+    }
+}
+
+/// A log waiting to be written, along with the innermost span it was emitted in.
+struct QueuedLog {
+    entry: LogEntry,
+    span: Option<Arc<PostgresSpan>>,
+    /// Counts the log as a running write until it's written or dropped.
+    _running_task: RunningTask,
+}
+
+/// Writes logs from the queue until it's closed.
+async fn write_queued_logs(
+    pool: PgPool,
+    receiver: Arc<Mutex<mpsc::Receiver<QueuedLog>>>,
+    metrics: Arc<Metrics>,
+) {
+    loop {
+        // Only one writer waits on the queue at a time, and it lets go before writing.
+        let Some(log) = receiver.lock().await.recv().await else {
+            return;
+        };
+        write_log(&pool, log, &metrics).await;
+    }
+}
+
+/// Inserts a log, retrying inserts that fail. Failures are reported on stderr rather than
+/// logged, since logging them would queue yet another log to write.
+async fn write_log(pool: &PgPool, log: QueuedLog, metrics: &Metrics) {
+    let span_id = match &log.span {
+        Some(span) => span.get_row_id(pool).await,
+        None => None,
+    };
+    let entry = log.entry;
+    let fields = Value::Object(entry.fields);
+
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=WRITE_ATTEMPTS {
+        let insert = sqlx::query(
+            "INSERT INTO logs (session_id, time_stamp, message, fields, span_id) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(entry.session_id)
+        .bind(entry.time_stamp)
+        .bind(&entry.message)
+        .bind(&fields)
+        .bind(span_id)
+        .execute(pool)
+        .await;
+
+        match insert {
+            Ok(_) => return,
+            Err(err) if attempt < WRITE_ATTEMPTS => {
+                eprintln!("Failed to write a log, retrying in {delay:?}. Error: {err}");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(err) => {
+                eprintln!(
+                    "Failed to write a log after {WRITE_ATTEMPTS} attempts, dropping it. Error: {err}"
+                );
+                metrics.record_dropped_log();
+            }
+        }
+    }
+}
 
-pub struct PostgresLayer(PgPool);
+/// A span's fields along with the row it's stored in. Spans are only written once something is
+/// logged within them, and then only once no matter how many events they enclose.
+struct PostgresSpan {
+    parent: Option<Arc<PostgresSpan>>,
+    /// How many spans this one is nested within.
+    depth: i32,
+    name: &'static str,
+    level: i32,
+    fields: Map<String, Value>,
+    row_id: OnceCell<Option<i64>>,
+}
+
+impl PostgresSpan {
+    /// Returns the id of the span's row, inserting it and its parents the first time it's
+    /// needed. A span that fails to be written is left out of the hierarchy rather than retried.
+    async fn get_row_id(&self, pool: &PgPool) -> Option<i64> {
+        *self
+            .row_id
+            .get_or_init(|| async {
+                let parent_id = match &self.parent {
+                    Some(parent) => Box::pin(parent.get_row_id(pool)).await,
+                    None => None,
+                };
+
+                sqlx::query_scalar::<_, i64>(
+                    "INSERT INTO log_spans (index, name, level, fields, parent_id) VALUES ($1, $2, $3, $4, $5) RETURNING id",
+                )
+                .bind(self.depth)
+                .bind(self.name)
+                .bind(self.level)
+                .bind(Value::Object(self.fields.clone()))
+                .bind(parent_id)
+                .fetch_one(pool)
+                .await
+                .ok()
+            })
+            .await
+    }
 
-impl From<PgPool> for PostgresLayer {
-    fn from(value: PgPool) -> Self {
-        Self(value)
+    /// Finds the `session_id` of the nearest span that has one.
+    fn get_session_id(&self) -> Option<i32> {
+        self.fields
+            .get("session_id")
+            .and_then(parse_session_id)
+            .or_else(|| self.parent.as_ref()?.get_session_id())
     }
 }
 
-struct PostgresFieldStorage(Value);
+/// What's logged of an event, by every layer that logs them.
+struct LogEntry {
+    /// When the event was emitted. It's read in `on_event` rather than once a log is taken off
+    /// the queue, so that logs keep their order relative to each other and to the messages they
+    /// describe.
+    time_stamp: time::OffsetDateTime,
+    session_id: Option<i32>,
+    message: String,
+    fields: Map<String, Value>,
+}
+
+impl LogEntry {
+    /// Reads an event, falling back to `get_span_session_id` for the session of events that
+    /// don't name one themselves.
+    fn new(event: &Event<'_>, get_span_session_id: impl FnOnce() -> Option<i32>) -> Self {
+        let mut fields = Map::new();
+        let mut visitor = Visitor(&mut fields);
+        event.record(&mut visitor);
+
+        let session_id = fields
+            .get("session_id")
+            .and_then(parse_session_id)
+            .or_else(get_span_session_id);
+
+        // Events without a message, which dependencies are free to emit, are described by their
+        // remaining fields instead.
+        let message = match fields.remove("message") {
+            Some(Value::String(message)) => message,
+            Some(message) => message.to_string(),
+            None => describe_fields(&fields),
+        };
+
+        Self {
+            time_stamp: time::OffsetDateTime::now_utc(),
+            session_id,
+            message,
+            fields,
+        }
+    }
+}
 
 impl<S> Layer<S> for PostgresLayer
 where
-    S: tracing::Subscriber + for<'lookup> tracing_subscriber::registry::LookupSpan<'lookup>,
+    S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
 {
     fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
         // Build our json object from the field values like we have been
@@ -23,132 +214,138 @@ where
         let mut visitor = Visitor(&mut fields);
         attrs.record(&mut visitor);
 
-        // And stuff it in our newtype.
-        let storage = PostgresFieldStorage(Value::Object(fields));
-
         // Get a reference to the internal span data
         let span = ctx.span(id).unwrap();
+        let parent = span
+            .parent()
+            .and_then(|parent| parent.extensions().get::<Arc<PostgresSpan>>().cloned());
+
+        let storage = PostgresSpan {
+            depth: parent.as_ref().map_or(0, |parent| parent.depth + 1),
+            parent,
+            name: span.name(),
+            level: match *span.metadata().level() {
+                Level::TRACE => 0,
+                Level::DEBUG => 1,
+                Level::INFO => 2,
+                Level::WARN => 3,
+                Level::ERROR => 4,
+            },
+            fields,
+            row_id: OnceCell::new(),
+        };
+
         // Get the special place where tracing stores custom data
         let mut extensions = span.extensions_mut();
         // And store our data
-        extensions.insert::<PostgresFieldStorage>(storage);
+        extensions.insert(Arc::new(storage));
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        // The fields of the event
+        // The innermost span the event was emitted in, which leads to all of the others.
+        let span = ctx
+            .event_span(event)
+            .and_then(|span| span.extensions().get::<Arc<PostgresSpan>>().cloned());
+
+        let log = QueuedLog {
+            entry: LogEntry::new(event, || span.as_ref()?.get_session_id()),
+            span,
+            _running_task: self.tasks.track(),
+        };
+
+        // Waiting for room would hold up whatever emitted the event, which may be the ingest of
+        // a message, so logs that don't fit are dropped instead.
+        if self.queue.try_send(log).is_err() {
+            self.metrics.record_dropped_log();
+        }
+    }
+}
+
+/// Writes each event to stdout as a line of JSON, with the same fields that are stored in
+/// Postgres, for deployments that collect logs from their output. Events are written as they
+/// happen, so they're kept even when storing them in Postgres fails.
+pub struct StdoutLayer;
+
+/// The fields of a span, kept for the events logged within it.
+struct StdoutSpan {
+    fields: Map<String, Value>,
+}
+
+impl<S> Layer<S> for StdoutLayer
+where
+    S: tracing::Subscriber + for<'lookup> LookupSpan<'lookup>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
         let mut fields = Map::new();
         let mut visitor = Visitor(&mut fields);
-        event.record(&mut visitor);
+        attrs.record(&mut visitor);
 
-        let mut session_id = fields
-            .get("session_id")
-            .map(|value| match value {
-                Value::Null => None,
-                Value::Bool(_) => panic!("session_id should never be a bool"),
-                Value::Number(number) => number.as_i64(),
-                Value::String(id) => id.parse::<i64>().ok(),
-                Value::Array(_) => panic!("session_id should never be an object"),
-                Value::Object(_) => panic!("session_id should never be an array"),
-            })
-            .flatten()
-            .map(|id| {
-                if id > (i32::MAX as i64) {
-                    None
-                } else if id < (i32::MIN as i64) {
-                    None
-                } else {
-                    Some(id as i32)
-                }
-            })
-            .flatten();
+        let span = ctx.span(id).unwrap();
+        span.extensions_mut().insert(StdoutSpan { fields });
+    }
 
-        // All of the span context
-        let scope = ctx.event_scope(event);
-        if let Some(scope) = scope {
-            for (i, span) in scope.from_root().enumerate() {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // The spans the event was emitted in, outermost first.
+        let mut spans = Vec::new();
+        let mut span_session_id = None;
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
                 let extensions = span.extensions();
-                let storage = extensions
-                    .get::<PostgresFieldStorage>()
-                    .expect("Did not get a PostgresFieldStorage");
-                let mut field_data: Value = storage.0.clone();
-
-                if session_id.is_none() {
-                    if let Value::Object(map) = &mut field_data {
-                        session_id = map
-                            .get("session_id")
-                            .map(|value| match value {
-                                Value::Null => None,
-                                Value::Bool(_) => panic!("session_id should never be a bool"),
-                                Value::Number(number) => number.as_i64(),
-                                Value::String(id) => id.parse::<i64>().ok(),
-                                Value::Array(_) => panic!("session_id should never be an object"),
-                                Value::Object(_) => panic!("session_id should never be an array"),
-                            })
-                            .flatten()
-                            .map(|id| {
-                                if id > (i32::MAX as i64) {
-                                    None
-                                } else if id < (i32::MIN as i64) {
-                                    None
-                                } else {
-                                    Some(id as i32)
-                                }
-                            })
-                            .flatten();
-                    }
+                let fields = extensions
+                    .get::<StdoutSpan>()
+                    .map(|span| span.fields.clone())
+                    .unwrap_or_default();
+                // The nearest span with a session wins, like it does for Postgres.
+                if let Some(session_id) = fields.get("session_id").and_then(parse_session_id) {
+                    span_session_id = Some(session_id);
                 }
-
-                let level = match span.metadata().level() {
-                    &Level::TRACE => 0,
-                    &Level::DEBUG => 1,
-                    &Level::INFO => 2,
-                    &Level::WARN => 3,
-                    &Level::ERROR => 4,
-                };
-
-                let span_name = span.name().to_string();
-                let pool = self.0.clone();
-                tokio::spawn(async move {
-                    sqlx::query_scalar::<_, ()>(
-                "INSERT INTO log_spans (index, name, level, fields) VALUES ($1, $2, $3, $4)",
-                    )
-                    .bind(i as i32)
-                    .bind(span_name)
-                    .bind(level)
-                    .bind(field_data.clone())
-                    .fetch_optional(&pool)
-                    .await
-                    .expect("failed to log to postgres");
-
-                    println!("logged to postgres");
-                });
+                spans.push(serde_json::json!({
+                    "name": span.name(),
+                    "fields": fields,
+                }));
             }
         }
 
-        let pool = self.0.clone();
-        let message = if let Some(Value::String(message)) = fields.remove("message") {
-            message
-        } else {
-            panic!("No message provided in an event.");
-        };
-
-        tokio::spawn(async move {
-            sqlx::query_scalar::<_, ()>(
-                "INSERT INTO logs (session_id, time_stamp, message, fields) VALUES ($1, $2, $3, $4)",
-            )
-            .bind(session_id)
-            .bind(time::OffsetDateTime::now_utc())
-            .bind(message)
-            .bind(Value::Object(fields))
-            .fetch_optional(&pool)
-            .await
-            .expect("failed to log to postgres");
-
-            println!("logged to postgres");
+        let entry = LogEntry::new(event, || span_session_id);
+        let line = serde_json::json!({
+            "time_stamp": get_iso_string(&entry.time_stamp),
+            "level": event.metadata().level().as_str(),
+            "target": event.metadata().target(),
+            "session_id": entry.session_id,
+            "message": entry.message,
+            "fields": entry.fields,
+            "spans": spans,
         });
+
+        // A line that can't be written has nowhere else to go, so it's dropped.
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{line}");
     }
 }
 
+/// Reads a `session_id` field, which may have been recorded as a number or as its debug string.
+/// Values of any other shape are ignored rather than trusted.
+fn parse_session_id(value: &Value) -> Option<i32> {
+    let id = match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(id) => id.parse::<i64>().ok(),
+        Value::Null | Value::Bool(_) | Value::Array(_) | Value::Object(_) => None,
+    };
+    id.and_then(|id| i32::try_from(id).ok())
+}
+
+/// Joins an event's fields into `name=value` pairs.
+fn describe_fields(fields: &Map<String, Value>) -> String {
+    fields
+        .iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => format!("{name}={value}"),
+            value => format!("{name}={value}"),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 struct Visitor<'a>(&'a mut Map<String, serde_json::Value>);
 
 impl<'a> Visit for Visitor<'a> {
@@ -210,3 +407,46 @@ impl<'a> Visit for Visitor<'a> {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn logs_emitted_in_quick_succession_keep_their_order(db: PgPool) {
+        let mut config = Config::from_env().unwrap();
+        config.log_writers = 4;
+        let tasks = TaskTracker::default();
+        let layer = PostgresLayer::new(
+            db.clone(),
+            tasks.clone(),
+            Arc::new(Metrics::default()),
+            &config,
+        );
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            for index in 0..10 {
+                tracing::info!("event {index}");
+            }
+        });
+        tasks.wait().await;
+
+        let logs = sqlx::query!("SELECT message, time_stamp FROM logs;")
+            .fetch_all(&db)
+            .await
+            .unwrap();
+        assert_eq!(logs.len(), 10);
+        let time_stamps = (0..10)
+            .map(|index| {
+                let message = format!("event {index}");
+                logs.iter()
+                    .find(|log| log.message == message)
+                    .unwrap()
+                    .time_stamp
+            })
+            .collect::<Vec<_>>();
+        assert!(time_stamps.is_sorted());
+    }
+}
@@ -1,109 +1,1014 @@
-use std::collections::HashSet;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
 
-use lsp_server::Message;
-use serde_json::Value;
+use lsp_server::{Message, RequestId};
+use lsp_types::notification::{Notification, Progress, PublishDiagnostics};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use time::OffsetDateTime;
+use tracing::error;
 
 use crate::{
-    message::{Conversation, MessageKind, classify},
-    session::MessageSource,
-    utils::get_iso_string,
+    html::{
+        code_actions::append_code_action_summary_to,
+        descriptors::{get_params_descriptor, get_result_descriptor},
+        theme::get_kind_class_name,
+    },
+    message::{
+        Conversation, DiagnosticTrigger, MessageKind, RequestResponsePair, classify,
+        get_method_description,
+    },
+    session::{
+        ExternalRequest, MalformedMessage, MessageSource, MessageWithTimeStamp,
+        get_stored_request_id,
+    },
+    spec::{SpecComparison, compare_request_with_spec},
+    utils::{get_byte_size_string, get_duration_string, get_iso_string, get_time_of_day_string},
 };
 
+/// How many elements of an array are rendered before the rest are collapsed.
+pub(crate) const DEFAULT_MAX_ARRAY_ITEMS: usize = 100;
+/// How many characters of a string are rendered before the rest are collapsed.
+pub(crate) const DEFAULT_MAX_STRING_CHARS: usize = 2000;
+/// How many messages are rendered on each page of the chat, unless a page size is given.
+pub(crate) const DEFAULT_PAGE_SIZE: usize = 1000;
+/// The most messages rendered on one page, however many are asked for.
+pub(crate) const MAX_PAGE_SIZE: usize = 10_000;
+
+/// Options controlling how message JSON is rendered.
+pub(crate) struct JsonRenderOptions {
+    /// Render object keys in alphabetical order instead of their stored order.
+    pub(crate) sort_keys: bool,
+    /// Show how each request's params compare with the spec for its method.
+    pub(crate) compare_with_spec: bool,
+    /// A lowercased search term. Messages that don't mention it are left out of the chat, and
+    /// its matches are highlighted in the messages that do.
+    pub(crate) search: Option<String>,
+    /// Elements of an array past this many are collapsed, since payloads like semantic tokens
+    /// can hold tens of thousands of them.
+    pub(crate) max_array_items: usize,
+    /// Characters of a string past this many are collapsed.
+    pub(crate) max_string_chars: usize,
+    /// Show each answered request together with its response in one collapsed block, instead
+    /// of as two messages that may be far apart.
+    pub(crate) group_pairs: bool,
+    /// Show runs of consecutive requests or notifications with the same method and params as
+    /// one message with a repeat count.
+    pub(crate) collapse_repeats: bool,
+    /// The session the messages belong to. Links between them name it, so that the server can
+    /// open whichever page holds the message linked to. Without it they only jump within the page.
+    pub(crate) session_id: Option<i64>,
+}
+
+impl Default for JsonRenderOptions {
+    fn default() -> Self {
+        Self {
+            sort_keys: false,
+            compare_with_spec: false,
+            search: None,
+            max_array_items: DEFAULT_MAX_ARRAY_ITEMS,
+            max_string_chars: DEFAULT_MAX_STRING_CHARS,
+            group_pairs: false,
+            collapse_repeats: false,
+            session_id: None,
+        }
+    }
+}
+
+/// Which messages to show, by category, by the side that sent them, and by when they were
+/// logged, and which page of them to render. Messages whose category or source couldn't be
+/// determined are allowed by `None`.
+pub(crate) struct MessageFilter {
+    pub(crate) kinds: HashSet<Option<MessageKind>>,
+    pub(crate) sources: HashSet<Option<MessageSource>>,
+    pub(crate) time_range: TimeRange,
+    /// Only show messages whose JSON holds a value at a path.
+    pub(crate) condition: Option<FieldCondition>,
+    /// Only show these messages, by seq, such as the lifecycle of one request.
+    pub(crate) seqs: Option<HashSet<i64>>,
+    pub(crate) page: ChatPage,
+}
+
+/// A condition on a value in a message's JSON, written as `dotted.path=value`, like
+/// `params.context.triggerKind=2`. Array elements are reached by their index. Messages without
+/// a value at the path don't match.
+pub(crate) struct FieldCondition {
+    path: Vec<String>,
+    value: String,
+}
+
+impl FieldCondition {
+    /// Parses a condition, or returns `None` when it has no `=` or no path.
+    pub(crate) fn parse(expression: &str) -> Option<Self> {
+        let (path, value) = expression.split_once('=')?;
+        let path = path.trim();
+        if path.is_empty() {
+            return None;
+        }
+        Some(Self {
+            path: path.split('.').map(str::to_string).collect(),
+            value: value.trim().to_string(),
+        })
+    }
+
+    /// Whether the message holds a string, number, boolean, or null at the path that's equal to
+    /// the condition's value. Objects and arrays never are.
+    fn matches(&self, message: &Message) -> bool {
+        let Ok(json) = serde_json::to_value(message) else {
+            return false;
+        };
+        let leaf = self.path.iter().try_fold(&json, |value, key| match value {
+            Value::Object(object) => object.get(key),
+            Value::Array(array) => key.parse::<usize>().ok().and_then(|index| array.get(index)),
+            _ => None,
+        });
+        match leaf {
+            Some(Value::String(string)) => *string == self.value,
+            Some(Value::Number(number)) => self
+                .value
+                .parse::<f64>()
+                .is_ok_and(|value| number.as_f64() == Some(value)),
+            Some(Value::Bool(bool)) => self.value.parse::<bool>() == Ok(*bool),
+            Some(Value::Null) => self.value == "null",
+            Some(Value::Object(_) | Value::Array(_)) | None => false,
+        }
+    }
+}
+
+/// A page of the messages that pass the filter, so that sessions with tens of thousands of
+/// messages can be paged through rather than rendered at once. A request shown together with
+/// its response counts as one message.
+#[derive(Clone, Copy)]
+pub(crate) struct ChatPage {
+    /// Counting from zero.
+    pub(crate) index: usize,
+    pub(crate) size: usize,
+    /// The seq of a message to show the page of. When the message passes the filter, its page
+    /// is rendered in place of `index`.
+    pub(crate) seq: Option<i64>,
+}
+
+impl ChatPage {
+    fn contains(&self, position: usize) -> bool {
+        position / self.size == self.index
+    }
+}
+
+/// The window of time to show messages from. Either end may be left open.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct TimeRange {
+    pub(crate) from: Option<OffsetDateTime>,
+    pub(crate) to: Option<OffsetDateTime>,
+}
+
+impl TimeRange {
+    pub(crate) fn contains(&self, time_stamp: OffsetDateTime) -> bool {
+        self.from.is_none_or(|from| from <= time_stamp) && self.to.is_none_or(|to| time_stamp <= to)
+    }
+}
+
+/// The order messages are listed in the chat.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ChatOrder {
+    /// Oldest first, in the order the messages were logged.
+    #[default]
+    Asc,
+    /// Newest first, which keeps the latest messages of a live session at the top.
+    Desc,
+}
+
+impl ChatOrder {
+    pub(crate) fn all() -> &'static [ChatOrder] {
+        &[ChatOrder::Asc, ChatOrder::Desc]
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ChatOrder::Asc => "asc",
+            ChatOrder::Desc => "desc",
+        }
+    }
+
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            ChatOrder::Asc => "oldest first",
+            ChatOrder::Desc => "newest first",
+        }
+    }
+}
+
+/// A row of the chat, which is either a message or a frame that couldn't be parsed as one.
+#[derive(Clone, Copy)]
+enum ChatEntry<'a> {
+    Message(&'a MessageWithTimeStamp),
+    Malformed(&'a MalformedMessage),
+}
+
+impl ChatEntry<'_> {
+    fn order_key(&self) -> (OffsetDateTime, i64) {
+        match self {
+            ChatEntry::Message(message) => (message.time_stamp, message.seq),
+            ChatEntry::Malformed(malformed) => (malformed.time_stamp, malformed.seq),
+        }
+    }
+
+    fn batch_id(&self) -> Option<i64> {
+        match self {
+            ChatEntry::Message(message) => message.batch_id,
+            ChatEntry::Malformed(_) => None,
+        }
+    }
+
+    fn time_stamp(&self) -> OffsetDateTime {
+        self.order_key().0
+    }
+}
+
+/// What a message is compared by when collapsing repeats: who sent it, its batch, and its
+/// method and params as serialized, since the ids of repeated requests differ. Responses are
+/// never collapsed.
+fn get_repeat_key(
+    message_with_time_stamp: &MessageWithTimeStamp,
+    conversation: &Conversation,
+) -> Option<(Option<MessageSource>, Option<i64>, String)> {
+    let content = match &message_with_time_stamp.message {
+        Message::Request(request) => serde_json::to_string(&(&request.method, &request.params)),
+        Message::Notification(notification) => {
+            serde_json::to_string(&(&notification.method, &notification.params))
+        }
+        Message::Response(_) => return None,
+    }
+    .ok()?;
+    Some((
+        message_with_time_stamp.get_source(conversation),
+        message_with_time_stamp.batch_id,
+        content,
+    ))
+}
+
+/// Writes the page of the chat that `filter` selects, returning how many messages pass the
+/// filter across every page and the page that was rendered. A run of collapsed repeats counts as
+/// one message.
 pub(crate) fn append_chat_html_to(
     html: &mut String,
     conversation: &Conversation,
-    allow_list: &HashSet<Option<MessageKind>>,
-) {
+    malformed_messages: &[MalformedMessage],
+    filter: &MessageFilter,
+    external_requests: &HashMap<RequestId, ExternalRequest>,
+    order: ChatOrder,
+    options: &JsonRenderOptions,
+) -> (usize, ChatPage) {
+    let diagnostic_triggers = conversation.correlate_diagnostics_with_edits();
+    let duplicate_responses = conversation.get_duplicate_responses();
+
+    let mut batch_sizes = HashMap::<i64, usize>::new();
+    for batch_id in conversation
+        .messages()
+        .iter()
+        .filter_map(|message| message.batch_id)
+    {
+        *batch_sizes.entry(batch_id).or_default() += 1;
+    }
+
+    // Only the listing is reversed. Pairing and linking work on the conversation as logged.
+    let mut entries = conversation
+        .messages()
+        .iter()
+        .map(ChatEntry::Message)
+        .chain(malformed_messages.iter().map(ChatEntry::Malformed))
+        .collect::<Vec<_>>();
+    entries.sort_by_key(ChatEntry::order_key);
+    if order == ChatOrder::Desc {
+        entries.reverse();
+    }
+
+    let is_shown = |message_with_time_stamp: &MessageWithTimeStamp| {
+        filter
+            .kinds
+            .contains(&classify(&message_with_time_stamp.message, conversation))
+            && filter
+                .sources
+                .contains(&message_with_time_stamp.get_source(conversation))
+            && filter
+                .time_range
+                .contains(message_with_time_stamp.time_stamp)
+            && options.search.as_ref().is_none_or(|search| {
+                message_mentions(&message_with_time_stamp.message, conversation, search)
+            })
+            && filter
+                .condition
+                .as_ref()
+                .is_none_or(|condition| condition.matches(&message_with_time_stamp.message))
+            && filter
+                .seqs
+                .as_ref()
+                .is_none_or(|seqs| seqs.contains(&message_with_time_stamp.seq))
+    };
+
+    // Answered requests that are shown, by the seq of the request. Their responses are shown
+    // with them rather than on their own, so they're mapped to the seq of their request.
+    let mut pairs = HashMap::new();
+    let mut paired_responses = HashMap::new();
+    if options.group_pairs {
+        for pair in conversation.pair_requests_with_responses() {
+            if let Some(response) = pair.response
+                && is_shown(pair.request)
+            {
+                paired_responses.insert(response.seq, pair.request.seq);
+                pairs.insert(pair.request.seq, pair);
+            }
+        }
+    }
+
+    append_unmatched_banner_to(html, conversation, external_requests, options);
+
+    // The entries that pass the filter, as runs of repeats. Each run is shown as its first entry,
+    // and every run is one entry unless repeats are collapsed.
+    let mut runs = Vec::<Vec<ChatEntry>>::new();
+    let mut last_repeat_key = None;
+    for entry in entries {
+        let is_entry_shown = match entry {
+            ChatEntry::Message(message_with_time_stamp) => {
+                !paired_responses.contains_key(&message_with_time_stamp.seq)
+                    && is_shown(message_with_time_stamp)
+            }
+            // Malformed frames have no JSON for a condition to match, nor an id to belong to a
+            // request's lifecycle.
+            ChatEntry::Malformed(malformed) => {
+                filter.condition.is_none()
+                    && filter.seqs.is_none()
+                    && filter.time_range.contains(malformed.time_stamp)
+                    && options
+                        .search
+                        .as_ref()
+                        .is_none_or(|search| malformed_mentions(malformed, search))
+            }
+        };
+        if !is_entry_shown {
+            continue;
+        }
+
+        // Paired requests are shown with their responses, which may differ between repeats.
+        let repeat_key = match entry {
+            ChatEntry::Message(message_with_time_stamp)
+                if options.collapse_repeats
+                    && !pairs.contains_key(&message_with_time_stamp.seq) =>
+            {
+                get_repeat_key(message_with_time_stamp, conversation)
+            }
+            ChatEntry::Message(_) | ChatEntry::Malformed(_) => None,
+        };
+        match runs.last_mut() {
+            Some(run) if repeat_key.is_some() && repeat_key == last_repeat_key => run.push(entry),
+            _ => runs.push(vec![entry]),
+        }
+        last_repeat_key = repeat_key;
+    }
+
+    let mut page = filter.page;
+    if let Some(seq) = filter.page.seq {
+        let seq = paired_responses.get(&seq).copied().unwrap_or(seq);
+        if let Some(position) = runs
+            .iter()
+            .position(|run| run.iter().any(|entry| entry.order_key().1 == seq))
+        {
+            page.index = position / page.size;
+        }
+    }
+
     html.push_str("<div id=\"chat\">");
     {
-        for message_with_time_stamp in conversation {
-            if !allow_list.contains(&classify(&message_with_time_stamp.message, conversation)) {
+        let mut open_batch = None;
+        for (index, run) in runs.iter().enumerate() {
+            if !page.contains(index) {
                 continue;
             }
+            let entry = run[0];
 
-            let source = crate::message::get_source(&message_with_time_stamp.message, conversation);
-            let message = &message_with_time_stamp.message;
-
-            let class_name;
-            let message_wrapper_class;
-            match source {
-                Some(MessageSource::Client) => {
-                    class_name = "client_message";
-                    message_wrapper_class = "client_message_wrapper";
+            // Consecutive messages from the same batch are wrapped in one group.
+            if entry.batch_id() != open_batch {
+                if open_batch.is_some() {
+                    html.push_str("</div>");
                 }
-                Some(MessageSource::Server) => {
-                    class_name = "server_message";
-                    message_wrapper_class = "server_message_wrapper";
+                if let Some(batch_id) = entry.batch_id() {
+                    html.push_str("<div class=\"batch\">");
+                    html.push_str("<span class=\"batch_label\">batch of ");
+                    html.push_str(&batch_sizes[&batch_id].to_string());
+                    html.push_str("</span>");
                 }
-                None => {
-                    class_name = "message";
-                    message_wrapper_class = "message_wrapper";
+                open_batch = entry.batch_id();
+            }
+
+            let message_with_time_stamp = match entry {
+                ChatEntry::Message(message_with_time_stamp) => message_with_time_stamp,
+                ChatEntry::Malformed(malformed) => {
+                    append_malformed_message_to(html, malformed, options);
+                    continue;
                 }
             };
+            if run.len() > 1 {
+                append_repeat_label_to(html, run);
+            }
+            match pairs.get(&message_with_time_stamp.seq) {
+                Some(pair) => append_request_pair_to(
+                    html,
+                    pair,
+                    conversation,
+                    external_requests,
+                    &duplicate_responses,
+                    &diagnostic_triggers,
+                    options,
+                ),
+                None => append_message_to(
+                    html,
+                    message_with_time_stamp,
+                    conversation,
+                    external_requests,
+                    &duplicate_responses,
+                    &diagnostic_triggers,
+                    options,
+                ),
+            }
+            if run.len() > 1 {
+                html.push_str("</div>");
+            }
+        }
+
+        if open_batch.is_some() {
+            html.push_str("</div>");
+        }
+    }
+    html.push_str("</div>");
+
+    (runs.len(), page)
+}
+
+/// Opens the group that a run of repeated messages is shown in, labelled with how many times
+/// the message was sent and over what span of time.
+fn append_repeat_label_to(html: &mut String, run: &[ChatEntry]) {
+    let first_time_stamp = run.iter().map(ChatEntry::time_stamp).min();
+    let last_time_stamp = run.iter().map(ChatEntry::time_stamp).max();
+
+    html.push_str("<div class=\"repeated\">");
+    html.push_str("<span class=\"repeat_label\">×");
+    html.push_str(&run.len().to_string());
+    if let (Some(first_time_stamp), Some(last_time_stamp)) = (first_time_stamp, last_time_stamp) {
+        html.push_str(" (from ");
+        html.push_str(&get_iso_string(&first_time_stamp));
+        html.push_str(" to ");
+        html.push_str(&get_iso_string(&last_time_stamp));
+        html.push(')');
+    }
+    html.push_str("</span>");
+}
+
+/// Warns about the requests that never received a response and the responses to requests that
+/// were never logged, linking to each, since they're usually the most interesting bugs.
+fn append_unmatched_banner_to(
+    html: &mut String,
+    conversation: &Conversation,
+    external_requests: &HashMap<RequestId, ExternalRequest>,
+    options: &JsonRenderOptions,
+) {
+    let mut unmatched = conversation.get_unmatched_messages();
+    unmatched
+        .unknown_responses
+        .retain(|message| match &message.message {
+            Message::Response(response) => !external_requests.contains_key(&response.id),
+            Message::Request(_) | Message::Notification(_) => false,
+        });
+
+    for (messages, singular, plural) in [
+        (
+            &unmatched.unanswered_requests,
+            " request never received a response: ",
+            " requests never received a response: ",
+        ),
+        (
+            &unmatched.unknown_responses,
+            " response doesn't answer any logged request: ",
+            " responses don't answer any logged request: ",
+        ),
+    ] {
+        if messages.is_empty() {
+            continue;
+        }
+
+        html.push_str("<p class=\"unmatched_messages\">");
+        html.push_str(&messages.len().to_string());
+        html.push_str(if messages.len() == 1 {
+            singular
+        } else {
+            plural
+        });
+        for (index, message) in messages.iter().enumerate() {
+            let id = match &message.message {
+                Message::Request(request) => &request.id,
+                Message::Response(response) => &response.id,
+                Message::Notification(_) => continue,
+            };
+            if index > 0 {
+                html.push_str(", ");
+            }
+            html.push_str("<a href=\"");
+            append_message_href_to(html, message.seq, options);
+            html.push_str("\">");
+            html.push_str(&html_escape::encode_text(&get_stored_request_id(id)));
+            html.push_str("</a>");
+        }
+        html.push_str("</p>");
+    }
+}
+
+/// Writes a request and its response in one collapsed block, headed by the request's method, a
+/// summary of its result and how long the response took.
+fn append_request_pair_to(
+    html: &mut String,
+    pair: &RequestResponsePair,
+    conversation: &Conversation,
+    external_requests: &HashMap<RequestId, ExternalRequest>,
+    duplicate_responses: &HashSet<i64>,
+    diagnostic_triggers: &HashMap<i64, DiagnosticTrigger>,
+    options: &JsonRenderOptions,
+) {
+    html.push_str("<details class=\"request_pair\">");
+    html.push_str("<summary>");
+    if let Message::Request(request) = &pair.request.message {
+        append_method_name_to(html, &request.method);
+        append_params_descriptor_to(html, &request.method, &request.params);
+        if let Some(Message::Response(response)) = pair.response.map(|response| &response.message)
+            && let Some(result) = &response.result
+        {
+            append_result_descriptor_to(html, &request.method, result);
+        }
+    }
+    if let Some(latency) = pair.latency() {
+        html.push_str(" <span class=\"pair_latency\">");
+        html.push_str(&get_duration_string(&latency));
+        html.push_str("</span>");
+    }
+    html.push_str("</summary>");
+
+    html.push_str("<div class=\"request_pair_messages\">");
+    for message_with_time_stamp in [Some(pair.request), pair.response].into_iter().flatten() {
+        append_message_to(
+            html,
+            message_with_time_stamp,
+            conversation,
+            external_requests,
+            duplicate_responses,
+            diagnostic_triggers,
+            options,
+        );
+    }
+    html.push_str("</div>");
+    html.push_str("</details>");
+}
+
+/// Writes one message as a chat bubble, with its time stamp and any diagnostic trigger.
+fn append_message_to(
+    html: &mut String,
+    message_with_time_stamp: &MessageWithTimeStamp,
+    conversation: &Conversation,
+    external_requests: &HashMap<RequestId, ExternalRequest>,
+    duplicate_responses: &HashSet<i64>,
+    diagnostic_triggers: &HashMap<i64, DiagnosticTrigger>,
+    options: &JsonRenderOptions,
+) {
+    let kind = classify(&message_with_time_stamp.message, conversation);
+    let source = message_with_time_stamp.get_source(conversation);
+    let message = &message_with_time_stamp.message;
+
+    let class_name;
+    let message_wrapper_class;
+    match source {
+        Some(MessageSource::Client) => {
+            class_name = "client_message";
+            message_wrapper_class = "client_message_wrapper";
+        }
+        Some(MessageSource::Server) => {
+            class_name = "server_message";
+            message_wrapper_class = "server_message_wrapper";
+        }
+        None => {
+            class_name = "message";
+            message_wrapper_class = "message_wrapper";
+        }
+    };
 
-            html.push_str("<div class=\"");
-            html.push_str(message_wrapper_class);
+    html.push_str("<div id=\"msg-");
+    html.push_str(&message_with_time_stamp.seq.to_string());
+    html.push_str("\" class=\"");
+    html.push_str(message_wrapper_class);
+    html.push_str("\">");
+    {
+        html.push_str("<div class=\"");
+        {
+            html.push_str(class_name);
+            html.push_str("\">");
+            html.push_str("<details class=\"message_summary");
+            match source {
+                Some(MessageSource::Client) => html.push_str(" client"),
+                Some(MessageSource::Server) => html.push_str(" server"),
+                None => {}
+            }
+            html.push(' ');
+            html.push_str(&get_kind_class_name(kind));
             html.push_str("\">");
             {
-                html.push_str("<div class=\"");
-                {
-                    html.push_str(class_name);
-                    html.push_str("\">");
-                    html.push_str("<details class=\"message_summary");
-                    match source {
-                        Some(MessageSource::Client) => html.push_str(" client"),
-                        Some(MessageSource::Server) => html.push_str(" server"),
-                        None => {}
+                html.push_str("<summary>");
+                match &message {
+                    Message::Request(req) => {
+                        html.push_str("Request: ");
+                        append_method_name_to(html, &req.method);
+                        append_params_descriptor_to(html, &req.method, &req.params);
                     }
-                    html.push_str("\">");
-                    {
-                        html.push_str("<summary>");
-                        match &message {
-                            Message::Request(req) => {
-                                html.push_str("Request: ");
-                                html.push_str(&req.method);
-                            }
-                            Message::Response(resp) => {
-                                html.push_str("Response: ");
-                                let method = conversation
-                                    .requests()
-                                    .get(&resp.id)
-                                    .map(|request| &request.method);
-                                if let Some(method) = method {
-                                    html.push_str(method);
-                                } else {
-                                    html.push_str("Unknown Response");
-                                }
+                    Message::Response(resp) => {
+                        html.push_str("Response: ");
+                        let request = conversation.requests().get(&resp.id);
+                        if let Some(request) = request {
+                            append_method_name_to(html, &request.method);
+                            append_params_descriptor_to(html, &request.method, &request.params);
+                            if let Some(result) = &resp.result {
+                                append_result_descriptor_to(html, &request.method, result);
                             }
-                            Message::Notification(not) => {
-                                html.push_str("Notification: ");
-                                html.push_str(&not.method);
+                        } else if let Some(external) = external_requests.get(&resp.id) {
+                            append_method_name_to(html, &external.method);
+                            if let Some(result) = &resp.result {
+                                append_result_descriptor_to(html, &external.method, result);
                             }
+                            html.push_str(" <a class=\"external_request\" href=\"");
+                            append_message_url_to(html, external.session_id, external.seq);
+                            html.push_str("\">(request in session ");
+                            html.push_str(&external.session_id.to_string());
+                            html.push_str(")</a>");
+                        } else {
+                            html.push_str("<span class=\"unknown_response\" title=\"No request with this id was logged\">Unknown Response</span>");
                         }
+                    }
+                    Message::Notification(not) => {
+                        html.push_str("Notification: ");
+                        append_method_name_to(html, &not.method);
+                        append_params_descriptor_to(html, &not.method, &not.params);
+                        if not.method == Progress::METHOD
+                            && let Some(request) = conversation.get_progress_request(not)
+                        {
+                            append_progress_request_link_to(html, request, options);
+                        }
+                    }
+                }
+
+                if let Some(byte_size) = message_with_time_stamp.byte_size {
+                    html.push_str(" <span class=\"byte_size\">&mdash; ");
+                    html.push_str(&get_byte_size_string(byte_size.into()));
+                    html.push_str("</span>");
+                }
+
+                if duplicate_responses.contains(&message_with_time_stamp.seq) {
+                    html.push_str("<span class=\"duplicate_response\" title=\"This request had already been answered. Only one response is allowed per request.\">duplicate response</span>");
+                }
+
+                if source.is_none() {
+                    html.push_str("<span class=\"direction_unknown\" title=\"The direction of this message could not be inferred from its method\">?</span>");
+                }
 
-                        html.push_str("</summary>");
+                html.push_str("</summary>");
+                if options.compare_with_spec
+                    && let Message::Request(request) = message
+                    && let Some(comparison) = compare_request_with_spec(request)
+                {
+                    append_spec_comparison_to(html, &comparison);
+                }
+                match message {
+                    Message::Request(request) => {
+                        append_code_action_summary_to(html, &request.method, &request.params)
+                    }
+                    Message::Response(response) => {
+                        if let (Some(request), Some(result)) =
+                            (conversation.requests().get(&response.id), &response.result)
                         {
-                            append_json_html_to(
-                                html,
-                                serde_json::to_value(message.clone()).unwrap(),
-                            );
+                            append_code_action_summary_to(html, &request.method, result);
                         }
                     }
-                    html.push_str("</details>");
+                    Message::Notification(_) => {}
                 }
-                html.push_str("</div>");
 
-                html.push_str("<span class=\"timestamp\">");
-                html.push_str(&get_iso_string(&message_with_time_stamp.time_stamp));
-                html.push_str("</span>");
+                append_message_body_to(
+                    html,
+                    message_with_time_stamp,
+                    serde_json::to_value(message),
+                    options,
+                );
             }
-            html.push_str("</div>");
+            html.push_str("</details>");
+        }
+        html.push_str("</div>");
+
+        append_message_anchor_to(
+            html,
+            message_with_time_stamp.seq,
+            &message_with_time_stamp.time_stamp,
+            options,
+        );
+
+        if let Message::Notification(notification) = message
+            && notification.method == PublishDiagnostics::METHOD
+        {
+            append_diagnostic_trigger_to(
+                html,
+                diagnostic_triggers.get(&message_with_time_stamp.seq),
+                options,
+            );
+        }
+    }
+    html.push_str("</div>");
+}
+
+/// Writes a message's JSON, or a placeholder when it couldn't be serialized, so that one bad
+/// message doesn't take the rest of the page down with it.
+fn append_message_body_to(
+    html: &mut String,
+    message_with_time_stamp: &MessageWithTimeStamp,
+    json: serde_json::Result<Value>,
+    options: &JsonRenderOptions,
+) {
+    match json {
+        Ok(value) => {
+            append_json_html_to(html, value, options);
+            append_raw_json_link_to(html, message_with_time_stamp.seq, options);
+            append_replay_links_to(html, message_with_time_stamp, options);
+        }
+        Err(err) => {
+            error!(
+                "Failed to serialize message {} for display. Error: {err}",
+                message_with_time_stamp.seq
+            );
+            html.push_str(
+                "<span class=\"render_error\">This message could not be displayed.</span>",
+            );
+        }
+    }
+}
+
+/// Writes a frame that couldn't be parsed, with why it couldn't be and its payload as text.
+fn append_malformed_message_to(
+    html: &mut String,
+    malformed: &MalformedMessage,
+    options: &JsonRenderOptions,
+) {
+    html.push_str("<div id=\"msg-");
+    html.push_str(&malformed.seq.to_string());
+    html.push_str("\" class=\"message_wrapper\">");
+    {
+        html.push_str("<div class=\"message malformed_message\">");
+        {
+            html.push_str("<details open class=\"message_summary\">");
+            {
+                html.push_str("<summary>Malformed: ");
+                html.push_str(&html_escape::encode_text(&malformed.parse_error));
+                html.push_str("</summary>");
+                match &malformed.raw_bytes {
+                    Some(raw_bytes) => {
+                        let payload = String::from_utf8_lossy(raw_bytes);
+                        if let Cow::Owned(_) = payload {
+                            html.push_str("<span class=\"malformed_encoding\">Not valid UTF-8. Invalid bytes are shown as &#xFFFD;.</span>");
+                        }
+                        html.push_str("<pre class=\"malformed_payload\">");
+                        append_highlighted_text_to(html, &payload, options);
+                        html.push_str("</pre>");
+                    }
+                    None => {
+                        html.push_str("<span class=\"malformed_encoding\">Payload purged.</span>")
+                    }
+                }
+            }
+            html.push_str("</details>");
         }
+        html.push_str("</div>");
+
+        append_message_anchor_to(html, malformed.seq, &malformed.time_stamp, options);
+    }
+    html.push_str("</div>");
+}
+
+/// Links to a message's JSON as text, so that its exact payload can be copied out. It's only
+/// fetched when it's asked for, since the payloads can be large.
+fn append_raw_json_link_to(html: &mut String, seq: i64, options: &JsonRenderOptions) {
+    let Some(session_id) = options.session_id else {
+        return;
+    };
+    html.push_str("<a class=\"raw_json\" href=\"/api/session/");
+    html.push_str(&session_id.to_string());
+    html.push_str("/messages/");
+    html.push_str(&seq.to_string());
+    html.push_str("\">Raw JSON</a>");
+}
+
+/// Links to what's needed to send a request or notification again by hand: the frame to write
+/// to a server's stdin, `Content-Length` header included, and a `POST /log` body to log it to
+/// another instance. They're only generated when they're asked for.
+fn append_replay_links_to(
+    html: &mut String,
+    message_with_time_stamp: &MessageWithTimeStamp,
+    options: &JsonRenderOptions,
+) {
+    let Some(session_id) = options.session_id else {
+        return;
+    };
+    if let Message::Response(_) = message_with_time_stamp.message {
+        return;
     }
+
+    let append_replay_link_to = |html: &mut String, format: &str, label: &str| {
+        html.push_str("<a href=\"/api/session/");
+        html.push_str(&session_id.to_string());
+        html.push_str("/messages/");
+        html.push_str(&message_with_time_stamp.seq.to_string());
+        html.push_str("/replay?format=");
+        html.push_str(format);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</a>");
+    };
+
+    html.push_str("<div class=\"replay_links\">Replay: ");
+    append_replay_link_to(html, "stdio", "over stdio");
+    html.push_str(", ");
+    append_replay_link_to(html, "log", "as a <code>POST /log</code> body");
+    html.push_str(" (with a <code>session_id</code> from <code>POST /sessions</code>)");
     html.push_str("</div>");
 }
 
-fn append_json_html_to(html: &mut String, value: Value) {
+/// Writes which request a progress notification reports on, linking to it.
+fn append_progress_request_link_to(
+    html: &mut String,
+    request: &MessageWithTimeStamp,
+    options: &JsonRenderOptions,
+) {
+    let Message::Request(lsp_request) = &request.message else {
+        return;
+    };
+    html.push_str(" <a class=\"progress_request\" href=\"");
+    append_message_href_to(html, request.seq, options);
+    html.push_str("\">(for ");
+    html.push_str(&html_escape::encode_text(&lsp_request.method));
+    html.push(' ');
+    html.push_str(&html_escape::encode_text(&get_stored_request_id(
+        &lsp_request.id,
+    )));
+    html.push_str(")</a>");
+}
+
+/// Writes the address of a message on the session's page, which names its seq so that the page
+/// holding it is opened whatever page the link is followed from. The seq is also the anchor
+/// the page is scrolled to.
+pub(crate) fn append_message_url_to(html: &mut String, session_id: i64, seq: i64) {
+    html.push_str("/session?session_id=");
+    html.push_str(&session_id.to_string());
+    html.push_str("&seq=");
+    html.push_str(&seq.to_string());
+    html.push_str("#msg-");
+    html.push_str(&seq.to_string());
+}
+
+/// Writes where a link to another message of the chat leads, which is only its anchor when the
+/// session isn't known.
+fn append_message_href_to(html: &mut String, seq: i64, options: &JsonRenderOptions) {
+    match options.session_id {
+        Some(session_id) => append_message_url_to(html, session_id, seq),
+        None => {
+            html.push_str("#msg-");
+            html.push_str(&seq.to_string());
+        }
+    }
+}
+
+/// Writes a message's time stamp as a link to the message itself, so that the address it leads
+/// to can be shared. Messages are identified by their seq, which doesn't change between loads
+/// or with the filters, and the page that's opened is the one holding the message.
+fn append_message_anchor_to(
+    html: &mut String,
+    seq: i64,
+    time_stamp: &OffsetDateTime,
+    options: &JsonRenderOptions,
+) {
+    html.push_str("<a class=\"timestamp\" href=\"");
+    append_message_href_to(html, seq, options);
+    html.push_str("\" title=\"Link to this message\">");
+    html.push_str(&get_iso_string(time_stamp));
+    html.push_str("</a>");
+}
+
+/// Whether a malformed frame's payload or parse error contains the lowercased `search` term.
+fn malformed_mentions(malformed: &MalformedMessage, search: &str) -> bool {
+    let payload = malformed
+        .raw_bytes
+        .as_deref()
+        .map(String::from_utf8_lossy)
+        .unwrap_or_default();
+    find_ignoring_case(&payload, search).is_some()
+        || find_ignoring_case(&malformed.parse_error, search).is_some()
+}
+
+/// Whether a message's JSON or method name contains the lowercased `search` term.
+fn message_mentions(message: &Message, conversation: &Conversation, search: &str) -> bool {
+    let method = match message {
+        Message::Request(request) => Some(&request.method),
+        Message::Response(response) => conversation
+            .requests()
+            .get(&response.id)
+            .map(|request| &request.method),
+        Message::Notification(notification) => Some(&notification.method),
+    };
+    if method.is_some_and(|method| method.to_lowercase().contains(search)) {
+        return true;
+    }
+
+    serde_json::to_string(message).is_ok_and(|json| json.to_lowercase().contains(search))
+}
+
+/// Writes a method name, with a tooltip describing the method when it's a standard one.
+fn append_method_name_to(html: &mut String, method: &str) {
+    match get_method_description(method) {
+        Some(description) => {
+            html.push_str("<span class=\"method_name\" title=\"");
+            html.push_str(description);
+            html.push_str("\">");
+            html.push_str(&html_escape::encode_text(method));
+            html.push_str("</span>");
+        }
+        // Custom methods are whatever the sender chose to call them.
+        None => html.push_str(&html_escape::encode_text(method)),
+    }
+}
+
+/// Follows a method name with what its params target, for the methods that have one.
+fn append_params_descriptor_to(html: &mut String, method: &str, params: &Value) {
+    if let Some(descriptor) = get_params_descriptor(method, params) {
+        html.push_str(" <span class=\"params_descriptor\">");
+        html.push_str(&html_escape::encode_text(&descriptor));
+        html.push_str("</span>");
+    }
+}
+
+/// Follows a response's method with a summary of its result, for the methods that have one.
+fn append_result_descriptor_to(html: &mut String, method: &str, result: &Value) {
+    if let Some(descriptor) = get_result_descriptor(method, result) {
+        html.push_str(" <span class=\"result_descriptor\">&rarr; ");
+        html.push_str(&html_escape::encode_text(&descriptor));
+        html.push_str("</span>");
+    }
+}
+
+fn append_spec_comparison_to(html: &mut String, comparison: &SpecComparison) {
+    html.push_str("<div class=\"spec_comparison\">");
+    if comparison.matches() {
+        html.push_str("Params match the spec");
+    } else if let Some(error) = &comparison.error {
+        html.push_str("Params don't match the spec: ");
+        html.push_str(&html_escape::encode_text(error));
+    } else {
+        html.push_str("Unexpected fields: ");
+        html.push_str(&html_escape::encode_text(
+            &comparison.unexpected_fields.join(", "),
+        ));
+    }
+
+    if !comparison.recognized_fields.is_empty() {
+        html.push_str("<br/>Recognized fields: ");
+        html.push_str(&html_escape::encode_text(
+            &comparison.recognized_fields.join(", "),
+        ));
+    }
+    html.push_str("</div>");
+}
+
+fn append_diagnostic_trigger_to(
+    html: &mut String,
+    trigger: Option<&DiagnosticTrigger>,
+    options: &JsonRenderOptions,
+) {
+    let (description, trigger) = match trigger {
+        Some(DiagnosticTrigger::Edit(edit)) => ("triggered after edit at ", edit),
+        Some(DiagnosticTrigger::Open(open)) => ("triggered after open at ", open),
+        None => {
+            html.push_str("<span class=\"diagnostic_trigger\">no preceding edit</span>");
+            return;
+        }
+    };
+
+    html.push_str("<a class=\"diagnostic_trigger\" href=\"");
+    append_message_href_to(html, trigger.seq, options);
+    html.push_str("\">");
+    html.push_str(description);
+    html.push_str(&get_time_of_day_string(&trigger.time_stamp));
+    html.push_str("</a>");
+}
+
+pub(crate) fn append_json_html_to(html: &mut String, value: Value, options: &JsonRenderOptions) {
     match value {
         Value::Null => {
             html.push_str("<span style=\"color: lightblue\">null</span>");
@@ -122,9 +1027,7 @@ fn append_json_html_to(html: &mut String, value: Value) {
             html.push_str("<br/>");
         }
         Value::String(str) => {
-            html.push('"');
-            html.push_str(&html_escape::encode_text(&str));
-            html.push('"');
+            append_json_string_to(html, &str, options);
             html.push_str("<br/>");
         }
         Value::Array(values) => {
@@ -134,9 +1037,7 @@ fn append_json_html_to(html: &mut String, value: Value) {
                 html.push_str("<details open class=\"array_container\">");
                 html.push_str("<summary>[]</summary>");
                 html.push_str("<div class=\"array_content\">");
-                for value in values {
-                    append_json_html_to(html, value);
-                }
+                append_json_array_items_to(html, values, options);
                 html.push_str("</div>");
                 html.push_str("</details>");
             }
@@ -148,8 +1049,8 @@ fn append_json_html_to(html: &mut String, value: Value) {
                 html.push_str("<details open class=\"object_container\">");
                 html.push_str("<summary>{}</summary>");
                 html.push_str("<div class=\"object_content\">");
-                for kvp in map {
-                    append_json_kvp_to(html, kvp);
+                for kvp in get_ordered_entries(map, options) {
+                    append_json_kvp_to(html, kvp, options);
                 }
                 html.push_str("</div>");
                 html.push_str("</details>");
@@ -158,7 +1059,11 @@ fn append_json_html_to(html: &mut String, value: Value) {
     }
 }
 
-fn append_json_kvp_to(html: &mut String, kvp: (String, Value)) {
+pub(crate) fn append_json_kvp_to(
+    html: &mut String,
+    kvp: (String, Value),
+    options: &JsonRenderOptions,
+) {
     match kvp.1 {
         Value::Null => {
             html.push('"');
@@ -193,9 +1098,7 @@ fn append_json_kvp_to(html: &mut String, kvp: (String, Value)) {
             html.push_str(&html_escape::encode_text(&kvp.0));
             html.push('"');
             html.push_str(": ");
-            html.push('"');
-            html.push_str(&html_escape::encode_text(&str));
-            html.push('"');
+            append_json_string_to(html, &str, options);
             html.push_str("<br/>");
         }
         Value::Array(values) => {
@@ -210,9 +1113,7 @@ fn append_json_kvp_to(html: &mut String, kvp: (String, Value)) {
             if !values.is_empty() {
                 html.push_str("</summary>");
                 html.push_str("<div class=\"array_content\">");
-                for value in values {
-                    append_json_html_to(html, value);
-                }
+                append_json_array_items_to(html, values, options);
                 html.push_str("</div>");
                 html.push_str("</details>");
             }
@@ -229,8 +1130,8 @@ fn append_json_kvp_to(html: &mut String, kvp: (String, Value)) {
             if !object.is_empty() {
                 html.push_str("</summary>");
                 html.push_str("<div class=\"object_content\">");
-                for kvp in object {
-                    append_json_kvp_to(html, kvp);
+                for kvp in get_ordered_entries(object, options) {
+                    append_json_kvp_to(html, kvp, options);
                 }
                 html.push_str("</div>");
                 html.push_str("</details>");
@@ -238,3 +1139,328 @@ fn append_json_kvp_to(html: &mut String, kvp: (String, Value)) {
         }
     }
 }
+
+/// Writes the elements of an array, collapsing any past `options.max_array_items` behind a
+/// toggle.
+fn append_json_array_items_to(html: &mut String, values: Vec<Value>, options: &JsonRenderOptions) {
+    let remaining = values.len().saturating_sub(options.max_array_items);
+    let mut values = values.into_iter();
+    for value in values.by_ref().take(options.max_array_items) {
+        append_json_html_to(html, value, options);
+    }
+
+    if remaining > 0 {
+        html.push_str("<details class=\"json_remainder\">");
+        html.push_str("<summary>");
+        html.push_str(&remaining.to_string());
+        html.push_str(" more items</summary>");
+        for value in values {
+            append_json_html_to(html, value, options);
+        }
+        html.push_str("</details>");
+    }
+}
+
+/// Writes a quoted string, collapsing any characters past `options.max_string_chars` behind a
+/// toggle.
+fn append_json_string_to(html: &mut String, str: &str, options: &JsonRenderOptions) {
+    html.push('"');
+    match str.char_indices().nth(options.max_string_chars) {
+        Some((cut, _)) => {
+            let (head, tail) = str.split_at(cut);
+            append_highlighted_text_to(html, head, options);
+            html.push_str("<details class=\"json_remainder\">");
+            html.push_str("<summary>");
+            html.push_str(&tail.chars().count().to_string());
+            html.push_str(" more characters</summary>");
+            append_highlighted_text_to(html, tail, options);
+            html.push_str("</details>");
+        }
+        None => append_highlighted_text_to(html, str, options),
+    }
+    html.push('"');
+}
+
+/// Writes escaped text, marking each match of the search term.
+fn append_highlighted_text_to(html: &mut String, text: &str, options: &JsonRenderOptions) {
+    let Some(search) = options.search.as_deref() else {
+        html.push_str(&html_escape::encode_text(text));
+        return;
+    };
+
+    let mut rest = text;
+    while let Some((start, end)) = find_ignoring_case(rest, search) {
+        html.push_str(&html_escape::encode_text(&rest[..start]));
+        html.push_str("<mark class=\"search_match\">");
+        html.push_str(&html_escape::encode_text(&rest[start..end]));
+        html.push_str("</mark>");
+        rest = &rest[end..];
+    }
+    html.push_str(&html_escape::encode_text(rest));
+}
+
+/// Finds the byte range of the first match of the lowercased `needle` in `haystack`, ignoring
+/// case. Lowercasing can change the length of some characters, so the range is found by walking
+/// the haystack rather than searching a lowercased copy of it.
+fn find_ignoring_case(haystack: &str, needle: &str) -> Option<(usize, usize)> {
+    if needle.is_empty() {
+        return None;
+    }
+
+    'starts: for (start, _) in haystack.char_indices() {
+        let mut expected = needle.chars();
+        let mut end = start;
+        let mut remaining = haystack[start..].chars();
+        while !expected.as_str().is_empty() {
+            let Some(char) = remaining.next() else {
+                continue 'starts;
+            };
+            for lower in char.to_lowercase() {
+                if expected.next() != Some(lower) {
+                    continue 'starts;
+                }
+            }
+            end += char.len_utf8();
+        }
+        return Some((start, end));
+    }
+    None
+}
+
+fn get_ordered_entries(
+    map: Map<String, Value>,
+    options: &JsonRenderOptions,
+) -> Vec<(String, Value)> {
+    let mut entries = map.into_iter().collect::<Vec<_>>();
+    if options.sort_keys {
+        entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// A filter that lets every message through, onto one page.
+    fn show_everything() -> MessageFilter {
+        MessageFilter {
+            kinds: MessageKind::all()
+                .map(Some)
+                .chain(std::iter::once(None))
+                .collect(),
+            sources: HashSet::from([
+                Some(MessageSource::Client),
+                Some(MessageSource::Server),
+                None,
+            ]),
+            time_range: TimeRange::default(),
+            condition: None,
+            seqs: None,
+            page: ChatPage {
+                index: 0,
+                size: DEFAULT_PAGE_SIZE,
+                seq: None,
+            },
+        }
+    }
+
+    fn render_chat(
+        messages: Vec<MessageWithTimeStamp>,
+        malformed_messages: &[MalformedMessage],
+        order: ChatOrder,
+        options: &JsonRenderOptions,
+    ) -> String {
+        let mut html = String::new();
+        append_chat_html_to(
+            &mut html,
+            &Conversation::from(messages),
+            malformed_messages,
+            &show_everything(),
+            &HashMap::new(),
+            order,
+            options,
+        );
+        html
+    }
+
+    #[test]
+    fn messages_of_an_unknown_direction_are_marked() {
+        let html = render_chat(
+            vec![
+                MessageWithTimeStamp::for_tests(
+                    1,
+                    0,
+                    json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+                ),
+                MessageWithTimeStamp::for_tests(
+                    2,
+                    10,
+                    json!({ "jsonrpc": "2.0", "method": "custom/indexingDone", "params": {} }),
+                ),
+            ],
+            &[],
+            ChatOrder::Asc,
+            &JsonRenderOptions::default(),
+        );
+
+        assert_eq!(html.matches("class=\"direction_unknown\"").count(), 1);
+        // Only the custom notification's direction can't be inferred from its method.
+        let custom = html.find("custom/indexingDone").unwrap();
+        assert!(html[custom..].contains("direction_unknown"));
+    }
+
+    #[test]
+    fn keys_render_in_sorted_order_when_enabled() {
+        let options = JsonRenderOptions {
+            sort_keys: true,
+            ..JsonRenderOptions::default()
+        };
+        let mut html = String::new();
+        append_json_html_to(
+            &mut html,
+            json!({ "zeta": 1, "beta": { "y": 2, "x": 3 }, "alpha": 4 }),
+            &options,
+        );
+
+        let positions = ["\"alpha\"", "\"beta\"", "\"x\"", "\"y\"", "\"zeta\""].map(|key| {
+            html.find(key)
+                .unwrap_or_else(|| panic!("{key} to be rendered"))
+        });
+        assert!(positions.is_sorted(), "{html}");
+    }
+
+    #[test]
+    fn a_message_that_fails_to_serialize_renders_a_placeholder() {
+        let message = MessageWithTimeStamp::for_tests(
+            1,
+            0,
+            json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        );
+        // Maps with keys that aren't strings can't be turned into JSON.
+        let failure = serde_json::to_value(HashMap::from([((1, 2), 3)]));
+        assert!(failure.is_err());
+
+        let mut html = String::new();
+        append_message_body_to(&mut html, &message, failure, &JsonRenderOptions::default());
+
+        assert!(
+            html.contains(
+                "<span class=\"render_error\">This message could not be displayed.</span>"
+            )
+        );
+        assert!(!html.contains("raw_json"));
+    }
+
+    #[test]
+    fn recognized_methods_are_described_in_their_title() {
+        let html = render_chat(
+            vec![
+                MessageWithTimeStamp::for_tests(
+                    1,
+                    0,
+                    json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+                ),
+                MessageWithTimeStamp::for_tests(
+                    2,
+                    100,
+                    json!({ "jsonrpc": "2.0", "id": 2, "method": "custom/request", "params": {} }),
+                ),
+            ],
+            &[],
+            ChatOrder::Asc,
+            &JsonRenderOptions::default(),
+        );
+
+        assert!(html.contains(
+            "<span class=\"method_name\" title=\"Requests hover information (types, docs) for the symbol at a position.\">textDocument/hover</span>"
+        ));
+        assert!(!html.contains("\">custom/request</span>"));
+    }
+
+    #[test]
+    fn a_second_response_to_a_request_is_flagged_as_a_duplicate() {
+        let messages = vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                100,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                200,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+            ),
+        ];
+        assert_eq!(
+            Conversation::from(messages.clone()).get_duplicate_responses(),
+            HashSet::from([3])
+        );
+
+        let html = render_chat(messages, &[], ChatOrder::Asc, &JsonRenderOptions::default());
+
+        assert_eq!(html.matches("class=\"duplicate_response\"").count(), 1);
+        let (_, after_first_response) = html.split_once("id=\"msg-2\"").unwrap();
+        let (_, after_second_response) = after_first_response.split_once("id=\"msg-3\"").unwrap();
+        assert!(after_second_response.contains("class=\"duplicate_response\""));
+    }
+
+    #[test]
+    fn newest_first_renders_the_last_message_first() {
+        let html = render_chat(
+            vec![
+                MessageWithTimeStamp::for_tests(
+                    1,
+                    0,
+                    json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+                ),
+                MessageWithTimeStamp::for_tests(
+                    2,
+                    100,
+                    json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+                ),
+                MessageWithTimeStamp::for_tests(
+                    3,
+                    200,
+                    json!({ "jsonrpc": "2.0", "method": "exit" }),
+                ),
+            ],
+            &[],
+            ChatOrder::Desc,
+            &JsonRenderOptions::default(),
+        );
+
+        let position_of = |seq: i64| html.find(&format!("id=\"msg-{seq}\"")).unwrap();
+        assert!(position_of(3) < position_of(2));
+        assert!(position_of(2) < position_of(1));
+    }
+
+    #[test]
+    fn invalid_utf8_in_a_malformed_frame_renders_lossily() {
+        let malformed = MalformedMessage {
+            time_stamp: OffsetDateTime::UNIX_EPOCH,
+            seq: 1,
+            raw_bytes: Some(b"{\"method\":\"\xff\xfe<b>\"".to_vec()),
+            parse_error: "EOF while parsing a string".to_string(),
+        };
+
+        let html = render_chat(
+            Vec::new(),
+            &[malformed],
+            ChatOrder::Asc,
+            &JsonRenderOptions::default(),
+        );
+
+        assert!(html.contains("Not valid UTF-8. Invalid bytes are shown as &#xFFFD;."));
+        assert!(html.contains(
+            "<pre class=\"malformed_payload\">{\"method\":\"\u{FFFD}\u{FFFD}&lt;b&gt;\"</pre>"
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use lsp_types::{
+    CodeAction, CodeActionOrCommand, CodeActionResponse, Command, DocumentChangeOperation,
+    DocumentChanges, OneOf, Range, ResourceOp, TextDocumentEdit, WorkspaceEdit,
+    request::{ApplyWorkspaceEdit, CodeActionRequest, CodeActionResolveRequest, Request},
+};
+use serde_json::Value;
+
+/// Writes a readable summary of the code actions or workspace edit carried by a payload of
+/// `method`, which is the params of a request or the result of its response. Messages of other
+/// methods, and payloads that don't parse, are left to the JSON view.
+pub(crate) fn append_code_action_summary_to(html: &mut String, method: &str, payload: &Value) {
+    match method {
+        CodeActionRequest::METHOD => {
+            let Ok(Some(actions)) =
+                serde_json::from_value::<Option<CodeActionResponse>>(payload.clone())
+            else {
+                return;
+            };
+
+            html.push_str("<div class=\"code_actions\">");
+            if actions.is_empty() {
+                html.push_str("No code actions");
+            } else {
+                html.push_str("<ul>");
+                for action in &actions {
+                    html.push_str("<li>");
+                    match action {
+                        CodeActionOrCommand::CodeAction(action) => {
+                            append_code_action_to(html, action)
+                        }
+                        CodeActionOrCommand::Command(command) => append_command_to(html, command),
+                    }
+                    html.push_str("</li>");
+                }
+                html.push_str("</ul>");
+            }
+            html.push_str("</div>");
+        }
+        CodeActionResolveRequest::METHOD => {
+            let Ok(action) = serde_json::from_value::<CodeAction>(payload.clone()) else {
+                return;
+            };
+
+            html.push_str("<div class=\"code_actions\">");
+            append_code_action_to(html, &action);
+            html.push_str("</div>");
+        }
+        ApplyWorkspaceEdit::METHOD => {
+            let Ok(params) =
+                serde_json::from_value::<<ApplyWorkspaceEdit as Request>::Params>(payload.clone())
+            else {
+                return;
+            };
+
+            html.push_str("<div class=\"code_actions\">");
+            if let Some(label) = &params.label {
+                html.push_str("<span class=\"code_action_title\">");
+                html.push_str(&html_escape::encode_text(label));
+                html.push_str("</span>");
+            }
+            append_workspace_edit_to(html, &params.edit);
+            html.push_str("</div>");
+        }
+        _ => {}
+    }
+}
+
+fn append_code_action_to(html: &mut String, action: &CodeAction) {
+    html.push_str("<span class=\"code_action_title\">");
+    html.push_str(&html_escape::encode_text(&action.title));
+    html.push_str("</span>");
+
+    if let Some(kind) = &action.kind {
+        html.push_str(" <span class=\"code_action_kind\">");
+        html.push_str(&html_escape::encode_text(kind.as_str()));
+        html.push_str("</span>");
+    }
+    if action.is_preferred == Some(true) {
+        html.push_str(" <span class=\"code_action_kind\">preferred</span>");
+    }
+    if let Some(disabled) = &action.disabled {
+        html.push_str(" (disabled: ");
+        html.push_str(&html_escape::encode_text(&disabled.reason));
+        html.push(')');
+    }
+
+    match &action.edit {
+        Some(edit) => append_workspace_edit_to(html, edit),
+        // The edit is often left for codeAction/resolve to fill in.
+        None if action.command.is_none() => html.push_str("<br/>No edit until resolved"),
+        None => {}
+    }
+    if let Some(command) = &action.command {
+        html.push_str("<br/>Then runs ");
+        append_command_to(html, command);
+    }
+}
+
+fn append_command_to(html: &mut String, command: &Command) {
+    html.push_str("<span class=\"code_action_title\">");
+    html.push_str(&html_escape::encode_text(&command.title));
+    html.push_str("</span>");
+    html.push_str(" <span class=\"code_action_kind\">command: ");
+    html.push_str(&html_escape::encode_text(&command.command));
+    html.push_str("</span>");
+}
+
+/// Lists the documents a workspace edit touches, with the ranges edited in each.
+fn append_workspace_edit_to(html: &mut String, edit: &WorkspaceEdit) {
+    html.push_str("<ul class=\"edit_targets\">");
+    if let Some(changes) = &edit.changes {
+        let mut changes = changes.iter().collect::<Vec<_>>();
+        changes.sort_by(|(uri1, _), (uri2, _)| uri1.as_str().cmp(uri2.as_str()));
+        for (uri, edits) in changes {
+            append_edit_target_to(
+                html,
+                uri.as_str(),
+                edits.iter().map(|edit| &edit.range).collect(),
+            );
+        }
+    }
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => {
+            for edit in edits {
+                append_text_document_edit_to(html, edit);
+            }
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                match operation {
+                    DocumentChangeOperation::Edit(edit) => append_text_document_edit_to(html, edit),
+                    DocumentChangeOperation::Op(operation) => {
+                        html.push_str("<li>");
+                        match operation {
+                            ResourceOp::Create(create) => {
+                                html.push_str("create ");
+                                html.push_str(&html_escape::encode_text(create.uri.as_str()));
+                            }
+                            ResourceOp::Rename(rename) => {
+                                html.push_str("rename ");
+                                html.push_str(&html_escape::encode_text(rename.old_uri.as_str()));
+                                html.push_str(" to ");
+                                html.push_str(&html_escape::encode_text(rename.new_uri.as_str()));
+                            }
+                            ResourceOp::Delete(delete) => {
+                                html.push_str("delete ");
+                                html.push_str(&html_escape::encode_text(delete.uri.as_str()));
+                            }
+                        }
+                        html.push_str("</li>");
+                    }
+                }
+            }
+        }
+        None => {}
+    }
+    html.push_str("</ul>");
+}
+
+fn append_text_document_edit_to(html: &mut String, edit: &TextDocumentEdit) {
+    append_edit_target_to(
+        html,
+        edit.text_document.uri.as_str(),
+        edit.edits
+            .iter()
+            .map(|edit| match edit {
+                OneOf::Left(edit) => &edit.range,
+                OneOf::Right(edit) => &edit.text_edit.range,
+            })
+            .collect(),
+    );
+}
+
+fn append_edit_target_to(html: &mut String, uri: &str, ranges: Vec<&Range>) {
+    html.push_str("<li>");
+    html.push_str(&html_escape::encode_text(uri));
+    html.push_str(": ");
+    html.push_str(
+        &ranges
+            .iter()
+            .map(|range| {
+                format!(
+                    "{}:{}-{}:{}",
+                    range.start.line, range.start.character, range.end.line, range.end.character
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    html.push_str("</li>");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn the_titles_of_a_code_action_response_are_rendered() {
+        let result = json!([
+            {
+                "title": "Import `HashMap`",
+                "kind": "quickfix",
+                "edit": { "changes": { "file:///main.rs": [{
+                    "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 0 } },
+                    "newText": "use std::collections::HashMap;\n",
+                }] } },
+            },
+            { "title": "Run test", "command": "rust-analyzer.runSingle" },
+        ]);
+
+        let mut html = String::new();
+        append_code_action_summary_to(&mut html, CodeActionRequest::METHOD, &result);
+
+        assert!(html.contains(
+            "<li><span class=\"code_action_title\">Import `HashMap`</span> <span class=\"code_action_kind\">quickfix</span><ul class=\"edit_targets\"><li>file:///main.rs: 0:0-0:0</li></ul></li>"
+        ));
+        assert!(html.contains(
+            "<li><span class=\"code_action_title\">Run test</span> <span class=\"code_action_kind\">command: rust-analyzer.runSingle</span></li>"
+        ));
+    }
+}
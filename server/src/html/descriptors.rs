@@ -0,0 +1,375 @@
+use lsp_types::{
+    CompletionResponse, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidOpenTextDocumentParams, DocumentChanges, DocumentDiagnosticReport,
+    DocumentDiagnosticReportResult, DocumentSymbolResponse, ExecuteCommandParams, FileChangeType,
+    GotoDefinitionResponse, HoverContents, Location, MarkedString, Position,
+    PublishDiagnosticsParams, Range, SemanticTokensResult, TextDocumentIdentifier,
+    TextDocumentPositionParams, WorkspaceEdit, WorkspaceSymbolParams, WorkspaceSymbolResponse,
+    notification::{
+        DidChangeTextDocument, DidChangeWatchedFiles, DidCloseTextDocument, DidOpenTextDocument,
+        DidSaveTextDocument, Notification, PublishDiagnostics, WillSaveTextDocument,
+    },
+    request::{
+        CallHierarchyPrepare, CodeActionRequest, CodeLensRequest, ColorPresentationRequest,
+        Completion, DocumentColor, DocumentDiagnosticRequest, DocumentHighlightRequest,
+        DocumentLinkRequest, DocumentSymbolRequest, ExecuteCommand, FoldingRangeRequest,
+        Formatting, GotoDeclaration, GotoDefinition, GotoImplementation, GotoTypeDefinition,
+        HoverRequest, InlayHintRequest, InlineValueRequest, LinkedEditingRange, MonikerRequest,
+        OnTypeFormatting, PrepareRenameRequest, RangeFormatting, References, Rename, Request,
+        SelectionRangeRequest, SemanticTokensFullDeltaRequest, SemanticTokensFullRequest,
+        SemanticTokensRangeRequest, SignatureHelpRequest, TypeHierarchyPrepare, WillSaveWaitUntil,
+        WorkspaceSymbolRequest,
+    },
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// How many files of a `workspace/didChangeWatchedFiles` notification, or locations of a
+/// response, are listed by name.
+const MAX_DESCRIBED_FILES: usize = 3;
+/// How many characters of a hover's first line are shown before it's cut short.
+const MAX_HOVER_LINE_CHARS: usize = 80;
+
+/// The params of methods that only name the document they act on.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentParams {
+    text_document: TextDocumentIdentifier,
+}
+
+/// The params of methods that act on a range of a document.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextDocumentRangeParams {
+    text_document: TextDocumentIdentifier,
+    range: Range,
+}
+
+/// Describes what a request or notification of `method` targets, like the document and
+/// position it was sent for, so that the chat can be scanned without opening each message.
+/// Returns `None` for methods without a known target and for params that don't parse.
+pub(crate) fn get_params_descriptor(method: &str, params: &Value) -> Option<String> {
+    match method {
+        HoverRequest::METHOD
+        | GotoDefinition::METHOD
+        | GotoDeclaration::METHOD
+        | GotoTypeDefinition::METHOD
+        | GotoImplementation::METHOD
+        | References::METHOD
+        | DocumentHighlightRequest::METHOD
+        | Completion::METHOD
+        | SignatureHelpRequest::METHOD
+        | PrepareRenameRequest::METHOD
+        | Rename::METHOD
+        | LinkedEditingRange::METHOD
+        | CallHierarchyPrepare::METHOD
+        | TypeHierarchyPrepare::METHOD
+        | MonikerRequest::METHOD
+        | OnTypeFormatting::METHOD => {
+            let params = parse::<TextDocumentPositionParams>(params)?;
+            Some(format!(
+                "{}:{}",
+                params.text_document.uri.as_str(),
+                get_position_string(&params.position)
+            ))
+        }
+        CodeActionRequest::METHOD
+        | RangeFormatting::METHOD
+        | InlayHintRequest::METHOD
+        | InlineValueRequest::METHOD
+        | SemanticTokensRangeRequest::METHOD
+        | ColorPresentationRequest::METHOD => {
+            let params = parse::<TextDocumentRangeParams>(params)?;
+            Some(format!(
+                "{}:{}-{}",
+                params.text_document.uri.as_str(),
+                get_position_string(&params.range.start),
+                get_position_string(&params.range.end)
+            ))
+        }
+        DocumentSymbolRequest::METHOD
+        | Formatting::METHOD
+        | CodeLensRequest::METHOD
+        | FoldingRangeRequest::METHOD
+        | SelectionRangeRequest::METHOD
+        | SemanticTokensFullRequest::METHOD
+        | SemanticTokensFullDeltaRequest::METHOD
+        | DocumentLinkRequest::METHOD
+        | DocumentColor::METHOD
+        | DocumentDiagnosticRequest::METHOD
+        | WillSaveWaitUntil::METHOD
+        | DidCloseTextDocument::METHOD
+        | DidSaveTextDocument::METHOD
+        | WillSaveTextDocument::METHOD => {
+            let params = parse::<TextDocumentParams>(params)?;
+            Some(params.text_document.uri.as_str().to_string())
+        }
+        DidOpenTextDocument::METHOD => {
+            let params = parse::<DidOpenTextDocumentParams>(params)?;
+            Some(format!(
+                "{} ({} v{})",
+                params.text_document.uri.as_str(),
+                params.text_document.language_id,
+                params.text_document.version
+            ))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params = parse::<DidChangeTextDocumentParams>(params)?;
+            Some(format!(
+                "{} v{}, {}",
+                params.text_document.uri.as_str(),
+                params.text_document.version,
+                get_count_string(params.content_changes.len(), "change", "changes")
+            ))
+        }
+        DidChangeWatchedFiles::METHOD => {
+            let params = parse::<DidChangeWatchedFilesParams>(params)?;
+            Some(get_list_string(params.changes.iter().map(|change| {
+                let change_type = match change.typ {
+                    FileChangeType::CREATED => "created",
+                    FileChangeType::CHANGED => "changed",
+                    FileChangeType::DELETED => "deleted",
+                    _ => "unknown change",
+                };
+                format!("{} ({change_type})", change.uri.as_str())
+            })))
+        }
+        PublishDiagnostics::METHOD => {
+            let params = parse::<PublishDiagnosticsParams>(params)?;
+            Some(format!(
+                "{}, {}",
+                params.uri.as_str(),
+                get_count_string(params.diagnostics.len(), "diagnostic", "diagnostics")
+            ))
+        }
+        WorkspaceSymbolRequest::METHOD => {
+            let params = parse::<WorkspaceSymbolParams>(params)?;
+            Some(format!("\"{}\"", params.query))
+        }
+        ExecuteCommand::METHOD => Some(parse::<ExecuteCommandParams>(params)?.command),
+        _ => None,
+    }
+}
+
+/// Summarizes the result of a response to a request of `method`, like how many completion items
+/// it holds or where a definition was found, by reading it as the method's result type. Returns
+/// `None` for methods without a known summary and for results that don't parse.
+pub(crate) fn get_result_descriptor(method: &str, result: &Value) -> Option<String> {
+    let descriptor = match method {
+        Completion::METHOD => match parse_result::<Completion>(result)? {
+            Some(CompletionResponse::Array(items)) => {
+                get_count_string(items.len(), "item", "items")
+            }
+            Some(CompletionResponse::List(list)) => {
+                let mut descriptor = get_count_string(list.items.len(), "item", "items");
+                if list.is_incomplete {
+                    descriptor.push_str(", incomplete");
+                }
+                descriptor
+            }
+            None => "no items".to_string(),
+        },
+        HoverRequest::METHOD => match parse_result::<HoverRequest>(result)? {
+            Some(hover) => get_hover_line(&hover.contents)?,
+            None => "no hover".to_string(),
+        },
+        GotoDefinition::METHOD => get_locations_string(parse_result::<GotoDefinition>(result)?),
+        GotoDeclaration::METHOD => get_locations_string(parse_result::<GotoDeclaration>(result)?),
+        GotoTypeDefinition::METHOD => {
+            get_locations_string(parse_result::<GotoTypeDefinition>(result)?)
+        }
+        GotoImplementation::METHOD => {
+            get_locations_string(parse_result::<GotoImplementation>(result)?)
+        }
+        References::METHOD => {
+            let count = parse_result::<References>(result)?.map_or(0, |locations| locations.len());
+            get_count_string(count, "reference", "references")
+        }
+        DocumentHighlightRequest::METHOD => {
+            let count = parse_result::<DocumentHighlightRequest>(result)?
+                .map_or(0, |highlights| highlights.len());
+            get_count_string(count, "highlight", "highlights")
+        }
+        DocumentSymbolRequest::METHOD => {
+            let count = match parse_result::<DocumentSymbolRequest>(result)? {
+                Some(DocumentSymbolResponse::Flat(symbols)) => symbols.len(),
+                Some(DocumentSymbolResponse::Nested(symbols)) => symbols.len(),
+                None => 0,
+            };
+            get_count_string(count, "symbol", "symbols")
+        }
+        WorkspaceSymbolRequest::METHOD => {
+            let count = match parse_result::<WorkspaceSymbolRequest>(result)? {
+                Some(WorkspaceSymbolResponse::Flat(symbols)) => symbols.len(),
+                Some(WorkspaceSymbolResponse::Nested(symbols)) => symbols.len(),
+                None => 0,
+            };
+            get_count_string(count, "symbol", "symbols")
+        }
+        CodeActionRequest::METHOD => {
+            let count =
+                parse_result::<CodeActionRequest>(result)?.map_or(0, |actions| actions.len());
+            get_count_string(count, "action", "actions")
+        }
+        CodeLensRequest::METHOD => {
+            let count = parse_result::<CodeLensRequest>(result)?.map_or(0, |lenses| lenses.len());
+            get_count_string(count, "code lens", "code lenses")
+        }
+        DocumentLinkRequest::METHOD => {
+            let count = parse_result::<DocumentLinkRequest>(result)?.map_or(0, |links| links.len());
+            get_count_string(count, "link", "links")
+        }
+        FoldingRangeRequest::METHOD => {
+            let count =
+                parse_result::<FoldingRangeRequest>(result)?.map_or(0, |ranges| ranges.len());
+            get_count_string(count, "range", "ranges")
+        }
+        InlayHintRequest::METHOD => {
+            let count = parse_result::<InlayHintRequest>(result)?.map_or(0, |hints| hints.len());
+            get_count_string(count, "hint", "hints")
+        }
+        Formatting::METHOD => get_edits_string(parse_result::<Formatting>(result)?),
+        RangeFormatting::METHOD => get_edits_string(parse_result::<RangeFormatting>(result)?),
+        OnTypeFormatting::METHOD => get_edits_string(parse_result::<OnTypeFormatting>(result)?),
+        Rename::METHOD => match parse_result::<Rename>(result)? {
+            Some(edit) => get_count_string(get_edited_file_count(&edit), "file", "files"),
+            None => "no edits".to_string(),
+        },
+        SignatureHelpRequest::METHOD => {
+            let help = parse_result::<SignatureHelpRequest>(result)?;
+            let signature = help.as_ref().and_then(|help| {
+                let active = help.active_signature.unwrap_or(0) as usize;
+                help.signatures
+                    .get(active)
+                    .or_else(|| help.signatures.first())
+            });
+            match signature {
+                Some(signature) => signature.label.clone(),
+                None => "no signatures".to_string(),
+            }
+        }
+        SemanticTokensFullRequest::METHOD => {
+            let count = match parse_result::<SemanticTokensFullRequest>(result)? {
+                Some(SemanticTokensResult::Tokens(tokens)) => tokens.data.len(),
+                Some(SemanticTokensResult::Partial(partial)) => partial.data.len(),
+                None => 0,
+            };
+            get_count_string(count, "token", "tokens")
+        }
+        DocumentDiagnosticRequest::METHOD => {
+            match parse_result::<DocumentDiagnosticRequest>(result)? {
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(report)) => {
+                    get_count_string(
+                        report.full_document_diagnostic_report.items.len(),
+                        "diagnostic",
+                        "diagnostics",
+                    )
+                }
+                DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(_)) => {
+                    "unchanged".to_string()
+                }
+                DocumentDiagnosticReportResult::Partial(_) => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(descriptor)
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: &Value) -> Option<T> {
+    T::deserialize(params).ok()
+}
+
+fn parse_result<R: Request>(result: &Value) -> Option<R::Result> {
+    R::Result::deserialize(result).ok()
+}
+
+fn get_count_string(count: usize, singular: &str, plural: &str) -> String {
+    format!("{count} {}", if count == 1 { singular } else { plural })
+}
+
+/// Joins the first `MAX_DESCRIBED_FILES` items, saying how many more there are.
+fn get_list_string(items: impl ExactSizeIterator<Item = String>) -> String {
+    let count = items.len();
+    let mut list = items
+        .take(MAX_DESCRIBED_FILES)
+        .collect::<Vec<_>>()
+        .join(", ");
+    if count > MAX_DESCRIBED_FILES {
+        list.push_str(&format!(" and {} more", count - MAX_DESCRIBED_FILES));
+    }
+    list
+}
+
+fn get_locations_string(response: Option<GotoDefinitionResponse>) -> String {
+    let get_location_string = |uri: &lsp_types::Uri, position: &Position| {
+        format!("{}:{}", uri.as_str(), get_position_string(position))
+    };
+    let locations = match response {
+        Some(GotoDefinitionResponse::Scalar(location)) => vec![location],
+        Some(GotoDefinitionResponse::Array(locations)) => locations,
+        Some(GotoDefinitionResponse::Link(links)) => {
+            if links.is_empty() {
+                return "no locations".to_string();
+            }
+            return get_list_string(links.iter().map(|link| {
+                get_location_string(&link.target_uri, &link.target_selection_range.start)
+            }));
+        }
+        None => Vec::new(),
+    };
+    if locations.is_empty() {
+        return "no locations".to_string();
+    }
+    get_list_string(
+        locations
+            .iter()
+            .map(|Location { uri, range }| get_location_string(uri, &range.start)),
+    )
+}
+
+fn get_edits_string(edits: Option<Vec<lsp_types::TextEdit>>) -> String {
+    get_count_string(edits.map_or(0, |edits| edits.len()), "edit", "edits")
+}
+
+/// Counts the documents a workspace edit changes, whichever way it lists them.
+fn get_edited_file_count(edit: &WorkspaceEdit) -> usize {
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(edits)) => edits.len(),
+        Some(DocumentChanges::Operations(operations)) => operations.len(),
+        None => edit.changes.as_ref().map_or(0, |changes| changes.len()),
+    }
+}
+
+/// The first line of a hover's text, skipping the fences of code blocks, cut short when it's
+/// long.
+fn get_hover_line(contents: &HoverContents) -> Option<String> {
+    let text = match contents {
+        HoverContents::Scalar(marked_string) => get_marked_string_text(marked_string),
+        HoverContents::Array(marked_strings) => get_marked_string_text(marked_strings.first()?),
+        HoverContents::Markup(markup) => &markup.value,
+    };
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("```"))?;
+    if line.chars().count() > MAX_HOVER_LINE_CHARS {
+        let mut line = line.chars().take(MAX_HOVER_LINE_CHARS).collect::<String>();
+        line.push('…');
+        Some(line)
+    } else {
+        Some(line.to_string())
+    }
+}
+
+fn get_marked_string_text(marked_string: &MarkedString) -> &str {
+    match marked_string {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(language_string) => &language_string.value,
+    }
+}
+
+/// Formats a position as the protocol's zero based `line:character`.
+fn get_position_string(position: &Position) -> String {
+    format!("{}:{}", position.line, position.character)
+}
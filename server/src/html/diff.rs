@@ -0,0 +1,317 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use lsp_server::Message;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{
+        chat_view::{
+            JsonRenderOptions, append_json_html_to, append_json_kvp_to, append_message_url_to,
+        },
+        stylesheet::Stylesheet,
+    },
+    session::MessageWithTimeStamp,
+    utils::get_iso_string,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct GetMessageDiffParams {
+    session_id: i64,
+    /// The position of the message to diff from in the session, counting from zero.
+    a: Option<usize>,
+    /// The position of the message to diff to, counting from zero.
+    b: Option<usize>,
+}
+
+/// How a value differs between two messages.
+enum JsonDiff {
+    Unchanged(Value),
+    Added(Value),
+    Removed(Value),
+    /// A value replaced by one that can't be compared with it entry by entry, like a string by
+    /// another string or an object by an array.
+    Changed {
+        before: Value,
+        after: Value,
+    },
+    /// An object with differing entries, compared by key.
+    Object(Vec<(String, JsonDiff)>),
+    /// An array with differing elements, compared by position.
+    Array(Vec<JsonDiff>),
+}
+
+impl JsonDiff {
+    fn new(before: Value, after: Value) -> Self {
+        if before == after {
+            return JsonDiff::Unchanged(before);
+        }
+        match (before, after) {
+            (Value::Object(mut before), Value::Object(after)) => {
+                let mut entries = Vec::new();
+                let mut added = Vec::new();
+                for (key, after) in after {
+                    match before.remove(&key) {
+                        Some(before) => entries.push((key, JsonDiff::new(before, after))),
+                        None => added.push((key, JsonDiff::Added(after))),
+                    }
+                }
+                // Keys only in the first message are listed before those only in the second.
+                entries.extend(
+                    before
+                        .into_iter()
+                        .map(|(key, before)| (key, JsonDiff::Removed(before))),
+                );
+                entries.extend(added);
+                JsonDiff::Object(entries)
+            }
+            (Value::Array(before), Value::Array(after)) => {
+                let mut before = before.into_iter();
+                let mut after = after.into_iter();
+                let mut elements = Vec::new();
+                loop {
+                    elements.push(match (before.next(), after.next()) {
+                        (Some(before), Some(after)) => JsonDiff::new(before, after),
+                        (Some(before), None) => JsonDiff::Removed(before),
+                        (None, Some(after)) => JsonDiff::Added(after),
+                        (None, None) => break,
+                    });
+                }
+                JsonDiff::Array(elements)
+            }
+            (before, after) => JsonDiff::Changed { before, after },
+        }
+    }
+}
+
+/// Shows how two messages of a session differ, as a single structural diff of their JSON. The
+/// parts both messages share are collapsed, so that subtle changes between similar messages,
+/// like two completion lists, stand out.
+pub(crate) async fn get_message_diff(
+    State(state): State<AppState>,
+    Query(request): Query<GetMessageDiffParams>,
+) -> Result<Html<String>, AppError> {
+    sqlx::query!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let messages = match (request.a, request.b) {
+        (Some(a), Some(b)) => {
+            let message_a = conversation.messages().get(a);
+            let message_b = conversation.messages().get(b);
+            Some(message_a.zip(message_b).ok_or_else(|| {
+                AppError::bad_request(format!(
+                    "session {} has no message at position {a} or {b}",
+                    request.session_id
+                ))
+            })?)
+        }
+        (None, None) => None,
+        _ => return Err(AppError::bad_request("a and b must be given together")),
+    };
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Chat.append_to(&mut html, &state.config);
+    html.push_str("<style>");
+    state.config.default_theme.append_css_to(&mut html);
+    html.push_str("</style>");
+
+    html.push_str("<h1>Session ");
+    html.push_str(&request.session_id.to_string());
+    html.push_str(" Message Diff</h1>");
+    html.push_str("<a href=\"/session?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Back to session</a>");
+
+    html.push_str("<form action=\"/session/diff\" method=\"GET\">");
+    html.push_str("<input type=\"hidden\" name=\"session_id\" value=\"");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">");
+    let max_index = conversation.messages().len().saturating_sub(1);
+    for (id, label, index) in [
+        ("a", "Diff message", request.a),
+        ("b", "with message", request.b),
+    ] {
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("<input type=\"number\" min=\"0\" max=\"");
+        html.push_str(&max_index.to_string());
+        html.push_str("\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"");
+        if let Some(index) = index {
+            html.push_str(&index.to_string());
+        }
+        html.push_str("\">");
+    }
+    html.push_str("<button type=\"submit\">Diff</button>");
+    html.push_str("</form>");
+
+    if let Some((a, b)) = messages {
+        html.push_str("<ul class=\"diff_messages\">");
+        append_diffed_message_to(&mut html, "diff_removed", request.session_id, a);
+        append_diffed_message_to(&mut html, "diff_added", request.session_id, b);
+        html.push_str("</ul>");
+
+        let diff = JsonDiff::new(to_value(a)?, to_value(b)?);
+        if matches!(diff, JsonDiff::Unchanged(_)) {
+            html.push_str("<p>The messages are identical.</p>");
+        }
+        html.push_str("<div class=\"diff\">");
+        append_json_diff_to(&mut html, None, diff, &JsonRenderOptions::default());
+        html.push_str("</div>");
+    }
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
+
+fn to_value(message: &MessageWithTimeStamp) -> Result<Value, AppError> {
+    serde_json::to_value(&message.message)
+        .map_err(|_| AppError::internal(format!("failed to serialize message {}", message.seq)))
+}
+
+/// Writes which message a side of the diff is, linking to it in the session.
+fn append_diffed_message_to(
+    html: &mut String,
+    class: &str,
+    session_id: i64,
+    message: &MessageWithTimeStamp,
+) {
+    html.push_str("<li class=\"");
+    html.push_str(class);
+    html.push_str("\"><a href=\"");
+    append_message_url_to(html, session_id, message.seq);
+    html.push_str("\">");
+    match &message.message {
+        Message::Request(request) => {
+            html.push_str("Request ");
+            html.push_str(&html_escape::encode_text(&request.method));
+        }
+        Message::Response(response) => {
+            html.push_str("Response to ");
+            html.push_str(&html_escape::encode_text(&response.id.to_string()));
+        }
+        Message::Notification(notification) => {
+            html.push_str("Notification ");
+            html.push_str(&html_escape::encode_text(&notification.method));
+        }
+    }
+    html.push_str(" at ");
+    html.push_str(&get_iso_string(&message.time_stamp));
+    html.push_str("</a></li>");
+}
+
+/// Writes a diff as JSON, with each added, removed, or changed value marked by its class.
+fn append_json_diff_to(
+    html: &mut String,
+    key: Option<&str>,
+    diff: JsonDiff,
+    options: &JsonRenderOptions,
+) {
+    match diff {
+        JsonDiff::Unchanged(value) => {
+            append_json_diff_value_to(html, "diff_unchanged", key, value, options);
+        }
+        JsonDiff::Added(value) => {
+            append_json_diff_value_to(html, "diff_added", key, value, options);
+        }
+        JsonDiff::Removed(value) => {
+            append_json_diff_value_to(html, "diff_removed", key, value, options);
+        }
+        JsonDiff::Changed { before, after } => {
+            append_json_diff_value_to(html, "diff_removed", key, before, options);
+            append_json_diff_value_to(html, "diff_added", key, after, options);
+        }
+        JsonDiff::Object(entries) => {
+            append_json_diff_container_start_to(html, key, "object", "{}");
+            for (key, diff) in entries {
+                append_json_diff_to(html, Some(&key), diff, options);
+            }
+            html.push_str("</div>");
+            html.push_str("</details>");
+        }
+        JsonDiff::Array(elements) => {
+            append_json_diff_container_start_to(html, key, "array", "[]");
+            for diff in elements {
+                append_json_diff_to(html, None, diff, options);
+            }
+            html.push_str("</div>");
+            html.push_str("</details>");
+        }
+    }
+}
+
+/// Writes a value that's the same, or entirely different, in both messages. Unchanged objects
+/// and arrays under a key are collapsed like they are in the chat.
+fn append_json_diff_value_to(
+    html: &mut String,
+    class: &str,
+    key: Option<&str>,
+    value: Value,
+    options: &JsonRenderOptions,
+) {
+    html.push_str("<div class=\"");
+    html.push_str(class);
+    html.push_str("\">");
+    match key {
+        Some(key) => append_json_kvp_to(html, (key.to_string(), value), options),
+        None => append_json_html_to(html, value, options),
+    }
+    html.push_str("</div>");
+}
+
+/// Opens an object or array whose entries differ, leaving its entries and the closing tags to
+/// the caller.
+fn append_json_diff_container_start_to(
+    html: &mut String,
+    key: Option<&str>,
+    container: &str,
+    brackets: &str,
+) {
+    html.push_str("<details open class=\"");
+    html.push_str(container);
+    html.push_str("_container diff_changed\">");
+    html.push_str("<summary>");
+    if let Some(key) = key {
+        html.push('"');
+        html.push_str(&html_escape::encode_text(key));
+        html.push_str("\": ");
+    }
+    html.push_str(brackets);
+    html.push_str("</summary>");
+    html.push_str("<div class=\"");
+    html.push_str(container);
+    html.push_str("_content\">");
+}
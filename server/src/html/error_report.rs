@@ -0,0 +1,152 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{append_method_cell_to, chat_view::append_message_url_to, stylesheet::Stylesheet},
+    utils::deserialize_optional_id,
+};
+
+/// How many of the latest errors of each group are shown as examples.
+const EXAMPLE_COUNT: i32 = 3;
+
+#[derive(Deserialize)]
+pub(crate) struct GetErrorReportParams {
+    /// Only report on the errors of this session.
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    session_id: Option<i64>,
+    /// Cut method names longer than this many characters short.
+    method_width: Option<usize>,
+}
+
+/// Every error response, grouped by its code and the method of the request it answered, so that
+/// protocol problems can be found without reading through each session.
+pub(crate) async fn get_error_report(
+    State(state): State<AppState>,
+    Query(request): Query<GetErrorReportParams>,
+) -> Result<Html<String>, AppError> {
+    // A response answers the latest request with its id that was logged before it in the same
+    // session, matching how the session view pairs them. Errors that answer no logged request
+    // are grouped under no method.
+    let records = sqlx::query!(
+        r#"WITH errors AS (
+            SELECT responses.error_code, requests.method, responses.error_message, responses.session_id, responses.seq
+            FROM responses
+            LEFT JOIN LATERAL (
+                SELECT method FROM requests
+                WHERE requests.session_id = responses.session_id
+                    AND requests.request_id = responses.request_id
+                    AND requests.seq < responses.seq
+                ORDER BY requests.seq DESC
+                LIMIT 1
+            ) requests ON TRUE
+            WHERE responses.is_error
+                AND ($1::BIGINT IS NULL OR responses.session_id = $1)
+        )
+        SELECT error_code,
+            method AS "method?",
+            COUNT(*) AS "count!",
+            (ARRAY_AGG(session_id ORDER BY seq DESC))[1:$2] AS "example_session_ids!",
+            (ARRAY_AGG(seq ORDER BY seq DESC))[1:$2] AS "example_seqs!",
+            (ARRAY_AGG(COALESCE(error_message, '') ORDER BY seq DESC))[1:$2] AS "example_messages!"
+        FROM errors
+        GROUP BY error_code, method
+        ORDER BY 3 DESC, error_code, method"#,
+        request.session_id,
+        EXAMPLE_COUNT
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|_| AppError::internal("failed to read the error responses"))?;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+
+    match request.session_id {
+        Some(session_id) => {
+            html.push_str("<h1>Session ");
+            html.push_str(&session_id.to_string());
+            html.push_str(" Errors</h1>");
+            html.push_str("<a href=\"/session?session_id=");
+            html.push_str(&session_id.to_string());
+            html.push_str("\">Back to session</a>");
+            html.push_str(" <a href=\"/errors\">Errors across sessions</a>");
+        }
+        None => {
+            html.push_str("<h1>Errors Across Sessions</h1>");
+            html.push_str("<a href=\"/\">Back to sessions</a>");
+        }
+    }
+
+    if records.is_empty() {
+        html.push_str("<p>No error responses were logged.</p>");
+    } else {
+        html.push_str("<table>");
+        html.push_str("<tr>");
+        html.push_str("<th>Code</th>");
+        html.push_str("<th>Method</th>");
+        html.push_str("<th>Count</th>");
+        html.push_str("<th>Latest Errors</th>");
+        html.push_str("</tr>");
+
+        for record in records.iter() {
+            html.push_str("<tr>");
+
+            html.push_str("<td>");
+            if let Some(error_code) = record.error_code {
+                html.push_str(&error_code.to_string());
+            }
+            html.push_str("</td>");
+
+            html.push_str("<td>");
+            match &record.method {
+                Some(method) => append_method_cell_to(&mut html, method, request.method_width),
+                None => html.push_str("<em>No logged request</em>"),
+            }
+            html.push_str("</td>");
+
+            html.push_str("<td>");
+            html.push_str(&record.count.to_string());
+            html.push_str("</td>");
+
+            html.push_str("<td><ul class=\"error_examples\">");
+            for ((session_id, seq), message) in record
+                .example_session_ids
+                .iter()
+                .zip(record.example_seqs.iter())
+                .zip(record.example_messages.iter())
+            {
+                html.push_str("<li><a href=\"");
+                append_message_url_to(&mut html, *session_id, *seq);
+                html.push_str("\">Session ");
+                html.push_str(&session_id.to_string());
+                html.push_str("</a>: ");
+                html.push_str(&html_escape::encode_text(message));
+                html.push_str("</li>");
+            }
+            html.push_str("</ul></td>");
+
+            html.push_str("</tr>");
+        }
+
+        html.push_str("</table>");
+    }
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
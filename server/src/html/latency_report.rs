@@ -0,0 +1,252 @@
+use axum::{
+    extract::{Query, RawQuery, State},
+    response::Html,
+};
+use serde::Deserialize;
+use time::Duration;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{append_method_cell_to, parse_time_param, stylesheet::Stylesheet},
+    utils::{get_datetime_local_string, get_duration_string},
+};
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LatencyReportColumn {
+    Method,
+    Count,
+    P50,
+    P90,
+    P99,
+    Max,
+}
+
+impl LatencyReportColumn {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LatencyReportColumn::Method => "method",
+            LatencyReportColumn::Count => "count",
+            LatencyReportColumn::P50 => "p50",
+            LatencyReportColumn::P90 => "p90",
+            LatencyReportColumn::P99 => "p99",
+            LatencyReportColumn::Max => "max",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            LatencyReportColumn::Method => "Method",
+            LatencyReportColumn::Count => "Answered",
+            LatencyReportColumn::P50 => "p50",
+            LatencyReportColumn::P90 => "p90",
+            LatencyReportColumn::P99 => "p99",
+            LatencyReportColumn::Max => "Max",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GetLatencyReportParams {
+    /// Only report on requests of this method.
+    method: Option<String>,
+    /// Only include sessions that started at or after this time. Either RFC 3339 or the value of
+    /// a `datetime-local` input in UTC.
+    from: Option<String>,
+    /// Only include sessions that started at or before this time, in the same formats as `from`.
+    to: Option<String>,
+    /// Defaults to the slowest methods at the median first.
+    sort: Option<LatencyReportColumn>,
+    asc: Option<bool>,
+    /// Cut method names longer than this many characters short.
+    method_width: Option<usize>,
+}
+
+struct MethodLatencies {
+    method: String,
+    /// How many requests of the method were answered.
+    count: i64,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+/// Latency percentiles of each method over the answered requests of every session, to compare
+/// a language server across many runs rather than within one.
+pub(crate) async fn get_latency_report(
+    State(state): State<AppState>,
+    Query(request): Query<GetLatencyReportParams>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Html<String>, AppError> {
+    let from = parse_time_param(request.from.as_deref())?;
+    let to = parse_time_param(request.to.as_deref())?;
+    let method = request
+        .method
+        .as_deref()
+        .map(str::trim)
+        .filter(|method| !method.is_empty());
+
+    // A response answers the latest request with its id that was logged before it in the same
+    // session, matching how the session view pairs them.
+    let records = sqlx::query!(
+        r#"WITH latencies AS (
+            SELECT requests.method, EXTRACT(EPOCH FROM responses.time_stamp - requests.time_stamp)::DOUBLE PRECISION AS seconds
+            FROM responses
+            JOIN sessions ON sessions.id = responses.session_id
+            CROSS JOIN LATERAL (
+                SELECT method, time_stamp FROM requests
+                WHERE requests.session_id = responses.session_id
+                    AND requests.request_id = responses.request_id
+                    AND requests.seq < responses.seq
+                ORDER BY requests.seq DESC
+                LIMIT 1
+            ) requests
+            WHERE ($1::TEXT IS NULL OR requests.method = $1)
+                AND ($2::TIMESTAMPTZ IS NULL OR sessions.start_time_stamp >= $2)
+                AND ($3::TIMESTAMPTZ IS NULL OR sessions.start_time_stamp <= $3)
+        )
+        SELECT method,
+            COUNT(*) AS "count!",
+            percentile_cont(0.5) WITHIN GROUP (ORDER BY seconds) AS "p50!",
+            percentile_cont(0.9) WITHIN GROUP (ORDER BY seconds) AS "p90!",
+            percentile_cont(0.99) WITHIN GROUP (ORDER BY seconds) AS "p99!",
+            MAX(seconds) AS "max!"
+        FROM latencies
+        GROUP BY method"#,
+        method,
+        from,
+        to
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|_| AppError::internal("failed to read the latencies"))?;
+
+    let sort = request.sort.unwrap_or(LatencyReportColumn::P50);
+    let asc = request.asc.unwrap_or(false);
+    let mut report = records
+        .into_iter()
+        .map(|record| MethodLatencies {
+            method: record.method,
+            count: record.count,
+            p50: Duration::seconds_f64(record.p50),
+            p90: Duration::seconds_f64(record.p90),
+            p99: Duration::seconds_f64(record.p99),
+            max: Duration::seconds_f64(record.max),
+        })
+        .collect::<Vec<_>>();
+    report.sort_by(|latencies1, latencies2| {
+        let ordering = match sort {
+            LatencyReportColumn::Method => latencies1.method.cmp(&latencies2.method),
+            LatencyReportColumn::Count => latencies1.count.cmp(&latencies2.count),
+            LatencyReportColumn::P50 => latencies1.p50.cmp(&latencies2.p50),
+            LatencyReportColumn::P90 => latencies1.p90.cmp(&latencies2.p90),
+            LatencyReportColumn::P99 => latencies1.p99.cmp(&latencies2.p99),
+            LatencyReportColumn::Max => latencies1.max.cmp(&latencies2.max),
+        };
+        let ordering = if asc { ordering } else { ordering.reverse() };
+        ordering.then_with(|| latencies1.method.cmp(&latencies2.method))
+    });
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+
+    html.push_str("<h1>Latency Across Sessions</h1>");
+    html.push_str("<a href=\"/\">Back to sessions</a>");
+
+    html.push_str("<form action=\"/stats\" method=\"GET\">");
+    html.push_str("<label for=\"method\">Method</label>");
+    html.push_str("<input type=\"text\" id=\"method\" name=\"method\" value=\"");
+    if let Some(method) = method {
+        html.push_str(&html_escape::encode_double_quoted_attribute(method));
+    }
+    html.push_str("\">");
+    for (id, label, time_stamp) in [("from", "Sessions started from", from), ("to", "to", to)] {
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("<input type=\"datetime-local\" step=\"0.001\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"");
+        if let Some(time_stamp) = time_stamp {
+            html.push_str(&get_datetime_local_string(&time_stamp));
+        }
+        html.push_str("\">");
+    }
+    html.push_str("<button type=\"submit\">Filter</button>");
+    html.push_str("</form>");
+
+    // The sort links keep the rest of the query, so sorting doesn't clear the filters.
+    let filter_query = raw_query
+        .as_deref()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && name != "sort" && name != "asc"
+        })
+        .map(|pair| format!("&{pair}"))
+        .collect::<String>();
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    for column in [
+        LatencyReportColumn::Method,
+        LatencyReportColumn::Count,
+        LatencyReportColumn::P50,
+        LatencyReportColumn::P90,
+        LatencyReportColumn::P99,
+        LatencyReportColumn::Max,
+    ] {
+        html.push_str("<th><a href=\"/stats?sort=");
+        html.push_str(column.as_str());
+        // Clicking the sorted column again flips its direction.
+        html.push_str("&asc=");
+        html.push_str(&(column == sort && !asc).to_string());
+        html.push_str(&html_escape::encode_double_quoted_attribute(&filter_query));
+        html.push_str("\">");
+        html.push_str(column.label());
+        html.push_str("</a></th>");
+    }
+    html.push_str("</tr>");
+
+    for latencies in report.iter() {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        append_method_cell_to(&mut html, &latencies.method, request.method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&latencies.count.to_string());
+        html.push_str("</td>");
+
+        for latency in [latencies.p50, latencies.p90, latencies.p99, latencies.max] {
+            html.push_str("<td>");
+            html.push_str(&get_duration_string(&latency));
+            html.push_str("</td>");
+        }
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
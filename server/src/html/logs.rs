@@ -0,0 +1,180 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use serde::Deserialize;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{
+        chat_view::{JsonRenderOptions, append_json_html_to},
+        stylesheet::Stylesheet,
+    },
+    utils::get_iso_string,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct GetSessionLogsParams {
+    session_id: i64,
+}
+
+pub(crate) async fn get_session_logs(
+    State(state): State<AppState>,
+    Query(request): Query<GetSessionLogsParams>,
+) -> Result<Html<String>, AppError> {
+    let logs = sqlx::query!(
+        "SELECT id, time_stamp, message FROM logs WHERE session_id = $1 ORDER BY time_stamp, id;",
+        request.session_id
+    )
+    .fetch_all(&state.db)
+    .await
+    .map_err(|_| {
+        AppError::internal(format!(
+            "failed to read the logs of session {}",
+            request.session_id
+        ))
+    })?;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+
+    html.push_str("<h1>Session ");
+    html.push_str(&request.session_id.to_string());
+    html.push_str(" Logs</h1>");
+    html.push_str("<a href=\"/session?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Back to session</a>");
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Logged At</th>");
+    html.push_str("<th>Message</th>");
+    html.push_str("</tr>");
+    for log in logs {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        html.push_str("<a href=\"/session/logs/entry?log_id=");
+        html.push_str(&log.id.to_string());
+        html.push_str("\">");
+        html.push_str(&get_iso_string(&log.time_stamp));
+        html.push_str("</a>");
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&html_escape::encode_text(&log.message));
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GetLogParams {
+    log_id: i64,
+}
+
+/// Shows a single log entry along with the structured fields recorded with it.
+pub(crate) async fn get_log(
+    State(state): State<AppState>,
+    Query(request): Query<GetLogParams>,
+) -> Result<Html<String>, AppError> {
+    let log = sqlx::query!(
+        "SELECT id, session_id, time_stamp, message, fields FROM logs WHERE id = $1 LIMIT 1;",
+        request.log_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| match err {
+        sqlx::Error::RowNotFound => {
+            AppError::not_found(format!("log {} not found", request.log_id))
+        }
+        _ => AppError::internal(format!("failed to read log {}", request.log_id)),
+    })?;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Chat.append_to(&mut html, &state.config);
+    html.push_str("<style>");
+    state.config.default_theme.append_css_to(&mut html);
+    html.push_str("</style>");
+
+    html.push_str("<h1>Log ");
+    html.push_str(&log.id.to_string());
+    html.push_str("</h1>");
+    if let Some(session_id) = log.session_id {
+        html.push_str("<a href=\"/session/logs?session_id=");
+        html.push_str(&session_id.to_string());
+        html.push_str("\">Back to session logs</a>");
+    }
+
+    html.push_str("<p>");
+    html.push_str(&get_iso_string(&log.time_stamp));
+    html.push_str(": ");
+    html.push_str(&html_escape::encode_text(&log.message));
+    html.push_str("</p>");
+
+    html.push_str("<div>");
+    match log.fields {
+        Some(fields) => append_json_html_to(&mut html, fields, &JsonRenderOptions::default()),
+        None => html.push_str("No fields were recorded."),
+    }
+    html.push_str("</div>");
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn a_log_entry_renders_its_fields_as_collapsible_json(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let log_id = sqlx::query_scalar!(
+            "INSERT INTO logs (time_stamp, message, fields) VALUES (NOW(), 'Malformed lsp_message', $1) RETURNING id;",
+            json!({ "error": "EOF while parsing", "frame": { "length": 12 } })
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+
+        let Html(html) = get_log(State(state), Query(GetLogParams { log_id }))
+            .await
+            .unwrap();
+
+        assert!(html.contains("<details open class=\"object_container\"><summary>{}</summary>"));
+        assert!(html.contains("\"frame\": {}</summary>"));
+        assert!(html.contains("\"length\": 12<br/>"));
+    }
+}
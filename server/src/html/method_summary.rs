@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use lsp_server::Message;
+use serde::Deserialize;
+use time::Duration;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{append_method_cell_to, stylesheet::Stylesheet},
+    message::{MessageKind, classify},
+    utils::get_duration_string,
+};
+
+#[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MethodSummaryColumn {
+    Method,
+    Kind,
+    Count,
+    TotalLatency,
+    AverageLatency,
+}
+
+impl MethodSummaryColumn {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MethodSummaryColumn::Method => "method",
+            MethodSummaryColumn::Kind => "kind",
+            MethodSummaryColumn::Count => "count",
+            MethodSummaryColumn::TotalLatency => "total_latency",
+            MethodSummaryColumn::AverageLatency => "average_latency",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            MethodSummaryColumn::Method => "Method",
+            MethodSummaryColumn::Kind => "Kind",
+            MethodSummaryColumn::Count => "Count",
+            MethodSummaryColumn::TotalLatency => "Total Latency",
+            MethodSummaryColumn::AverageLatency => "Average Latency",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GetMethodSummaryParams {
+    session_id: i64,
+    /// Defaults to the most frequent methods first.
+    sort: Option<MethodSummaryColumn>,
+    asc: Option<bool>,
+    /// Cut method names longer than this many characters short.
+    method_width: Option<usize>,
+}
+
+#[derive(Default)]
+struct MethodSummary<'a> {
+    method: &'a str,
+    kind: Option<MessageKind>,
+    /// How many requests or notifications used the method.
+    count: usize,
+    answered_count: u32,
+    total_latency: Duration,
+}
+
+impl MethodSummary<'_> {
+    fn average_latency(&self) -> Option<Duration> {
+        (self.answered_count > 0).then(|| self.total_latency / self.answered_count)
+    }
+}
+
+/// An overview of which methods a session used and how long their requests took, to spot the
+/// methods that dominated it before reading the chat.
+pub(crate) async fn get_method_summary(
+    State(state): State<AppState>,
+    Query(request): Query<GetMethodSummaryParams>,
+) -> Result<Html<String>, AppError> {
+    sqlx::query!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let mut summaries = HashMap::<&str, MethodSummary>::new();
+    for message in conversation.messages() {
+        // Responses are counted through the latency of the request they answer.
+        let method = match &message.message {
+            Message::Request(request) => &request.method,
+            Message::Notification(notification) => &notification.method,
+            Message::Response(_) => continue,
+        };
+        let summary = summaries.entry(method).or_insert_with(|| MethodSummary {
+            method,
+            kind: classify(&message.message, &conversation),
+            ..Default::default()
+        });
+        summary.count += 1;
+    }
+    for pair in conversation.pair_requests_with_responses() {
+        if let (Message::Request(request), Some(latency)) = (&pair.request.message, pair.latency())
+            && let Some(summary) = summaries.get_mut(request.method.as_str())
+        {
+            summary.answered_count += 1;
+            summary.total_latency += latency;
+        }
+    }
+
+    let sort = request.sort.unwrap_or(MethodSummaryColumn::Count);
+    let asc = request.asc.unwrap_or(false);
+    let mut summaries = summaries.into_values().collect::<Vec<_>>();
+    summaries.sort_by(|summary1, summary2| {
+        let ordering = match sort {
+            MethodSummaryColumn::Method => summary1.method.cmp(summary2.method),
+            MethodSummaryColumn::Kind => summary1
+                .kind
+                .map(|kind| kind.as_str())
+                .cmp(&summary2.kind.map(|kind| kind.as_str())),
+            MethodSummaryColumn::Count => summary1.count.cmp(&summary2.count),
+            MethodSummaryColumn::TotalLatency => {
+                summary1.total_latency.cmp(&summary2.total_latency)
+            }
+            MethodSummaryColumn::AverageLatency => {
+                summary1.average_latency().cmp(&summary2.average_latency())
+            }
+        };
+        let ordering = if asc { ordering } else { ordering.reverse() };
+        ordering.then_with(|| summary1.method.cmp(summary2.method))
+    });
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+
+    html.push_str("<h1>Session ");
+    html.push_str(&request.session_id.to_string());
+    html.push_str(" Methods</h1>");
+    html.push_str("<a href=\"/session?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Back to session</a>");
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    for column in [
+        MethodSummaryColumn::Method,
+        MethodSummaryColumn::Kind,
+        MethodSummaryColumn::Count,
+        MethodSummaryColumn::TotalLatency,
+        MethodSummaryColumn::AverageLatency,
+    ] {
+        html.push_str("<th><a href=\"/session/summary?session_id=");
+        html.push_str(&request.session_id.to_string());
+        html.push_str("&sort=");
+        html.push_str(column.as_str());
+        // Clicking the sorted column again flips its direction.
+        html.push_str("&asc=");
+        html.push_str(&(column == sort && !asc).to_string());
+        if let Some(method_width) = request.method_width {
+            html.push_str("&method_width=");
+            html.push_str(&method_width.to_string());
+        }
+        html.push_str("\">");
+        html.push_str(column.label());
+        html.push_str("</a></th>");
+    }
+    html.push_str("</tr>");
+
+    for summary in summaries.iter() {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        append_method_cell_to(&mut html, summary.method, request.method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(
+            summary
+                .kind
+                .map(|kind| kind.as_str())
+                .unwrap_or("uncategorized"),
+        );
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&summary.count.to_string());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        if summary.answered_count > 0 {
+            html.push_str(&get_duration_string(&summary.total_latency));
+        }
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        if let Some(average_latency) = summary.average_latency() {
+            html.push_str(&get_duration_string(&average_latency));
+        }
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
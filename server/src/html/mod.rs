@@ -1,227 +1,225 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::LazyLock,
+};
 
 use axum::{
-    extract::{Query, State},
-    http::{HeaderMap, HeaderValue, Response, StatusCode},
+    extract::{Query, RawQuery, State},
+    http::{
+        HeaderMap, HeaderValue, StatusCode,
+        header::{CACHE_CONTROL, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    },
     response::Html,
 };
+use lsp_server::{Message, RequestId};
 use serde::Deserialize;
+use time::OffsetDateTime;
 
 use crate::{
     AppState,
-    html::chat_view::append_chat_html_to,
-    message::{Conversation, MessageKind, classify},
+    error::AppError,
+    html::{
+        chat_view::{
+            ChatOrder, ChatPage, DEFAULT_MAX_ARRAY_ITEMS, DEFAULT_MAX_STRING_CHARS,
+            DEFAULT_PAGE_SIZE, FieldCondition, JsonRenderOptions, MAX_PAGE_SIZE, MessageFilter,
+            TimeRange, append_chat_html_to, append_message_url_to,
+        },
+        stylesheet::Stylesheet,
+        theme::Theme,
+    },
+    message::{Conversation, MessageKind, UNCATEGORIZED_KEY, classify},
+    session::MessageSource,
+    utils::{
+        deserialize_id, get_datetime_local_string, get_duration_string, get_http_date_string,
+        get_iso_string, parse_http_date, parse_time_stamp,
+    },
 };
 
 mod chat_view;
+mod code_actions;
+mod descriptors;
+pub(crate) mod diff;
+pub(crate) mod error_report;
+pub(crate) mod latency_report;
+pub(crate) mod logs;
+pub(crate) mod method_summary;
 pub(crate) mod session_search;
+pub(crate) mod star;
+pub(crate) mod stats;
+pub(crate) mod stylesheet;
+pub(crate) mod theme;
+pub(crate) mod timeline;
+
+const DEFAULT_SLOWEST_COUNT: usize = 5;
 
 #[derive(Deserialize)]
 pub(crate) struct GetSessionParams {
+    #[serde(deserialize_with = "deserialize_id")]
     session_id: i64,
-    life_cycle: Option<bool>,
-    document_synchronization: Option<bool>,
-    notebook_synchronization: Option<bool>,
-    workspace_synchronization: Option<bool>,
-    workspace: Option<bool>,
-    telemetry: Option<bool>,
-    declaration: Option<bool>,
-    definition: Option<bool>,
-    type_definition: Option<bool>,
-    implementation: Option<bool>,
-    references: Option<bool>,
-    call_hierarchy: Option<bool>,
-    type_hierarchy: Option<bool>,
-    document_highlight: Option<bool>,
-    document_link: Option<bool>,
-    hover: Option<bool>,
-    code_lens: Option<bool>,
-    folding_range: Option<bool>,
-    selection: Option<bool>,
-    symbol: Option<bool>,
-    semantic_tokens: Option<bool>,
-    inlay_hint: Option<bool>,
-    inline_value: Option<bool>,
-    moniker: Option<bool>,
-    completion: Option<bool>,
-    diagnostic: Option<bool>,
-    signature_help: Option<bool>,
-    code_action: Option<bool>,
-    document_color: Option<bool>,
-    formatting: Option<bool>,
-    rename: Option<bool>,
-    linked_editing_range: Option<bool>,
-    execute_command: Option<bool>,
-    uncategorized: Option<bool>,
+    theme: Option<Theme>,
+    sort_keys: Option<bool>,
+    /// Defaults to oldest first.
+    order: Option<ChatOrder>,
+    /// Show each answered request together with its response.
+    group_pairs: Option<bool>,
+    /// Show consecutive repeats of a request or notification as one message.
+    collapse_repeats: Option<bool>,
+    /// Only show messages whose JSON or method name contains this, ignoring case.
+    search: Option<String>,
+    /// Only show messages whose JSON has this value at a path, written as `dotted.path=value`.
+    r#where: Option<String>,
+    /// Only show the lifecycle of the request with this id, written as JSON. String ids may be
+    /// given without their quotes.
+    request_id: Option<String>,
+    /// Only show messages logged at or after this time. Either RFC 3339 or the value of a
+    /// `datetime-local` input in UTC.
+    from: Option<String>,
+    /// Only show messages logged at or before this time, in the same formats as `from`.
+    to: Option<String>,
+    /// How many of the slowest requests to list. Defaults to `DEFAULT_SLOWEST_COUNT`.
+    slowest_count: Option<usize>,
+    /// Cut method names in tables longer than this many characters short. Zero doesn't limit them.
+    method_width: Option<usize>,
+    /// How many elements of each array to show before collapsing the rest. Defaults to
+    /// `DEFAULT_MAX_ARRAY_ITEMS`.
+    max_array_items: Option<usize>,
+    /// How many characters of each string to show before collapsing the rest. Defaults to
+    /// `DEFAULT_MAX_STRING_CHARS`.
+    max_string_chars: Option<usize>,
+    /// Which page of the shown messages to render, counting from zero.
+    page: Option<usize>,
+    /// Render the page that holds the message with this seq instead, so that links to a
+    /// message work whichever page it's on. Ignored when the message isn't shown.
+    seq: Option<i64>,
+    /// How many messages to render on each page. Defaults to `DEFAULT_PAGE_SIZE`, and is
+    /// clamped to `MAX_PAGE_SIZE`.
+    page_size: Option<usize>,
+    /// Show messages sent by the client.
+    client: Option<bool>,
+    /// Show messages sent by the server.
+    server: Option<bool>,
+    /// Show messages whose sender couldn't be determined.
+    unknown_source: Option<bool>,
 }
 
 impl GetSessionParams {
-    fn build_message_classification_allow_list(&self) -> HashSet<Option<MessageKind>> {
-        let request = self;
-
-        let show_all = request.life_cycle.is_none()
-            && request.document_synchronization.is_none()
-            && request.notebook_synchronization.is_none()
-            && request.workspace_synchronization.is_none()
-            && request.workspace.is_none()
-            && request.telemetry.is_none()
-            && request.declaration.is_none()
-            && request.definition.is_none()
-            && request.type_definition.is_none()
-            && request.implementation.is_none()
-            && request.references.is_none()
-            && request.call_hierarchy.is_none()
-            && request.type_hierarchy.is_none()
-            && request.document_highlight.is_none()
-            && request.document_link.is_none()
-            && request.hover.is_none()
-            && request.code_lens.is_none()
-            && request.folding_range.is_none()
-            && request.selection.is_none()
-            && request.symbol.is_none()
-            && request.semantic_tokens.is_none()
-            && request.inlay_hint.is_none()
-            && request.inline_value.is_none()
-            && request.moniker.is_none()
-            && request.completion.is_none()
-            && request.diagnostic.is_none()
-            && request.signature_help.is_none()
-            && request.code_action.is_none()
-            && request.document_color.is_none()
-            && request.formatting.is_none()
-            && request.rename.is_none()
-            && request.linked_editing_range.is_none()
-            && request.execute_command.is_none()
-            && request.uncategorized.is_none();
-
-        let mut msg_types_to_include = HashSet::new();
-        if show_all || matches!(request.life_cycle, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Lifecycle));
-        }
-        if show_all || matches!(request.document_synchronization, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::TextDocumentSynchronization));
-        }
-        if show_all || matches!(request.notebook_synchronization, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::NotebookDocumentSynchronization));
-        }
-        if show_all || matches!(request.workspace_synchronization, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::WorkspaceSynchronization));
-        }
-        if show_all || matches!(request.workspace, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Workspace));
-        }
-        if show_all || matches!(request.telemetry, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Telemetry));
-        }
-        if show_all || matches!(request.declaration, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Declaration));
-        }
-        if show_all || matches!(request.definition, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Definition));
-        }
-        if show_all || matches!(request.type_definition, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::TypeDefinition));
-        }
-        if show_all || matches!(request.implementation, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Implementation));
-        }
-        if show_all || matches!(request.references, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::References));
-        }
-        if show_all || matches!(request.call_hierarchy, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::CallHierarchy));
-        }
-        if show_all || matches!(request.type_hierarchy, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::TypeHierarchy));
-        }
-        if show_all || matches!(request.document_highlight, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::DocumentHighlight));
-        }
-        if show_all || matches!(request.document_link, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::DocumentLink));
-        }
-        if show_all || matches!(request.hover, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Hover));
-        }
-        if show_all || matches!(request.code_lens, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::CodeLens));
-        }
-        if show_all || matches!(request.folding_range, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::FoldingRange));
-        }
-        if show_all || matches!(request.selection, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Selection));
-        }
-        if show_all || matches!(request.symbol, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Symbol));
-        }
-        if show_all || matches!(request.semantic_tokens, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::SemanticTokens));
-        }
-        if show_all || matches!(request.inlay_hint, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::InlayHint));
-        }
-        if show_all || matches!(request.inline_value, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::InlineValue));
-        }
-        if show_all || matches!(request.moniker, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Moniker));
-        }
-        if show_all || matches!(request.completion, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Completion));
-        }
-        if show_all || matches!(request.diagnostic, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Diagnostic));
-        }
-        if show_all || matches!(request.signature_help, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::SignatureHelp));
-        }
-        if show_all || matches!(request.code_action, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::CodeAction));
-        }
-        if show_all || matches!(request.document_color, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::DocumentColor));
-        }
-        if show_all || matches!(request.formatting, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Formatting));
-        }
-        if show_all || matches!(request.rename, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::Rename));
-        }
-        if show_all || matches!(request.linked_editing_range, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::LinkedEditingRange));
+    /// Like the categories, every source is shown until at least one of them is chosen.
+    fn build_source_allow_list(&self) -> HashSet<Option<MessageSource>> {
+        let show_all =
+            self.client.is_none() && self.server.is_none() && self.unknown_source.is_none();
+
+        let mut sources_to_include = HashSet::new();
+        if show_all || matches!(self.client, Some(true)) {
+            sources_to_include.insert(Some(MessageSource::Client));
         }
-        if show_all || matches!(request.execute_command, Some(true)) {
-            msg_types_to_include.insert(Some(MessageKind::ExecuteCommand));
+        if show_all || matches!(self.server, Some(true)) {
+            sources_to_include.insert(Some(MessageSource::Server));
         }
-        if show_all || matches!(request.uncategorized, Some(true)) {
-            msg_types_to_include.insert(None);
+        if show_all || matches!(self.unknown_source, Some(true)) {
+            sources_to_include.insert(None);
         }
-        msg_types_to_include
+        sources_to_include
     }
 }
 
 pub(crate) async fn get_session(
     State(state): State<AppState>,
     Query(request): Query<GetSessionParams>,
-) -> Result<(StatusCode, HeaderMap, Html<String>), StatusCode> {
+    RawQuery(raw_query): RawQuery,
+    request_headers: HeaderMap,
+) -> Result<(StatusCode, HeaderMap, Html<String>), AppError> {
+    // The latest seq of the session's messages changes whenever one is logged, even after the
+    // session ended, since messages can still be posted to `/log`.
     let session = sqlx::query!(
-        "SELECT id, end_time_stamp FROM sessions WHERE id = $1 LIMIT 1;",
+        r#"SELECT id, end_time_stamp, timed_out, compacted, sample_rate, sampled_methods, starred,
+            (SELECT MAX(seq) FROM (
+                SELECT seq FROM requests WHERE session_id = sessions.id
+                UNION ALL SELECT seq FROM responses WHERE session_id = sessions.id
+                UNION ALL SELECT seq FROM notifications WHERE session_id = sessions.id
+                UNION ALL SELECT seq FROM malformed_messages WHERE session_id = sessions.id
+            ) seqs) AS "last_seq?"
+        FROM sessions WHERE id = $1 LIMIT 1;"#,
         request.session_id
     )
     .fetch_one(&state.db)
     .await
-    .map_err(|err| match err {
-        sqlx::Error::RowNotFound => StatusCode::NOT_FOUND,
-        _ => StatusCode::INTERNAL_SERVER_ERROR,
-    })?;
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let headers = match session.end_time_stamp {
+        Some(end_time_stamp) => {
+            let validators = SessionValidators {
+                etag: format!(
+                    "W/\"{}-{}-{}-{}-{}\"",
+                    session.last_seq.unwrap_or(0),
+                    end_time_stamp.unix_timestamp_nanos(),
+                    u8::from(session.starred),
+                    u8::from(session.compacted),
+                    *SERVER_RUN_ID
+                ),
+                last_modified: end_time_stamp,
+            };
+            if validators.match_request(&request_headers) {
+                return Ok((
+                    StatusCode::NOT_MODIFIED,
+                    validators.into_headers(),
+                    Html(String::new()),
+                ));
+            }
+            validators.into_headers()
+        }
+        // A live session changes with every message, so it's never worth keeping.
+        None => HeaderMap::from_iter([(CACHE_CONTROL, HeaderValue::from_static("no-store"))]),
+    };
+
+    let time_range = TimeRange {
+        from: parse_time_param(request.from.as_deref())?,
+        to: parse_time_param(request.to.as_deref())?,
+    };
 
     let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
         &state.db,
         request.session_id,
     )
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let malformed_messages =
+        crate::session::get_malformed_messages_for_session(&state.db, request.session_id)
+            .await
+            .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let external_requests = match state.config.cross_session_lookup_sessions {
+        Some(session_count) => {
+            let unresolved_ids = conversation
+                .messages()
+                .iter()
+                .filter_map(|message| match &message.message {
+                    Message::Response(response)
+                        if !conversation.requests().contains_key(&response.id) =>
+                    {
+                        Some(&response.id)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+
+            if unresolved_ids.is_empty() {
+                HashMap::new()
+            } else {
+                crate::session::find_requests_in_preceding_sessions(
+                    &state.db,
+                    request.session_id,
+                    session_count,
+                    &unresolved_ids,
+                )
+                .await
+                .map_err(|_| {
+                    AppError::internal("failed to look up requests in the preceding sessions")
+                })?
+            }
+        }
+        None => HashMap::new(),
+    };
 
     let mut html = String::new();
     html.push_str("<!DOCTYPE=html>");
@@ -233,41 +231,252 @@ pub(crate) async fn get_session(
     html.push_str("</head>");
 
     html.push_str("<body>");
+    Stylesheet::Chat.append_to(&mut html, &state.config);
     html.push_str("<style>");
-    html.push_str(include_str!("../css/chat.css"));
+    request
+        .theme
+        .unwrap_or(state.config.default_theme)
+        .append_css_to(&mut html);
     html.push_str("</style>");
 
-    html.push_str(&generate_filtering_form(&request, &conversation));
-    let allow_list = request.build_message_classification_allow_list();
-    append_chat_html_to(&mut html, &conversation, &allow_list);
+    html.push_str("<a href=\"/session/stats?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Session Stats</a>");
+    html.push_str(" <a href=\"/session/timeline?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Timeline</a>");
+    html.push_str(" <a href=\"/errors?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Errors</a>");
+    html.push_str(" <a href=\"/session/summary?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Method Summary</a>");
+    html.push_str(" <a href=\"/session/logs?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Server Logs</a>");
+    html.push_str(" <a href=\"/api/session/");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("/test_case\">Export as test case</a>");
+    html.push_str(" <a href=\"/session/export?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Export trace</a>");
+    html.push_str(" <a href=\"/session/diff?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Diff messages</a>");
+    star::append_star_form_to(
+        &mut html,
+        request.session_id,
+        session.starred,
+        raw_query.as_deref(),
+    );
+
+    if session.timed_out {
+        html.push_str("<p class=\"timed_out\">Timed out: the connection stopped responding without being closed, so the session was ended at its last frame.</p>");
+    }
+
+    if session.compacted {
+        html.push_str("<p class=\"payloads_purged\">Payloads purged: this session was compacted, so only message metadata remains.</p>");
+    }
+
+    if let (Some(sample_rate), Some(sampled_methods)) =
+        (session.sample_rate, &session.sampled_methods)
+    {
+        html.push_str("<p class=\"sampled\">Sampled: ");
+        html.push_str(&html_escape::encode_text(&sampled_methods.join(", ")));
+        html.push_str(" notifications were stored at 1/");
+        html.push_str(&sample_rate.to_string());
+        html.push_str("</p>");
+    }
+
+    append_slowest_requests_to(
+        &mut html,
+        request.session_id,
+        &conversation,
+        request.slowest_count.unwrap_or(DEFAULT_SLOWEST_COUNT),
+        request.method_width,
+    );
+
+    let kinds = build_message_classification_allow_list(raw_query.as_deref());
+    html.push_str(&generate_filtering_form(
+        &request,
+        raw_query.as_deref(),
+        &kinds,
+        &conversation,
+        time_range,
+        state.config.default_theme,
+    ));
+    let page = ChatPage {
+        index: request.page.unwrap_or(0),
+        size: request
+            .page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE),
+        seq: request.seq,
+    };
+    let condition = match request.r#where.as_deref().map(str::trim) {
+        Some(expression) if !expression.is_empty() => {
+            Some(FieldCondition::parse(expression).ok_or_else(|| {
+                AppError::bad_request(format!("{expression} is not a path=value condition"))
+            })?)
+        }
+        _ => None,
+    };
+    let seqs = request
+        .request_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|request_id| !request_id.is_empty())
+        .map(|request_id| conversation.get_request_lifecycle(&parse_request_id(request_id)));
+    let filter = MessageFilter {
+        kinds,
+        sources: request.build_source_allow_list(),
+        time_range,
+        condition,
+        seqs,
+        page,
+    };
+    let json_render_options = JsonRenderOptions {
+        sort_keys: request.sort_keys.unwrap_or(false),
+        // Purged params would all be reported as missing their required fields.
+        compare_with_spec: !session.compacted,
+        search: request
+            .search
+            .as_deref()
+            .map(str::trim)
+            .filter(|search| !search.is_empty())
+            .map(str::to_lowercase),
+        max_array_items: request.max_array_items.unwrap_or(DEFAULT_MAX_ARRAY_ITEMS),
+        max_string_chars: request.max_string_chars.unwrap_or(DEFAULT_MAX_STRING_CHARS),
+        group_pairs: request.group_pairs.unwrap_or(false),
+        collapse_repeats: request.collapse_repeats.unwrap_or(false),
+        session_id: Some(request.session_id),
+    };
+    let mut chat_html = String::new();
+    let (shown_count, page) = append_chat_html_to(
+        &mut chat_html,
+        &conversation,
+        &malformed_messages,
+        &filter,
+        &external_requests,
+        request.order.unwrap_or_default(),
+        &json_render_options,
+    );
+    append_chat_pagination_to(&mut html, raw_query.as_deref(), page, shown_count);
+    html.push_str(&chat_html);
+    append_chat_pagination_to(&mut html, raw_query.as_deref(), page, shown_count);
 
     html.push_str("</body>");
     html.push_str("</html>");
 
-    // if the session has been ended, it won't change. Set long-lived caching headers
-    let mut headers = HeaderMap::new();
-    if session.end_time_stamp.is_some() {
-        headers.insert("immutable", HeaderValue::from_str("true").unwrap());
-    } else {
-        headers.insert("no-store", HeaderValue::from_str("true").unwrap());
+    Ok((StatusCode::OK, headers, Html(html)))
+}
+
+/// Identifies this run of the server, so that pages rendered by an earlier run, which may have
+/// been another version or had another config, aren't revalidated.
+static SERVER_RUN_ID: LazyLock<String> =
+    LazyLock::new(|| format!("{:x}", OffsetDateTime::now_utc().unix_timestamp_nanos()));
+
+/// What the page of an ended session is revalidated with. An ended session can still be
+/// starred, compacted or have messages posted to it, so its page is never taken to be
+/// immutable. Browsers keep it but ask whether it changed before reusing it, which is answered
+/// with a `304` without reading its messages.
+struct SessionValidators {
+    /// Changes with the session's latest message, its end, its star and its compaction.
+    etag: String,
+    /// When the session ended. It misses the changes made since, so the `ETag` is preferred.
+    last_modified: OffsetDateTime,
+}
+
+impl SessionValidators {
+    /// Whether the request's conditional headers match, so the page it has is still current.
+    /// `If-Modified-Since` is only used when there's no `If-None-Match`, as RFC 9110 requires.
+    fn match_request(&self, request_headers: &HeaderMap) -> bool {
+        let header_str = |name| {
+            request_headers
+                .get(name)
+                .and_then(|value: &HeaderValue| value.to_str().ok())
+        };
+        if let Some(if_none_match) = header_str(IF_NONE_MATCH) {
+            // Weak comparison, which ignores whether either tag is weak.
+            let etag = self.etag.trim_start_matches("W/");
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+        }
+        header_str(IF_MODIFIED_SINCE)
+            .and_then(parse_http_date)
+            .is_some_and(|since| self.last_modified.unix_timestamp() <= since.unix_timestamp())
     }
 
-    Ok((StatusCode::OK, headers, Html(html)))
+    fn into_headers(self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Ok(last_modified) = HeaderValue::from_str(&get_http_date_string(&self.last_modified))
+        {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        headers
+    }
+}
+
+/// Reads a request id written as JSON, so that `5` is a numeric id and `"5"` a string one.
+/// Anything else, like `abc`, is taken as the string id it spells out.
+fn parse_request_id(request_id: &str) -> RequestId {
+    serde_json::from_str(request_id).unwrap_or_else(|_| RequestId::from(request_id.to_string()))
+}
+
+/// Reads which categories to show from the query string, where each is chosen by its
+/// `MessageKind::key`, like `life_cycle=true`. They're read from the raw query, rather than
+/// `GetSessionParams`, so that they always match the kinds there are. Every category is shown
+/// until at least one of them is given.
+fn build_message_classification_allow_list(
+    raw_query: Option<&str>,
+) -> HashSet<Option<MessageKind>> {
+    let mut is_any_given = false;
+    let mut msg_types_to_include = HashSet::new();
+    for (key, value) in form_urlencoded::parse(raw_query.unwrap_or_default().as_bytes()) {
+        let kind = match MessageKind::try_parse_str(&key) {
+            Some(kind) => Some(kind),
+            None if key == UNCATEGORIZED_KEY => None,
+            None => continue,
+        };
+        is_any_given = true;
+        if value == "true" {
+            msg_types_to_include.insert(kind);
+        }
+    }
+
+    if !is_any_given {
+        msg_types_to_include.extend(MessageKind::all().map(Some));
+        msg_types_to_include.insert(None);
+    }
+    msg_types_to_include
 }
 
-fn generate_filtering_form(request: &GetSessionParams, conversation: &Conversation) -> String {
+fn generate_filtering_form(
+    request: &GetSessionParams,
+    raw_query: Option<&str>,
+    allow_list: &HashSet<Option<MessageKind>>,
+    conversation: &Conversation,
+    time_range: TimeRange,
+    default_theme: Theme,
+) -> String {
     let message_types_in_conversation = conversation
         .messages()
-        .into_iter()
+        .iter()
+        .filter(|message_with_time_stamp| time_range.contains(message_with_time_stamp.time_stamp))
         .map(|message_with_time_stamp| classify(&message_with_time_stamp.message, conversation))
         .collect::<HashSet<_>>();
 
-    let allow_list = request.build_message_classification_allow_list();
-
     let mut html = String::new();
 
     html.push_str("<form action=\"/session\" method=\"GET\" style=\"display: flex;flex-direction: column;align-items: center; background-color: gray; border-radius: 40px; padding: 20px; row-gap: 5px;\">");
     html.push_str("<h2>Filter Your Results</h2>");
+    append_lifecycle_preset_link_to(&mut html, raw_query);
     html.push_str("<fieldset style=\"display: grid; grid-template-columns: auto auto; row-gap: 5px; column-gap: 5px; place-content: space-evenly; width: 100%;\">");
     html.push_str("<legend>Filter Messages by Category:</legend>");
     html.push_str("<input type=\"text\" id=\"session_id\" name=\"session_id\" style=\"display: none;\" value=\"");
@@ -275,29 +484,29 @@ fn generate_filtering_form(request: &GetSessionParams, conversation: &Conversati
     html.push_str("\">");
 
     for msg_kind in MessageKind::all() {
-        if !message_types_in_conversation.contains(&Some(*msg_kind)) {
+        if !message_types_in_conversation.contains(&Some(msg_kind)) {
             continue;
         }
 
         html.push_str("<span>");
         let msg_name = msg_kind.as_str();
-        let msg_id = msg_name.replace(" ", "_");
+        let msg_id = msg_kind.key();
         html.push_str("<input type=\"checkbox\" id=\"");
-        html.push_str(&msg_id);
+        html.push_str(msg_id);
         html.push_str("\" name=\"");
-        html.push_str(&msg_id);
+        html.push_str(msg_id);
         html.push_str("\" value=\"");
         html.push_str("true");
-        html.push_str("\"");
+        html.push('"');
 
-        if allow_list.contains(&Some(*msg_kind)) {
+        if allow_list.contains(&Some(msg_kind)) {
             html.push_str(" checked");
         }
 
         html.push('>');
 
         html.push_str("<label for=\"");
-        html.push_str(&msg_id);
+        html.push_str(msg_id);
         html.push_str("\">");
         html.push_str(msg_name);
         html.push_str("</label><br/>");
@@ -306,22 +515,493 @@ fn generate_filtering_form(request: &GetSessionParams, conversation: &Conversati
 
     if message_types_in_conversation.contains(&None) {
         html.push_str("<span>");
-        html.push_str("<input type=\"checkbox\" id=\"uncategorized\" name=\"uncategorized\"");
+        html.push_str("<input type=\"checkbox\" id=\"");
+        html.push_str(UNCATEGORIZED_KEY);
+        html.push_str("\" name=\"");
+        html.push_str(UNCATEGORIZED_KEY);
+        html.push_str("\" value=\"true\"");
 
         if allow_list.contains(&None) {
             html.push_str(" checked");
         }
         html.push('>');
 
-        html.push_str("<label for=\"uncategorized\">uncategorized</label><br/>");
+        html.push_str("<label for=\"");
+        html.push_str(UNCATEGORIZED_KEY);
+        html.push_str("\">uncategorized</label><br/>");
         html.push_str("</span>");
     }
 
     html.push_str("</fieldset>");
 
+    let sources_in_conversation = conversation
+        .messages()
+        .iter()
+        .filter(|message_with_time_stamp| time_range.contains(message_with_time_stamp.time_stamp))
+        .map(|message_with_time_stamp| message_with_time_stamp.get_source(conversation))
+        .collect::<HashSet<_>>();
+    let source_allow_list = request.build_source_allow_list();
+
+    html.push_str("<fieldset style=\"display: flex; column-gap: 15px; place-content: space-evenly; width: 100%;\">");
+    html.push_str("<legend>Filter Messages by Source:</legend>");
+    for (id, label, source) in [
+        ("client", "client", Some(MessageSource::Client)),
+        ("server", "server", Some(MessageSource::Server)),
+        ("unknown_source", "unknown", None),
+    ] {
+        if !sources_in_conversation.contains(&source) {
+            continue;
+        }
+
+        html.push_str("<span>");
+        html.push_str("<input type=\"checkbox\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"true\"");
+        if source_allow_list.contains(&source) {
+            html.push_str(" checked");
+        }
+        html.push('>');
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("</span>");
+    }
+    html.push_str("</fieldset>");
+
+    for (id, label, time_stamp) in [
+        ("from", "From (UTC): ", time_range.from),
+        ("to", "To (UTC): ", time_range.to),
+    ] {
+        html.push_str("<span>");
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("<input type=\"datetime-local\" step=\"0.001\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"");
+        if let Some(time_stamp) = time_stamp {
+            html.push_str(&get_datetime_local_string(&time_stamp));
+        }
+        html.push_str("\">");
+        html.push_str("</span>");
+    }
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"search\">Search messages: </label>");
+    html.push_str("<input type=\"search\" id=\"search\" name=\"search\" value=\"");
+    if let Some(search) = &request.search {
+        html.push_str(&html_escape::encode_double_quoted_attribute(search));
+    }
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"where\">Where (path=value): </label>");
+    html.push_str("<input type=\"text\" id=\"where\" name=\"where\" placeholder=\"params.context.triggerKind=2\" value=\"");
+    if let Some(condition) = &request.r#where {
+        html.push_str(&html_escape::encode_double_quoted_attribute(condition));
+    }
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"request_id\">Lifecycle of request id: </label>");
+    html.push_str("<input type=\"text\" id=\"request_id\" name=\"request_id\" value=\"");
+    if let Some(request_id) = &request.request_id {
+        html.push_str(&html_escape::encode_double_quoted_attribute(request_id));
+    }
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<input type=\"checkbox\" id=\"sort_keys\" name=\"sort_keys\" value=\"true\"");
+    if request.sort_keys.unwrap_or(false) {
+        html.push_str(" checked");
+    }
+    html.push('>');
+    html.push_str("<label for=\"sort_keys\">Sort object keys alphabetically</label>");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str(
+        "<input type=\"checkbox\" id=\"group_pairs\" name=\"group_pairs\" value=\"true\"",
+    );
+    if request.group_pairs.unwrap_or(false) {
+        html.push_str(" checked");
+    }
+    html.push('>');
+    html.push_str("<label for=\"group_pairs\">Group requests with their responses</label>");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str(
+        "<input type=\"checkbox\" id=\"collapse_repeats\" name=\"collapse_repeats\" value=\"true\"",
+    );
+    if request.collapse_repeats.unwrap_or(false) {
+        html.push_str(" checked");
+    }
+    html.push('>');
+    html.push_str("<label for=\"collapse_repeats\">Collapse repeated messages</label>");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"slowest_count\">Slowest requests shown: </label>");
+    html.push_str(
+        "<input type=\"number\" id=\"slowest_count\" name=\"slowest_count\" min=\"0\" value=\"",
+    );
+    html.push_str(
+        &request
+            .slowest_count
+            .unwrap_or(DEFAULT_SLOWEST_COUNT)
+            .to_string(),
+    );
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"method_width\">Longest method name shown (0 for any): </label>");
+    html.push_str(
+        "<input type=\"number\" id=\"method_width\" name=\"method_width\" min=\"0\" value=\"",
+    );
+    html.push_str(&request.method_width.unwrap_or(0).to_string());
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    for (id, label, value) in [
+        (
+            "max_array_items",
+            "Array items shown: ",
+            request.max_array_items.unwrap_or(DEFAULT_MAX_ARRAY_ITEMS),
+        ),
+        (
+            "max_string_chars",
+            "String characters shown: ",
+            request.max_string_chars.unwrap_or(DEFAULT_MAX_STRING_CHARS),
+        ),
+    ] {
+        html.push_str("<span>");
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("<input type=\"number\" min=\"0\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"");
+        html.push_str(&value.to_string());
+        html.push_str("\">");
+        html.push_str("</span>");
+    }
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"page_size\">Messages per page: </label>");
+    html.push_str("<input type=\"number\" id=\"page_size\" name=\"page_size\" min=\"1\" max=\"");
+    html.push_str(&MAX_PAGE_SIZE.to_string());
+    html.push_str("\" value=\"");
+    html.push_str(&request.page_size.unwrap_or(DEFAULT_PAGE_SIZE).to_string());
+    html.push_str("\">");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"order\">Order: </label>");
+    html.push_str("<select id=\"order\" name=\"order\">");
+    let selected_order = request.order.unwrap_or_default();
+    for order in ChatOrder::all() {
+        html.push_str("<option value=\"");
+        html.push_str(order.as_str());
+        html.push('"');
+        if *order == selected_order {
+            html.push_str(" selected");
+        }
+        html.push('>');
+        html.push_str(order.label());
+        html.push_str("</option>");
+    }
+    html.push_str("</select>");
+    html.push_str("</span>");
+
+    html.push_str("<span>");
+    html.push_str("<label for=\"theme\">Color theme: </label>");
+    html.push_str("<select id=\"theme\" name=\"theme\">");
+    let selected_theme = request.theme.unwrap_or(default_theme);
+    for theme in Theme::all() {
+        html.push_str("<option value=\"");
+        html.push_str(theme.as_str());
+        html.push('"');
+        if *theme == selected_theme {
+            html.push_str(" selected");
+        }
+        html.push('>');
+        html.push_str(&theme.as_str().replace("_", " "));
+        html.push_str("</option>");
+    }
+    html.push_str("</select>");
+    html.push_str("</span>");
+
     html.push_str("<button type=\"Submit\">Update Results</button>");
 
     html.push_str("</form>");
 
     html
 }
+
+/// Parses an optional time stamp from the query string. Empty values, as submitted by a cleared
+/// input, are treated as missing.
+pub(crate) fn parse_time_param(value: Option<&str>) -> Result<Option<OffsetDateTime>, AppError> {
+    match value.map(str::trim).filter(|value| !value.is_empty()) {
+        Some(value) => parse_time_stamp(value)
+            .map(Some)
+            .ok_or_else(|| AppError::bad_request(format!("{value} is not a time stamp"))),
+        None => Ok(None),
+    }
+}
+
+/// Writes a method name for a table cell. Names longer than `max_chars` are cut short with an
+/// ellipsis, and keep their full name in a tooltip. A `max_chars` of zero doesn't limit them.
+pub(crate) fn append_method_cell_to(html: &mut String, method: &str, max_chars: Option<usize>) {
+    match max_chars {
+        Some(max_chars) if max_chars > 0 && method.chars().count() > max_chars => {
+            html.push_str("<span class=\"truncated_method\" title=\"");
+            html.push_str(&html_escape::encode_double_quoted_attribute(method));
+            html.push_str("\">");
+            // The ellipsis takes up the last character.
+            let kept = method.chars().take(max_chars - 1).collect::<String>();
+            html.push_str(&html_escape::encode_text(&kept));
+            html.push('…');
+            html.push_str("</span>");
+        }
+        _ => html.push_str(&html_escape::encode_text(method)),
+    }
+}
+
+/// The query string of the page with the parameters that `is_removed` picks left out, as it was
+/// written, so that links can change some options and keep the rest.
+fn get_query_without(raw_query: Option<&str>, is_removed: impl Fn(&str) -> bool) -> String {
+    raw_query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && !is_removed(name)
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Writes links to the neighbouring pages of the chat, when its messages don't fit on one. The
+/// links keep the rest of the query, so paging doesn't clear the filters.
+fn append_chat_pagination_to(
+    html: &mut String,
+    raw_query: Option<&str>,
+    page: ChatPage,
+    shown_count: usize,
+) {
+    let page_count = shown_count.div_ceil(page.size);
+    if page_count <= 1 && page.index == 0 {
+        return;
+    }
+
+    let query = get_query_without(raw_query, |name| name == "page" || name == "seq");
+    let append_page_link_to = |html: &mut String, index: usize, label: &str| {
+        html.push_str("<a href=\"/session?");
+        html.push_str(&html_escape::encode_double_quoted_attribute(&query));
+        html.push_str("&page=");
+        html.push_str(&index.to_string());
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</a>");
+    };
+
+    html.push_str("<div class=\"pagination\">");
+    if page.index == 0 {
+        html.push_str("<span class=\"disabled\">Previous</span>");
+    } else {
+        // A page past the end links back to the last page rather than to the one before it.
+        append_page_link_to(
+            html,
+            (page.index - 1).min(page_count.saturating_sub(1)),
+            "Previous",
+        );
+    }
+    html.push_str("<span>Page ");
+    html.push_str(&(page.index + 1).to_string());
+    html.push_str(" of ");
+    html.push_str(&page_count.max(1).to_string());
+    html.push_str("</span>");
+    if page.index + 1 < page_count {
+        append_page_link_to(html, page.index + 1, "Next");
+    } else {
+        html.push_str("<span class=\"disabled\">Next</span>");
+    }
+    html.push_str("</div>");
+}
+
+/// A one-click preset that narrows the chat down to the initialize/shutdown handshake by
+/// choosing only the lifecycle category. Every other option of the page, like its source and
+/// text filters, is kept, and it starts again from the first page.
+fn append_lifecycle_preset_link_to(html: &mut String, raw_query: Option<&str>) {
+    let query = get_query_without(raw_query, |name| {
+        name == "page"
+            || name == "seq"
+            || name == UNCATEGORIZED_KEY
+            || MessageKind::try_parse_str(name).is_some()
+    });
+    html.push_str("<a href=\"/session?");
+    html.push_str(&html_escape::encode_double_quoted_attribute(&query));
+    html.push('&');
+    html.push_str(MessageKind::Lifecycle.key());
+    html.push_str("=true");
+    html.push_str("\" style=\"padding: 5px 15px; border-radius: 20px; background-color: #223546; color: white; text-decoration: none;\">Lifecycle only</a>");
+}
+
+fn append_slowest_requests_to(
+    html: &mut String,
+    session_id: i64,
+    conversation: &Conversation,
+    count: usize,
+    method_width: Option<usize>,
+) {
+    let slowest = conversation.get_slowest_requests(count);
+    if slowest.is_empty() {
+        return;
+    }
+
+    html.push_str("<details class=\"slowest_requests\" open>");
+    html.push_str("<summary>Slowest Requests</summary>");
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Method</th>");
+    html.push_str("<th>Latency</th>");
+    html.push_str("<th>Sent At</th>");
+    html.push_str("</tr>");
+    for pair in slowest {
+        let (Message::Request(request), Some(latency)) = (&pair.request.message, pair.latency())
+        else {
+            continue;
+        };
+
+        html.push_str("<tr>");
+
+        html.push_str("<td><a href=\"");
+        append_message_url_to(html, session_id, pair.request.seq);
+        html.push_str("\">");
+        append_method_cell_to(html, &request.method, method_width);
+        html.push_str("</a></td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_duration_string(&latency));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_iso_string(&pair.request.time_stamp));
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+    html.push_str("</details>");
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::PgPool;
+
+    use super::*;
+
+    async fn render_session(state: AppState, query: &str) -> String {
+        let uri = format!("/session?{query}").parse().unwrap();
+        let (_, _, Html(html)) = get_session(
+            State(state),
+            Query::try_from_uri(&uri).unwrap(),
+            RawQuery(Some(query.to_string())),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        html
+    }
+
+    #[test]
+    fn the_lifecycle_preset_applies_the_lifecycle_only_filter() {
+        let mut html = String::new();
+        append_lifecycle_preset_link_to(
+            &mut html,
+            Some("session_id=3&client=true&definition=true&uncategorized=false&page=2&seq=40"),
+        );
+
+        let href = html
+            .split_once("href=\"/session?")
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(href, _)| href.replace("&amp;", "&"))
+            .unwrap();
+        assert_eq!(href, "session_id=3&client=true&life_cycle=true");
+        assert_eq!(
+            build_message_classification_allow_list(Some(&href)),
+            HashSet::from([Some(MessageKind::Lifecycle)])
+        );
+    }
+
+    #[test]
+    fn a_long_method_name_is_truncated_with_the_full_name_in_its_title() {
+        let method = "$/rust-analyzer/experimental/serverStatus";
+
+        let mut truncated = String::new();
+        append_method_cell_to(&mut truncated, method, Some(12));
+        let mut unlimited = String::new();
+        append_method_cell_to(&mut unlimited, method, Some(0));
+
+        assert_eq!(
+            truncated,
+            "<span class=\"truncated_method\" title=\"$/rust-analyzer/experimental/serverStatus\">$/rust-anal…</span>"
+        );
+        assert_eq!(unlimited, method);
+    }
+
+    #[sqlx::test]
+    async fn a_response_links_to_its_request_in_a_preceding_session(db: PgPool) {
+        let state = AppState::for_tests(db, |config| {
+            config.cross_session_lookup_sessions = Some(3);
+        });
+        let insert_session = async || {
+            sqlx::query_scalar!(
+                "INSERT INTO sessions (start_time_stamp) VALUES (NOW()) RETURNING id;"
+            )
+            .fetch_one(&state.db)
+            .await
+            .unwrap()
+        };
+        let request_session_id = insert_session().await;
+        let response_session_id = insert_session().await;
+        let request_seq = sqlx::query_scalar!(
+            "INSERT INTO requests (request_id, session_id, method, params, time_stamp) VALUES ('7', $1, 'textDocument/definition', '{}', NOW()) RETURNING seq;",
+            request_session_id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO responses (request_id, session_id, is_error, result, time_stamp) VALUES ('7', $1, false, 'null', NOW());",
+            response_session_id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        let html = render_session(state, &format!("session_id={response_session_id}")).await;
+
+        assert!(html.contains(&format!(
+            "<a class=\"external_request\" href=\"/session?session_id={request_session_id}&seq={request_seq}#msg-{request_seq}\">(request in session {request_session_id})</a>"
+        )));
+        assert!(!html.contains("Unknown Response"));
+    }
+}
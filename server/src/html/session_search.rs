@@ -1,26 +1,133 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
     response::Html,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer, de::Error};
 use sqlx::prelude::FromRow;
 use time::OffsetDateTime;
 
-use crate::{AppState, utils::get_iso_string};
+use crate::{
+    AppState,
+    error::AppError,
+    html::stylesheet::Stylesheet,
+    utils::{deserialize_optional_id, get_byte_size_string, get_duration_string, get_iso_string},
+};
+
+/// How many sessions are listed on each page, unless a page size is given.
+const DEFAULT_PAGE_SIZE: usize = 100;
+/// The most sessions listed on one page, however many are asked for.
+const MAX_PAGE_SIZE: usize = 500;
 
 #[derive(FromRow)]
 struct Session {
     id: i64,
     start_time_stamp: OffsetDateTime,
     end_time_stamp: Option<OffsetDateTime>,
+    starred: bool,
+    client_name: Option<String>,
+    client_version: Option<String>,
+    server_name: Option<String>,
+    server_version: Option<String>,
+    workspace_root: Option<String>,
+    message_count: i64,
+    byte_size: i64,
 }
 
+/// The sessions along with the totals they can be sorted by. Sessions that haven't ended have no
+/// `duration`, which sorts them as the longest.
+const SESSIONS_WITH_TOTALS: &str = "(SELECT sessions.*,
+    (SELECT COUNT(*) FROM requests WHERE session_id = sessions.id)
+        + (SELECT COUNT(*) FROM responses WHERE session_id = sessions.id)
+        + (SELECT COUNT(*) FROM notifications WHERE session_id = sessions.id) AS message_count,
+    (SELECT COALESCE(SUM(byte_size), 0) FROM requests WHERE session_id = sessions.id)
+        + (SELECT COALESCE(SUM(byte_size), 0) FROM responses WHERE session_id = sessions.id)
+        + (SELECT COALESCE(SUM(byte_size), 0) FROM notifications WHERE session_id = sessions.id) AS byte_size,
+    end_time_stamp - start_time_stamp AS duration
+    FROM sessions)";
+
 #[repr(u8)]
 #[derive(Clone, Copy, Deserialize, PartialEq, Eq)]
 enum SortColumn {
     StartTime = 0,
     EndTime = 1,
+    MessageCount = 2,
+    Duration = 3,
+}
+
+impl SortColumn {
+    fn column_name(self) -> &'static str {
+        match self {
+            SortColumn::StartTime => "start_time_stamp",
+            SortColumn::EndTime => "end_time_stamp",
+            SortColumn::MessageCount => "message_count",
+            SortColumn::Duration => "duration",
+        }
+    }
+}
+
+/// Whether to list sessions that are still open, have been closed, or both.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum SessionStatus {
+    #[default]
+    All,
+    /// Sessions that haven't ended, which usually have an editor still attached.
+    Open,
+    Closed,
+}
+
+impl SessionStatus {
+    fn all() -> &'static [SessionStatus] {
+        &[
+            SessionStatus::All,
+            SessionStatus::Open,
+            SessionStatus::Closed,
+        ]
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SessionStatus::All => "all",
+            SessionStatus::Open => "open",
+            SessionStatus::Closed => "closed",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            SessionStatus::All => "All sessions",
+            SessionStatus::Open => "Open",
+            SessionStatus::Closed => "Closed",
+        }
+    }
+
+    fn condition(&self) -> Option<&'static str> {
+        match self {
+            SessionStatus::All => None,
+            SessionStatus::Open => Some("s.end_time_stamp IS NULL"),
+            SessionStatus::Closed => Some("s.end_time_stamp IS NOT NULL"),
+        }
+    }
+}
+
+/// A session to paginate from, which is faster than skipping a page number's worth of sessions
+/// once the list grows long.
+#[derive(Clone, Copy)]
+enum Cursor {
+    /// The page of sessions that follows this one.
+    After(i64),
+    /// The page of sessions that precedes this one.
+    Before(i64),
+}
+
+/// Deserializes the number of a column to sort by, rejecting numbers that aren't a column.
+fn deserialize_sort_column<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<usize>, D::Error> {
+    let column = usize::deserialize(deserializer)?;
+    SortColumn::try_from(column)
+        .map_err(|_| D::Error::custom(format!("{column} is not a column to sort by")))?;
+    Ok(Some(column))
 }
 
 impl TryFrom<usize> for SortColumn {
@@ -29,74 +136,188 @@ impl TryFrom<usize> for SortColumn {
         match value {
             0 => Ok(SortColumn::StartTime),
             1 => Ok(SortColumn::EndTime),
+            2 => Ok(SortColumn::MessageCount),
+            3 => Ok(SortColumn::Duration),
             _ => Err(()),
         }
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Deserialize)]
 pub(crate) struct PagedSessionRequest {
     page: Option<usize>,
+    /// How many sessions to list on each page. Defaults to `DEFAULT_PAGE_SIZE`, and is clamped
+    /// to `MAX_PAGE_SIZE`.
+    page_size: Option<usize>,
+    #[serde(default, deserialize_with = "deserialize_sort_column")]
     primary_sort: Option<usize>,
     primary_asc: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_sort_column")]
     secondary_sort: Option<usize>,
     secondary_asc: Option<bool>,
+    /// Only list sessions that have been starred.
+    starred_only: Option<bool>,
+    /// List the sessions after this one rather than skipping to `page`, which is then only used
+    /// to label the page.
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    after_id: Option<i64>,
+    /// List the sessions before this one rather than skipping to `page`, which is then only used
+    /// to label the page.
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    before_id: Option<i64>,
+    /// Only list sessions whose client name contains this, ignoring case.
+    client: Option<String>,
+    /// Only list sessions whose server name contains this, ignoring case.
+    server: Option<String>,
+    /// Only list sessions whose workspace root contains this, ignoring case.
+    root: Option<String>,
+    /// Defaults to listing every session.
+    status: Option<SessionStatus>,
+}
+
+impl PagedSessionRequest {
+    fn page_size(&self) -> usize {
+        self.page_size
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// The text filters that were given, as their query parameter, the column they match, and
+    /// their value. Empty filters are left out so that they match every session.
+    fn text_filters(&self) -> impl Iterator<Item = (&'static str, &'static str, &str)> {
+        [
+            ("client", "client_name", &self.client),
+            ("server", "server_name", &self.server),
+            ("root", "workspace_root", &self.root),
+        ]
+        .into_iter()
+        .filter_map(|(param, column, value)| {
+            let value = value.as_deref()?.trim();
+            (!value.is_empty()).then_some((param, column, value))
+        })
+    }
 }
 
 pub(crate) async fn get_sessions(
     State(state): State<AppState>,
     Query(request): Query<PagedSessionRequest>,
-) -> Result<Html<String>, StatusCode> {
-    let mut order_by = String::new();
+) -> Result<Html<String>, AppError> {
+    // Each key is a column and whether it's sorted ascending. The id always comes last so that
+    // every session has a distinct position to paginate from.
+    let mut sort_keys = Vec::new();
     if let Some(primary_sort) = request.primary_sort {
-        match SortColumn::try_from(primary_sort).map_err(|_| StatusCode::BAD_REQUEST)? {
-            SortColumn::StartTime => order_by.push_str("ORDER BY start_time_stamp"),
-            SortColumn::EndTime => order_by.push_str("ORDER BY end_time_stamp"),
-        }
-
-        if request.primary_asc.unwrap_or(true) {
-            order_by.push_str(" ASC");
-        } else {
-            order_by.push_str(" DESC");
-        }
-
-        if let Some(secondary_sort) = request.secondary_sort {
-            match SortColumn::try_from(secondary_sort).map_err(|_| StatusCode::BAD_REQUEST)? {
-                SortColumn::StartTime => order_by.push_str(", start_time_stamp"),
-                SortColumn::EndTime => order_by.push_str(", end_time_stamp"),
-            }
-
-            if request.secondary_asc.unwrap_or(true) {
-                order_by.push_str(" ASC");
-            } else {
-                order_by.push_str(" DESC");
-            }
-        }
+        let column = SortColumn::try_from(primary_sort).map_err(|_| {
+            AppError::bad_request(format!("{primary_sort} is not a column to sort by"))
+        })?;
+        sort_keys.push((column.column_name(), request.primary_asc.unwrap_or(true)));
+    }
+    if let Some(secondary_sort) = request.secondary_sort {
+        let column = SortColumn::try_from(secondary_sort).map_err(|_| {
+            AppError::bad_request(format!("{secondary_sort} is not a column to sort by"))
+        })?;
+        sort_keys.push((column.column_name(), request.secondary_asc.unwrap_or(true)));
+    }
+    sort_keys.push(("id", true));
 
-        order_by.push_str(", id");
-    } else if let Some(secondary_sort) = request.secondary_sort {
-        match SortColumn::try_from(secondary_sort).map_err(|_| StatusCode::BAD_REQUEST)? {
-            SortColumn::StartTime => order_by.push_str("ORDER BY start_time_stamp"),
-            SortColumn::EndTime => order_by.push_str("ORDER BY end_time_stamp"),
+    let cursor = match (request.after_id, request.before_id) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::bad_request(
+                "after_id and before_id can't be given together",
+            ));
         }
+        (Some(after_id), None) => Some(Cursor::After(after_id)),
+        (None, Some(before_id)) => Some(Cursor::Before(before_id)),
+        (None, None) => None,
+    };
+    let page = request.page.unwrap_or(0);
+    let page_size = request.page_size();
+    if cursor.is_none()
+        && state
+            .config
+            .max_session_page
+            .is_some_and(|max_session_page| page > max_session_page)
+    {
+        return Err(AppError::bad_request(format!(
+            "page {page} can only be reached through the next and previous links"
+        )));
+    }
 
-        if request.secondary_asc.unwrap_or(true) {
-            order_by.push_str(" ASC");
-        } else {
-            order_by.push_str(" DESC");
+    // Walking backwards from a cursor reads the preceding sessions in reverse, so the order is
+    // flipped for the query and restored once the page has been read.
+    if let Some(Cursor::Before(_)) = cursor {
+        for (_, asc) in sort_keys.iter_mut() {
+            *asc = !*asc;
         }
+    }
 
-        order_by.push_str(", id");
+    // Skipping to a page without a cursor counts the sessions before it instead.
+    let offset = match cursor {
+        Some(_) => 0,
+        None => page
+            .checked_mul(page_size)
+            .and_then(|offset| i64::try_from(offset).ok())
+            .ok_or_else(|| AppError::bad_request(format!("page {page} is too far")))?,
+    };
+
+    // Every value from the request is bound as a parameter after the page size and offset, so
+    // that only the whitelisted sort columns are written into the query itself.
+    let starred_only = request.starred_only.unwrap_or(false);
+    let mut query = format!(
+        "SELECT s.id, s.start_time_stamp, s.end_time_stamp, s.starred, s.client_name, s.client_version, s.server_name, s.server_version, s.workspace_root, s.message_count, s.byte_size FROM {SESSIONS_WITH_TOTALS} s"
+    );
+    let mut parameter_count = 2;
+    let mut conditions = Vec::new();
+    if cursor.is_some() {
+        parameter_count += 1;
+        query.push_str(" CROSS JOIN (SELECT * FROM ");
+        query.push_str(SESSIONS_WITH_TOTALS);
+        query.push_str(" sessions WHERE id = $");
+        query.push_str(&parameter_count.to_string());
+        query.push_str(") c");
+        conditions.push(build_keyset_condition(&sort_keys));
+    }
+    if starred_only {
+        conditions.push("s.starred".to_string());
+    }
+    if let Some(condition) = request.status.unwrap_or_default().condition() {
+        conditions.push(condition.to_string());
+    }
+    let mut patterns = Vec::new();
+    for (_, column, value) in request.text_filters() {
+        parameter_count += 1;
+        patterns.push(format!("%{}%", escape_like_pattern(value)));
+        conditions.push(format!("s.{column} ILIKE ${parameter_count}"));
+    }
+    if !conditions.is_empty() {
+        query.push_str(" WHERE ");
+        query.push_str(&conditions.join(" AND "));
     }
 
-    let sessions = sqlx::query_as::<_, Session>(&format!(
-        "SELECT id, start_time_stamp, end_time_stamp FROM sessions {} LIMIT 100 OFFSET {};",
-        order_by,
-        request.page.unwrap_or(0) * 100
-    ))
-    .fetch_all(&state.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    query.push_str(" ORDER BY ");
+    query.push_str(
+        &sort_keys
+            .iter()
+            .map(|(column, asc)| format!("s.{} {}", column, if *asc { "ASC" } else { "DESC" }))
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    query.push_str(" LIMIT $1 OFFSET $2");
+
+    let mut sessions_query = sqlx::query_as::<_, Session>(&query)
+        .bind(page_size as i64)
+        .bind(offset);
+    if let Some(Cursor::After(id) | Cursor::Before(id)) = cursor {
+        sessions_query = sessions_query.bind(id);
+    }
+    let mut sessions = patterns
+        .into_iter()
+        .fold(sessions_query, |query, pattern| query.bind(pattern))
+        .fetch_all(&state.db)
+        .await
+        .map_err(|_| AppError::internal("failed to read the sessions"))?;
+    if let Some(Cursor::Before(_)) = cursor {
+        sessions.reverse();
+    }
 
     let mut html = String::new();
     html.push_str("<!DOCTYPE=html>");
@@ -108,29 +329,87 @@ pub(crate) async fn get_sessions(
     html.push_str("</head>");
 
     html.push_str("<body>");
-    html.push_str("<style>");
-    html.push_str(include_str!("../css/sessions.css"));
-    html.push_str("</style>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+    if starred_only {
+        html.push_str("<a href=\"/\">Show all sessions</a>");
+    } else {
+        html.push_str("<a href=\"/?starred_only=true\">Show starred sessions only</a>");
+    }
+    html.push_str(" <a href=\"/stats\">Latency across sessions</a>");
+    html.push_str(" <a href=\"/errors\">Errors across sessions</a>");
+    append_filter_form_to(&mut html, &request);
+    append_status_links_to(&mut html, &request);
     html.push_str("<table>");
     html.push_str("<tr>");
     html.push_str("<th>ID</th>");
+    html.push_str("<th>Starred</th>");
 
     html.push_str("<th><a href=\"");
-    html.push_str(&build_sorted_query_string(&request, SortColumn::StartTime));
+    html.push_str(&build_sorted_query_string(
+        &request,
+        Some(SortColumn::StartTime),
+        request.page,
+        None,
+    ));
     html.push_str("\">Start Time</a></th>");
 
     html.push_str("<th><a href=\"");
-    html.push_str(&build_sorted_query_string(&request, SortColumn::EndTime));
+    html.push_str(&build_sorted_query_string(
+        &request,
+        Some(SortColumn::EndTime),
+        request.page,
+        None,
+    ));
     html.push_str("\">End Time</a></th>");
 
+    html.push_str("<th><a href=\"");
+    html.push_str(&build_sorted_query_string(
+        &request,
+        Some(SortColumn::Duration),
+        request.page,
+        None,
+    ));
+    html.push_str("\">Duration</a></th>");
+
+    html.push_str("<th><a href=\"");
+    html.push_str(&build_sorted_query_string(
+        &request,
+        Some(SortColumn::MessageCount),
+        request.page,
+        None,
+    ));
+    html.push_str("\">Messages</a></th>");
+    html.push_str("<th>Size</th>");
+
+    html.push_str("<th>Client</th>");
+    html.push_str("<th>Server</th>");
+    html.push_str("<th>Workspace</th>");
     html.push_str("<th>Link</th>");
     html.push_str("</tr>");
 
+    // A full page may be followed by more sessions, while a short one is the last. Walking
+    // backwards, a short page instead means the start of the list was reached.
+    let (is_first_page, has_next_page) = match cursor {
+        Some(Cursor::Before(_)) => (page == 0 || sessions.len() < page_size, true),
+        _ => (page == 0, sessions.len() == page_size),
+    };
+    let first_id = sessions.first().map(|session| session.id);
+    let last_id = sessions.last().map(|session| session.id);
+
     for session in sessions {
         html.push_str("<tr>");
 
         html.push_str("<td>");
         html.push_str(&session.id.to_string());
+        if session.end_time_stamp.is_none() {
+            html.push_str(" <span class=\"live_badge\">live</span>");
+        }
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        if session.starred {
+            html.push_str("&#9733;");
+        }
         html.push_str("</td>");
 
         html.push_str("<td>");
@@ -143,6 +422,37 @@ pub(crate) async fn get_sessions(
         }
         html.push_str("</td>");
 
+        html.push_str("<td>");
+        match &session.end_time_stamp {
+            Some(end_time_stamp) => html.push_str(&get_duration_string(
+                &(*end_time_stamp - session.start_time_stamp),
+            )),
+            None => html.push_str("ongoing"),
+        }
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&session.message_count.to_string());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_byte_size_string(session.byte_size));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        append_name_and_version_to(&mut html, &session.client_name, &session.client_version);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        append_name_and_version_to(&mut html, &session.server_name, &session.server_version);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        if let Some(workspace_root) = &session.workspace_root {
+            html.push_str(&html_escape::encode_text(workspace_root));
+        }
+        html.push_str("</td>");
+
         html.push_str("<td>");
         html.push_str("<a href=\"/session?session_id=");
         html.push_str(&session.id.to_string());
@@ -156,6 +466,35 @@ pub(crate) async fn get_sessions(
     }
 
     html.push_str("</table>");
+
+    html.push_str("<div class=\"pagination\">");
+    if is_first_page {
+        html.push_str("<span class=\"disabled\">Previous</span>");
+    } else {
+        html.push_str("<a href=\"");
+        html.push_str(&build_sorted_query_string(
+            &request,
+            None,
+            Some(page - 1),
+            first_id.map(Cursor::Before),
+        ));
+        html.push_str("\">Previous</a>");
+    }
+    html.push_str("<span>Page ");
+    html.push_str(&(page + 1).to_string());
+    html.push_str("</span>");
+    if has_next_page {
+        html.push_str("<a href=\"");
+        html.push_str(&build_sorted_query_string(
+            &request,
+            None,
+            Some(page + 1),
+            last_id.map(Cursor::After),
+        ));
+        html.push_str("\">Next</a>");
+    }
+    html.push_str("</div>");
+
     html.push_str("</body>");
 
     html.push_str("</html>");
@@ -163,17 +502,183 @@ pub(crate) async fn get_sessions(
     Ok(Html(html))
 }
 
+fn append_name_and_version_to(html: &mut String, name: &Option<String>, version: &Option<String>) {
+    if let Some(name) = name {
+        html.push_str(&html_escape::encode_text(name));
+        if let Some(version) = version {
+            html.push(' ');
+            html.push_str(&html_escape::encode_text(version));
+        }
+    }
+}
+
+/// Writes links that list the open or closed sessions, keeping the current sort and page.
+fn append_status_links_to(html: &mut String, request: &PagedSessionRequest) {
+    let current_status = request.status.unwrap_or_default();
+    html.push_str("<div class=\"status_links\">");
+    for status in SessionStatus::all() {
+        if *status == current_status {
+            html.push_str("<span>");
+            html.push_str(status.label());
+            html.push_str("</span>");
+            continue;
+        }
+        let request = PagedSessionRequest {
+            status: Some(*status),
+            ..request.clone()
+        };
+        html.push_str("<a href=\"");
+        html.push_str(&build_sorted_query_string(
+            &request,
+            None,
+            request.page,
+            None,
+        ));
+        html.push_str("\">");
+        html.push_str(status.label());
+        html.push_str("</a>");
+    }
+    html.push_str("</div>");
+}
+
+/// Writes the form that filters sessions by client, server, and workspace. Filtering keeps the
+/// current sort but starts over from the first page.
+fn append_filter_form_to(html: &mut String, request: &PagedSessionRequest) {
+    html.push_str("<form action=\"/\" method=\"GET\">");
+    if request.starred_only.unwrap_or(false) {
+        html.push_str("<input type=\"hidden\" name=\"starred_only\" value=\"true\">");
+    }
+    if let Some(status) = request.status {
+        html.push_str("<input type=\"hidden\" name=\"status\" value=\"");
+        html.push_str(status.as_str());
+        html.push_str("\">");
+    }
+    if let Some(page_size) = request.page_size {
+        html.push_str("<input type=\"hidden\" name=\"page_size\" value=\"");
+        html.push_str(&page_size.to_string());
+        html.push_str("\">");
+    }
+    for (name, sort, asc) in [
+        ("primary", request.primary_sort, request.primary_asc),
+        ("secondary", request.secondary_sort, request.secondary_asc),
+    ] {
+        let Some(sort) = sort else {
+            continue;
+        };
+        html.push_str("<input type=\"hidden\" name=\"");
+        html.push_str(name);
+        html.push_str("_sort\" value=\"");
+        html.push_str(&sort.to_string());
+        html.push_str("\">");
+        html.push_str("<input type=\"hidden\" name=\"");
+        html.push_str(name);
+        html.push_str("_asc\" value=\"");
+        html.push_str(&asc.unwrap_or(true).to_string());
+        html.push_str("\">");
+    }
+    for (id, label, value) in [
+        ("client", "Client", &request.client),
+        ("server", "Server", &request.server),
+        ("root", "Workspace", &request.root),
+    ] {
+        html.push_str("<label for=\"");
+        html.push_str(id);
+        html.push_str("\">");
+        html.push_str(label);
+        html.push_str("</label>");
+        html.push_str("<input type=\"text\" id=\"");
+        html.push_str(id);
+        html.push_str("\" name=\"");
+        html.push_str(id);
+        html.push_str("\" value=\"");
+        if let Some(value) = value {
+            html.push_str(&html_escape::encode_double_quoted_attribute(value));
+        }
+        html.push_str("\">");
+    }
+    html.push_str("<button type=\"submit\">Filter</button>");
+    html.push_str("</form>");
+}
+
+/// Escapes the wildcards of a `LIKE` pattern, so that a filter matches its text literally.
+fn escape_like_pattern(value: &str) -> String {
+    let mut pattern = String::with_capacity(value.len());
+    for character in value.chars() {
+        if matches!(character, '\\' | '%' | '_') {
+            pattern.push('\\');
+        }
+        pattern.push(character);
+    }
+    pattern
+}
+
+/// Builds a link to the sessions list on `page`, reading from `cursor` when one is given. With a
+/// `sort_column_to_toggle` the link toggles sorting by that column, and otherwise it keeps the
+/// current sort.
 fn build_sorted_query_string(
     request: &PagedSessionRequest,
-    sort_column_to_toggle: SortColumn,
+    sort_column_to_toggle: Option<SortColumn>,
+    page: Option<usize>,
+    cursor: Option<Cursor>,
 ) -> String {
     let mut url = "/?".to_string();
-    if let Some(page) = request.page {
+    if request.starred_only.unwrap_or(false) {
+        url.push_str("starred_only=true&");
+    }
+    for (param, _, value) in request.text_filters() {
+        url.push_str(param);
+        url.push('=');
+        url.extend(form_urlencoded::byte_serialize(value.as_bytes()));
+        url.push('&');
+    }
+    if let Some(status) = request.status {
+        url.push_str("status=");
+        url.push_str(status.as_str());
+        url.push('&');
+    }
+    if let Some(page_size) = request.page_size {
+        url.push_str("page_size=");
+        url.push_str(&page_size.to_string());
+        url.push('&');
+    }
+    if let Some(page) = page {
         url.push_str("page=");
         url.push_str(&page.to_string());
 
         url.push('&');
     }
+    match cursor {
+        Some(Cursor::After(id)) => {
+            url.push_str("after_id=");
+            url.push_str(&id.to_string());
+            url.push('&');
+        }
+        Some(Cursor::Before(id)) => {
+            url.push_str("before_id=");
+            url.push_str(&id.to_string());
+            url.push('&');
+        }
+        None => {}
+    }
+
+    let Some(sort_column_to_toggle) = sort_column_to_toggle else {
+        if let Some(primary_sort) = request.primary_sort {
+            url.push_str("primary_sort=");
+            url.push_str(&primary_sort.to_string());
+            url.push_str("&primary_asc=");
+            url.push_str(&request.primary_asc.unwrap_or(true).to_string());
+            url.push('&');
+        }
+        if let Some(secondary_sort) = request.secondary_sort {
+            url.push_str("secondary_sort=");
+            url.push_str(&secondary_sort.to_string());
+            url.push_str("&secondary_asc=");
+            url.push_str(&request.secondary_asc.unwrap_or(true).to_string());
+            url.push('&');
+        }
+        url.pop();
+        return url;
+    };
 
     match request.primary_sort {
         None => {
@@ -226,3 +731,176 @@ fn build_sorted_query_string(
 
     url
 }
+
+/// Builds a condition matching the sessions that come after the cursor session `c` when ordered
+/// by `sort_keys`. Nulls sort last when ascending and first when descending, as Postgres orders
+/// them by default.
+fn build_keyset_condition(sort_keys: &[(&str, bool)]) -> String {
+    let mut alternatives = Vec::new();
+    for (i, (column, asc)) in sort_keys.iter().enumerate() {
+        let mut terms = sort_keys[..i]
+            .iter()
+            .map(|(column, _)| format!("s.{column} IS NOT DISTINCT FROM c.{column}"))
+            .collect::<Vec<_>>();
+        if *asc {
+            terms.push(format!(
+                "(s.{column} > c.{column} OR (s.{column} IS NULL AND c.{column} IS NOT NULL))"
+            ));
+        } else {
+            terms.push(format!(
+                "(s.{column} < c.{column} OR (c.{column} IS NULL AND s.{column} IS NOT NULL))"
+            ));
+        }
+        alternatives.push(format!("({})", terms.join(" AND ")));
+    }
+    format!("({})", alternatives.join(" OR "))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, response::IntoResponse};
+    use sqlx::PgPool;
+
+    use super::*;
+
+    /// Lists the sessions for a query string, returning the ids shown and the link to the next
+    /// page.
+    async fn list_sessions(state: &AppState, query: &str) -> (Vec<i64>, Option<String>) {
+        try_list_sessions(state, query).await.unwrap()
+    }
+
+    /// Lists the sessions for a query string like [`list_sessions`], or returns the status the
+    /// query string was refused with.
+    async fn try_list_sessions(
+        state: &AppState,
+        query: &str,
+    ) -> Result<(Vec<i64>, Option<String>), StatusCode> {
+        let uri = format!("/?{query}").parse().unwrap();
+        let query =
+            Query::try_from_uri(&uri).map_err(|rejection| rejection.into_response().status())?;
+        let Html(html) = get_sessions(State(state.clone()), query)
+            .await
+            .map_err(|err| err.into_response().status())?;
+
+        let ids = html
+            .split("<tr><td>")
+            .skip(1)
+            .map(|row| {
+                let end = row.find(|char: char| !char.is_ascii_digit()).unwrap();
+                row[..end].parse().unwrap()
+            })
+            .collect();
+        let next_link = html
+            .split_once("\">Next</a>")
+            .and_then(|(before, _)| before.rsplit_once("<a href=\"/?"))
+            .map(|(_, link)| link.to_string());
+        Ok((ids, next_link))
+    }
+
+    #[sqlx::test]
+    async fn keyset_pagination_reads_the_next_page_whatever_its_number(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut ids_by_start = Vec::new();
+        for hours in [5, 1, 4, 2, 7, 3, 6] {
+            let id = sqlx::query_scalar!(
+                "INSERT INTO sessions (start_time_stamp) VALUES ($1) RETURNING id;",
+                OffsetDateTime::UNIX_EPOCH + time::Duration::hours(hours)
+            )
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+            ids_by_start.push((hours, id));
+        }
+        ids_by_start.sort();
+        let ids_by_start = ids_by_start
+            .into_iter()
+            .map(|(_, id)| id)
+            .collect::<Vec<_>>();
+
+        let (first_page, next_link) =
+            list_sessions(&state, "page_size=3&primary_sort=0&primary_asc=true").await;
+        assert_eq!(first_page, ids_by_start[..3]);
+        let next_link = next_link.unwrap();
+        assert!(next_link.contains(&format!("after_id={}&", ids_by_start[2])));
+
+        let (second_page, _) = list_sessions(&state, &next_link).await;
+        assert_eq!(second_page, ids_by_start[3..6]);
+        let (offset_page, _) =
+            list_sessions(&state, "page_size=3&page=1&primary_sort=0&primary_asc=true").await;
+        assert_eq!(offset_page, second_page);
+
+        // The page number only labels the page once there's a cursor.
+        let (relabelled_page, _) = list_sessions(
+            &state,
+            &format!(
+                "page_size=3&page=40&after_id={}&primary_sort=0&primary_asc=true",
+                ids_by_start[2]
+            ),
+        )
+        .await;
+        assert_eq!(relabelled_page, second_page);
+    }
+
+    #[sqlx::test]
+    async fn hostile_query_strings_are_refused_or_ignored(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        for hours in 0..3 {
+            sqlx::query!(
+                "INSERT INTO sessions (start_time_stamp, client_name) VALUES ($1, 'vscode');",
+                OffsetDateTime::UNIX_EPOCH + time::Duration::hours(hours)
+            )
+            .execute(&state.db)
+            .await
+            .unwrap();
+        }
+
+        for query in [
+            "primary_sort=1;DROP%20TABLE%20sessions",
+            "primary_sort=1%20DESC;%20DROP%20TABLE%20sessions;--",
+            "primary_sort=4",
+            "secondary_sort=99",
+            "primary_sort=-1",
+            "primary_sort=18446744073709551616",
+            "page=abc",
+            "page=-1",
+            "page=99999999999999999999999",
+            "page=18446744073709551615&page_size=10",
+            "page_size=abc",
+            "page_size=-5",
+            "after_id=%27%20OR%20%271%27=%271",
+            "after_id=1%27;DROP%20TABLE%20sessions;--",
+            "before_id=%221%22",
+        ] {
+            assert_eq!(
+                try_list_sessions(&state, query).await.err(),
+                Some(StatusCode::BAD_REQUEST),
+                "{query} wasn't refused"
+            );
+        }
+
+        // Values that are bound as parameters or clamped are harmless, so they're listed like any
+        // other.
+        for query in [
+            "page_size=99999999999",
+            "page_size=0",
+            "page=1000000",
+            "client=%27;DROP%20TABLE%20sessions;--",
+            "client=%25%27%20OR%201=1%20--",
+        ] {
+            assert!(
+                try_list_sessions(&state, query).await.is_ok(),
+                "{query} wasn't listed"
+            );
+        }
+        let (ids, _) = list_sessions(&state, "client=%27;DROP%20TABLE%20sessions;--").await;
+        assert!(ids.is_empty());
+
+        let session_count = sqlx::query_scalar!(r#"SELECT COUNT(*) AS "count!" FROM sessions;"#)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(session_count, 3);
+        let (ids, _) = list_sessions(&state, "").await;
+        assert_eq!(ids.len(), 3);
+    }
+}
@@ -0,0 +1,120 @@
+use axum::{Form, extract::State, response::Redirect};
+use serde::Deserialize;
+use tracing::error;
+
+use crate::{AppState, error::AppError};
+
+#[derive(Deserialize)]
+pub(crate) struct StarSessionForm {
+    session_id: i64,
+    starred: bool,
+    /// The query string of the session page the form was submitted from, so its filters are
+    /// kept after the redirect back.
+    return_query: Option<String>,
+}
+
+/// Flags or unflags a session as interesting, then returns to the session page.
+pub(crate) async fn post_session_star(
+    State(state): State<AppState>,
+    Form(form): Form<StarSessionForm>,
+) -> Result<Redirect, AppError> {
+    let updated = sqlx::query!(
+        "UPDATE sessions SET starred = $1 WHERE id = $2;",
+        form.starred,
+        form.session_id
+    )
+    .execute(&state.db)
+    .await
+    .map_err(|err| {
+        error!("Failed to star session {}. Error: {err}", form.session_id);
+        AppError::internal(format!("failed to star session {}", form.session_id))
+    })?;
+
+    if updated.rows_affected() == 0 {
+        return Err(AppError::not_found(format!(
+            "session {} not found",
+            form.session_id
+        )));
+    }
+
+    Ok(Redirect::to(&match form.return_query {
+        Some(query) if !query.is_empty() => format!("/session?{query}"),
+        _ => format!("/session?session_id={}", form.session_id),
+    }))
+}
+
+/// Writes a button that toggles whether the session is starred. It can also be pressed with the
+/// browser's access key and `s`.
+pub(crate) fn append_star_form_to(
+    html: &mut String,
+    session_id: i64,
+    starred: bool,
+    return_query: Option<&str>,
+) {
+    html.push_str("<form class=\"star_form\" action=\"/session/star\" method=\"POST\">");
+    html.push_str("<input type=\"hidden\" name=\"session_id\" value=\"");
+    html.push_str(&session_id.to_string());
+    html.push_str("\">");
+    html.push_str("<input type=\"hidden\" name=\"starred\" value=\"");
+    html.push_str(&(!starred).to_string());
+    html.push_str("\">");
+    if let Some(return_query) = return_query {
+        html.push_str("<input type=\"hidden\" name=\"return_query\" value=\"");
+        html.push_str(&html_escape::encode_double_quoted_attribute(return_query));
+        html.push_str("\">");
+    }
+    if starred {
+        html.push_str("<button type=\"submit\" class=\"starred\" accesskey=\"s\" title=\"Unstar this session\">&#9733; Starred</button>");
+    } else {
+        html.push_str("<button type=\"submit\" accesskey=\"s\" title=\"Star this session as interesting\">&#9734; Star</button>");
+    }
+    html.push_str("</form>");
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::header::LOCATION, response::IntoResponse};
+    use sqlx::PgPool;
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn starring_from_the_session_page_updates_the_flag(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO sessions (start_time_stamp) VALUES (NOW()) RETURNING id;"
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let return_query = format!("session_id={session_id}&order=desc");
+
+        let redirect = post_session_star(
+            State(state.clone()),
+            Form(StarSessionForm {
+                session_id,
+                starred: true,
+                return_query: Some(return_query.clone()),
+            }),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        let starred =
+            sqlx::query_scalar!("SELECT starred FROM sessions WHERE id = $1;", session_id)
+                .fetch_one(&state.db)
+                .await
+                .unwrap();
+        assert!(starred);
+        assert_eq!(
+            redirect.headers()[LOCATION],
+            format!("/session?{return_query}")
+        );
+
+        let mut html = String::new();
+        append_star_form_to(&mut html, session_id, starred, Some(&return_query));
+        assert!(html.contains("<input type=\"hidden\" name=\"starred\" value=\"false\">"));
+        assert!(html.contains("&#9733; Starred"));
+    }
+}
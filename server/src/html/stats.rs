@@ -0,0 +1,674 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use lsp_server::Message;
+use serde::Deserialize;
+use time::Duration;
+
+use crate::{
+    AppState,
+    capabilities::find_unadvertised_methods,
+    error::AppError,
+    html::{append_method_cell_to, stylesheet::Stylesheet},
+    message::{CancellationAnalysis, Conversation, ProtocolPhaseSpan},
+    utils::{get_byte_size_string, get_duration_string, get_iso_string},
+};
+
+#[derive(Deserialize)]
+pub(crate) struct GetSessionStatsParams {
+    session_id: i64,
+    /// Show the responder's think time in place of round trip latency where it's known.
+    think_time: Option<bool>,
+    /// Cut method names longer than this many characters short.
+    method_width: Option<usize>,
+}
+
+pub(crate) async fn get_session_stats(
+    State(state): State<AppState>,
+    Query(request): Query<GetSessionStatsParams>,
+) -> Result<Html<String>, AppError> {
+    let session = sqlx::query!(
+        "SELECT id, start_time_stamp, end_time_stamp FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+
+    html.push_str("<h1>Session ");
+    html.push_str(&request.session_id.to_string());
+    html.push_str(" Stats</h1>");
+    html.push_str("<a href=\"/session?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Back to session</a>");
+
+    append_protocol_phases_to(
+        &mut html,
+        &conversation.get_protocol_phases(session.start_time_stamp, session.end_time_stamp),
+    );
+    append_latency_stats_to(&mut html, &request, &conversation);
+    append_cancellation_stats_to(&mut html, &conversation, request.method_width);
+    append_payload_size_stats_to(&mut html, &conversation, request.method_width);
+    append_unadvertised_methods_to(&mut html, &conversation, request.method_width);
+    append_diagnostics_by_document_to(&mut html, &conversation);
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
+
+fn append_protocol_phases_to(html: &mut String, phases: &[ProtocolPhaseSpan]) {
+    html.push_str("<h2>Protocol Phases</h2>");
+
+    let total = phases
+        .iter()
+        .map(ProtocolPhaseSpan::duration)
+        .sum::<Duration>();
+
+    html.push_str("<div class=\"phase_bar\">");
+    for phase in phases.iter() {
+        let percent = if total.is_zero() {
+            100.0 / phases.len() as f64
+        } else {
+            phase.duration() / total * 100.0
+        };
+
+        html.push_str("<div class=\"phase_");
+        html.push_str(&phase.phase.as_str().replace([' ', '-'], "_"));
+        html.push_str("\" style=\"width: ");
+        html.push_str(&format!("{percent:.2}%"));
+        html.push_str(";\" title=\"");
+        html.push_str(phase.phase.as_str());
+        html.push_str(": ");
+        html.push_str(&get_duration_string(&phase.duration()));
+        html.push_str("\"></div>");
+    }
+    html.push_str("</div>");
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Phase</th>");
+    html.push_str("<th>Started At</th>");
+    html.push_str("<th>Duration</th>");
+    html.push_str("</tr>");
+    for phase in phases.iter() {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        html.push_str(phase.phase.as_str());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_iso_string(&phase.start));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_duration_string(&phase.duration()));
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+    html.push_str("</table>");
+}
+
+fn append_latency_stats_to(
+    html: &mut String,
+    request: &GetSessionStatsParams,
+    conversation: &Conversation,
+) {
+    let show_think_time = request.think_time.unwrap_or(false);
+    let method_width = request.method_width;
+
+    html.push_str("<h2>Latency</h2>");
+    html.push_str("<a href=\"/session/stats?session_id=");
+    html.push_str(&request.session_id.to_string());
+    if let Some(method_width) = method_width {
+        html.push_str("&method_width=");
+        html.push_str(&method_width.to_string());
+    }
+    if show_think_time {
+        html.push_str("\">Show round trip latency</a>");
+    } else {
+        html.push_str("&think_time=true\">Show think time</a>");
+    }
+    html.push_str(" <a href=\"/session/");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("/latencies.csv\">Download as CSV</a>");
+
+    let pairs = conversation.pair_requests_with_responses();
+    if pairs.iter().all(|pair| pair.response.is_none()) {
+        return;
+    }
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Request ID</th>");
+    html.push_str("<th>Method</th>");
+    html.push_str("<th>Sent At</th>");
+    if show_think_time {
+        html.push_str("<th>Think Time</th>");
+    } else {
+        html.push_str("<th>Latency</th>");
+    }
+    html.push_str("</tr>");
+
+    for pair in pairs.iter() {
+        let (Message::Request(request), Some(latency)) = (&pair.request.message, pair.latency())
+        else {
+            continue;
+        };
+
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        html.push_str(&html_escape::encode_text(&request.id.to_string()));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        append_method_cell_to(html, &request.method, method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_iso_string(&pair.request.time_stamp));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        match pair.think_time().filter(|_| show_think_time) {
+            Some(think_time) => html.push_str(&get_duration_string(&think_time)),
+            None => {
+                html.push_str(&get_duration_string(&latency));
+                // Without both proxy time stamps only the round trip is known.
+                if show_think_time {
+                    html.push_str(" (round trip)");
+                }
+            }
+        }
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+}
+
+fn append_cancellation_stats_to(
+    html: &mut String,
+    conversation: &Conversation,
+    method_width: Option<usize>,
+) {
+    let CancellationAnalysis {
+        cancelled,
+        unknown_cancellations,
+    } = conversation.analyze_cancellations();
+
+    html.push_str("<h2>Cancellations</h2>");
+
+    let request_count = conversation
+        .messages()
+        .iter()
+        .filter(|message| matches!(message.message, Message::Request(_)))
+        .count();
+
+    let late_responses = cancelled
+        .iter()
+        .filter(|cancelled| cancelled.has_late_response())
+        .count();
+
+    let latencies = cancelled
+        .iter()
+        .map(|cancelled| cancelled.cancel_latency())
+        .collect::<Vec<_>>();
+
+    html.push_str("<table>");
+    append_stat_row_to(html, "Requests", &request_count.to_string());
+    append_stat_row_to(html, "Cancelled requests", &cancelled.len().to_string());
+    append_stat_row_to(
+        html,
+        "Average cancel latency",
+        &average(&latencies)
+            .map(|latency| get_duration_string(&latency))
+            .unwrap_or_default(),
+    );
+    append_stat_row_to(
+        html,
+        "Max cancel latency",
+        &latencies
+            .iter()
+            .max()
+            .map(get_duration_string)
+            .unwrap_or_default(),
+    );
+    append_stat_row_to(
+        html,
+        "Responses after cancellation",
+        &late_responses.to_string(),
+    );
+    append_stat_row_to(
+        html,
+        "Cancellations for unknown requests",
+        &unknown_cancellations.len().to_string(),
+    );
+    html.push_str("</table>");
+
+    if cancelled.is_empty() {
+        return;
+    }
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Request ID</th>");
+    html.push_str("<th>Method</th>");
+    html.push_str("<th>Sent At</th>");
+    html.push_str("<th>Cancel Latency</th>");
+    html.push_str("<th>Late Response</th>");
+    html.push_str("</tr>");
+
+    for cancelled in cancelled.iter() {
+        let Message::Request(request) = &cancelled.request.message else {
+            continue;
+        };
+
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        html.push_str(&html_escape::encode_text(&request.id.to_string()));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        append_method_cell_to(html, &request.method, method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_iso_string(&cancelled.request.time_stamp));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&get_duration_string(&cancelled.cancel_latency()));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        if cancelled.has_late_response() {
+            html.push_str("yes");
+        } else {
+            html.push_str("no");
+        }
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+}
+
+fn append_payload_size_stats_to(
+    html: &mut String,
+    conversation: &Conversation,
+    method_width: Option<usize>,
+) {
+    html.push_str("<h2>Payload Sizes</h2>");
+
+    // Keyed by method then message kind, so each method's rows stay together.
+    let mut sizes = BTreeMap::<(&str, &str), Vec<i32>>::new();
+    for message in conversation {
+        let Some(byte_size) = message.byte_size else {
+            continue;
+        };
+
+        let (method, kind) = match &message.message {
+            Message::Request(request) => (request.method.as_str(), "request"),
+            Message::Response(response) => match conversation.requests().get(&response.id) {
+                Some(request) => (request.method.as_str(), "response"),
+                None => continue,
+            },
+            Message::Notification(notification) => (notification.method.as_str(), "notification"),
+        };
+        sizes.entry((method, kind)).or_default().push(byte_size);
+    }
+
+    if sizes.is_empty() {
+        return;
+    }
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Method</th>");
+    html.push_str("<th>Kind</th>");
+    html.push_str("<th>Count</th>");
+    html.push_str("<th>Min</th>");
+    html.push_str("<th>Median</th>");
+    html.push_str("<th>P95</th>");
+    html.push_str("<th>Max</th>");
+    html.push_str("</tr>");
+
+    for ((method, kind), mut sizes) in sizes {
+        sizes.sort_unstable();
+
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        append_method_cell_to(html, method, method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(kind);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&sizes.len().to_string());
+        html.push_str("</td>");
+
+        for size in [
+            sizes[0],
+            percentile(&sizes, 0.5),
+            percentile(&sizes, 0.95),
+            sizes[sizes.len() - 1],
+        ] {
+            html.push_str("<td>");
+            html.push_str(&get_byte_size_string(size.into()));
+            html.push_str("</td>");
+        }
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+}
+
+/// Lists the methods the client requested that were added in a later protocol version than the
+/// server advertised support for.
+fn append_unadvertised_methods_to(
+    html: &mut String,
+    conversation: &Conversation,
+    method_width: Option<usize>,
+) {
+    html.push_str("<h2>Unadvertised Methods</h2>");
+
+    let Some(unadvertised) = find_unadvertised_methods(conversation) else {
+        html.push_str("<p>The server's response to initialize wasn't logged.</p>");
+        return;
+    };
+
+    if unadvertised.is_empty() {
+        html.push_str("<p>The server advertised every method the client requested.</p>");
+        return;
+    }
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Method</th>");
+    html.push_str("<th>Since</th>");
+    html.push_str("<th>Missing Capability</th>");
+    html.push_str("<th>Requests</th>");
+    html.push_str("</tr>");
+
+    for method in unadvertised.iter() {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        append_method_cell_to(html, method.method, method_width);
+        html.push_str("</td>");
+
+        html.push_str("<td>LSP ");
+        html.push_str(method.requirement.since);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(method.requirement.capability);
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&method.count.to_string());
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+}
+
+/// Lists the documents diagnostics were published for, with a sparkline of how many each
+/// publish reported, so documents that flip between clean and broken stand out.
+fn append_diagnostics_by_document_to(html: &mut String, conversation: &Conversation) {
+    html.push_str("<h2>Diagnostics by Document</h2>");
+
+    let counts_by_document = conversation.get_diagnostic_counts_by_document();
+    if counts_by_document.is_empty() {
+        html.push_str("<p>No diagnostics were published.</p>");
+        return;
+    }
+
+    html.push_str("<table>");
+    html.push_str("<tr>");
+    html.push_str("<th>Document</th>");
+    html.push_str("<th>Publishes</th>");
+    html.push_str("<th>Most</th>");
+    html.push_str("<th>Last</th>");
+    html.push_str("<th>Over Time</th>");
+    html.push_str("</tr>");
+
+    for (uri, counts) in counts_by_document.iter() {
+        html.push_str("<tr>");
+
+        html.push_str("<td>");
+        html.push_str(&html_escape::encode_text(uri));
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&counts.len().to_string());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&counts.iter().max().copied().unwrap_or(0).to_string());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        html.push_str(&counts.last().copied().unwrap_or(0).to_string());
+        html.push_str("</td>");
+
+        html.push_str("<td>");
+        append_sparkline_to(html, counts);
+        html.push_str("</td>");
+
+        html.push_str("</tr>");
+    }
+
+    html.push_str("</table>");
+}
+
+const SPARKLINE_WIDTH: f64 = 120.0;
+const SPARKLINE_HEIGHT: f64 = 24.0;
+
+/// Draws `values` as an inline SVG line, scaled so the largest value touches the top.
+fn append_sparkline_to(html: &mut String, values: &[usize]) {
+    let max = values.iter().max().copied().unwrap_or(0).max(1) as f64;
+    // Keep the stroke from being clipped at the edges.
+    let padding = 2.0;
+    let points = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let x = if values.len() == 1 {
+                SPARKLINE_WIDTH / 2.0
+            } else {
+                padding + i as f64 * (SPARKLINE_WIDTH - 2.0 * padding) / (values.len() - 1) as f64
+            };
+            let y = SPARKLINE_HEIGHT
+                - padding
+                - *value as f64 / max * (SPARKLINE_HEIGHT - 2.0 * padding);
+            (x, y)
+        })
+        .collect::<Vec<_>>();
+
+    html.push_str("<svg class=\"sparkline\" width=\"");
+    html.push_str(&SPARKLINE_WIDTH.to_string());
+    html.push_str("\" height=\"");
+    html.push_str(&SPARKLINE_HEIGHT.to_string());
+    html.push_str("\" viewBox=\"0 0 ");
+    html.push_str(&SPARKLINE_WIDTH.to_string());
+    html.push(' ');
+    html.push_str(&SPARKLINE_HEIGHT.to_string());
+    html.push_str("\">");
+    html.push_str("<title>");
+    html.push_str(
+        &values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    html.push_str("</title>");
+    // A single publish has nothing to draw a line between.
+    if let [(x, y)] = points.as_slice() {
+        html.push_str("<circle r=\"2\" cx=\"");
+        html.push_str(&format!("{x:.1}"));
+        html.push_str("\" cy=\"");
+        html.push_str(&format!("{y:.1}"));
+        html.push_str("\"/>");
+    } else {
+        html.push_str("<polyline points=\"");
+        html.push_str(
+            &points
+                .iter()
+                .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        html.push_str("\"/>");
+    }
+    html.push_str("</svg>");
+}
+
+fn append_stat_row_to(html: &mut String, name: &str, value: &str) {
+    html.push_str("<tr>");
+    html.push_str("<th>");
+    html.push_str(name);
+    html.push_str("</th>");
+    html.push_str("<td>");
+    html.push_str(value);
+    html.push_str("</td>");
+    html.push_str("</tr>");
+}
+
+fn average(durations: &[Duration]) -> Option<Duration> {
+    if durations.is_empty() {
+        return None;
+    }
+
+    let total = durations.iter().copied().sum::<Duration>();
+    Some(total / durations.len() as u32)
+}
+
+/// The nearest-rank percentile of an ascending, non-empty slice.
+fn percentile<T: Copy>(sorted: &[T], percentile: f64) -> T {
+    let rank = (percentile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::session::MessageWithTimeStamp;
+
+    #[test]
+    fn payload_sizes_are_distributed_per_method_and_kind() {
+        let mut messages = [100, 300, 200, 2048, 400]
+            .into_iter()
+            .zip(1..)
+            .map(|(byte_size, id)| {
+                let mut request = MessageWithTimeStamp::for_tests(
+                    id,
+                    id * 100,
+                    json!({ "jsonrpc": "2.0", "id": id, "method": "textDocument/hover", "params": {} }),
+                );
+                request.byte_size = Some(byte_size);
+                request
+            })
+            .collect::<Vec<_>>();
+        let mut response = MessageWithTimeStamp::for_tests(
+            6,
+            600,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+        );
+        response.byte_size = Some(50);
+        messages.push(response);
+        let conversation = Conversation::from(messages);
+
+        let mut html = String::new();
+        append_payload_size_stats_to(&mut html, &conversation, None);
+
+        assert!(html.contains(
+            "<td>request</td><td>5</td><td>100 B</td><td>300 B</td><td>2.0 KiB</td><td>2.0 KiB</td>"
+        ));
+        assert!(html.contains(
+            "<td>response</td><td>1</td><td>50 B</td><td>50 B</td><td>50 B</td><td>50 B</td>"
+        ));
+    }
+
+    #[test]
+    fn sparkline_points_follow_the_diagnostic_counts() {
+        let publish = |seq, uri, count| {
+            let diagnostic = json!({
+                "range": { "start": { "line": 0, "character": 0 }, "end": { "line": 0, "character": 1 } },
+                "message": "unused",
+            });
+            MessageWithTimeStamp::for_tests(
+                seq,
+                seq * 100,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": vec![diagnostic; count] },
+                }),
+            )
+        };
+        let conversation = Conversation::from(vec![
+            publish(1, "file:///main.rs", 2),
+            publish(2, "file:///lib.rs", 1),
+            publish(3, "file:///main.rs", 0),
+            publish(4, "file:///main.rs", 4),
+        ]);
+
+        let counts = conversation.get_diagnostic_counts_by_document();
+        assert_eq!(
+            counts,
+            BTreeMap::from([
+                ("file:///lib.rs".to_string(), vec![1]),
+                ("file:///main.rs".to_string(), vec![2, 0, 4]),
+            ])
+        );
+
+        let mut html = String::new();
+        append_sparkline_to(&mut html, &counts["file:///main.rs"]);
+        assert!(html.contains("<title>2, 0, 4</title>"));
+        assert!(html.contains("<polyline points=\"2.0,12.0 60.0,22.0 118.0,2.0\"/>"));
+
+        let mut html = String::new();
+        append_sparkline_to(&mut html, &counts["file:///lib.rs"]);
+        assert!(html.contains("<circle r=\"2\" cx=\"60.0\" cy=\"2.0\"/>"));
+    }
+}
@@ -0,0 +1,89 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::http::{HeaderMap, HeaderValue, header};
+
+use crate::config::Config;
+
+/// The stylesheets shared between pages. They're served from `/static` so that browsers can
+/// cache them across pages, or inlined into each page when `LLS_INLINE_CSS` is set.
+#[derive(Clone, Copy)]
+pub(crate) enum Stylesheet {
+    Chat,
+    Sessions,
+}
+
+impl Stylesheet {
+    fn contents(&self) -> &'static str {
+        match self {
+            Stylesheet::Chat => include_str!("../css/chat.css"),
+            Stylesheet::Sessions => include_str!("../css/sessions.css"),
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            Stylesheet::Chat => "/static/chat.css",
+            Stylesheet::Sessions => "/static/sessions.css",
+        }
+    }
+
+    /// Identifies this build's version of the stylesheet, so that links change whenever the
+    /// contents do and long-lived caching stays safe.
+    fn version(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.contents().hash(&mut hasher);
+        format!("{:x}", hasher.finish())
+    }
+
+    pub(crate) fn append_to(&self, html: &mut String, config: &Config) {
+        if config.inline_css {
+            html.push_str("<style>");
+            html.push_str(self.contents());
+            html.push_str("</style>");
+        } else {
+            html.push_str("<link rel=\"stylesheet\" href=\"");
+            html.push_str(self.path());
+            html.push_str("?v=");
+            html.push_str(&self.version());
+            html.push_str("\">");
+        }
+    }
+
+    fn serve(&self) -> (HeaderMap, &'static str) {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("text/css; charset=utf-8"),
+        );
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=31536000, immutable"),
+        );
+        (headers, self.contents())
+    }
+}
+
+pub(crate) async fn get_chat_css() -> (HeaderMap, &'static str) {
+    Stylesheet::Chat.serve()
+}
+
+pub(crate) async fn get_sessions_css() -> (HeaderMap, &'static str) {
+    Stylesheet::Sessions.serve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn the_chat_stylesheet_is_served_with_a_long_cache_header() {
+        let (headers, body) = get_chat_css().await;
+
+        assert_eq!(body, include_str!("../css/chat.css"));
+        assert_eq!(headers[header::CONTENT_TYPE], "text/css; charset=utf-8");
+        assert_eq!(
+            headers[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+    }
+}
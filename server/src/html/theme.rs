@@ -0,0 +1,134 @@
+use serde::Deserialize;
+
+use crate::message::MessageKind;
+
+/// Named palettes used to color messages by their `MessageKind`. The default theme leaves
+/// messages uncolored by kind.
+#[derive(Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Theme {
+    #[default]
+    Default,
+    Colorblind,
+    HighContrast,
+}
+
+// Okabe-Ito palette, distinguishable under the common forms of color blindness.
+const COLORBLIND_PALETTE: &[&str] = &[
+    "#E69F00", "#56B4E9", "#009E73", "#F0E442", "#0072B2", "#D55E00", "#CC79A7", "#FFFFFF",
+];
+
+const HIGH_CONTRAST_PALETTE: &[&str] = &[
+    "#FFFF00", "#00FFFF", "#FF00FF", "#00FF00", "#FF8000", "#FFFFFF",
+];
+
+impl Theme {
+    pub(crate) fn all() -> &'static [Theme] {
+        &[Theme::Default, Theme::Colorblind, Theme::HighContrast]
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Colorblind => "colorblind",
+            Theme::HighContrast => "high_contrast",
+        }
+    }
+
+    pub(crate) fn try_parse_str(str: &str) -> Option<Self> {
+        Theme::all()
+            .iter()
+            .find(|theme| theme.as_str() == str)
+            .copied()
+    }
+
+    fn client_color(&self) -> &'static str {
+        match self {
+            Theme::Default | Theme::Colorblind => "#245B47",
+            Theme::HighContrast => "#000000",
+        }
+    }
+
+    fn server_color(&self) -> &'static str {
+        match self {
+            Theme::Default | Theme::Colorblind => "#224942",
+            Theme::HighContrast => "#1A1A1A",
+        }
+    }
+
+    fn kind_palette(&self) -> Option<&'static [&'static str]> {
+        match self {
+            Theme::Default => None,
+            Theme::Colorblind => Some(COLORBLIND_PALETTE),
+            Theme::HighContrast => Some(HIGH_CONTRAST_PALETTE),
+        }
+    }
+
+    /// Emits the CSS variables for this theme. Themes with a kind palette also get one class
+    /// per `MessageKind` that points `--kind-color` at the kind's variable.
+    pub(crate) fn append_css_to(&self, html: &mut String) {
+        html.push_str(":root {");
+        html.push_str("--client-color: ");
+        html.push_str(self.client_color());
+        html.push(';');
+        html.push_str("--server-color: ");
+        html.push_str(self.server_color());
+        html.push(';');
+
+        let Some(palette) = self.kind_palette() else {
+            html.push('}');
+            return;
+        };
+        let kinds = MessageKind::all().map(Some).chain(std::iter::once(None));
+        for (index, kind) in kinds.clone().enumerate() {
+            html.push_str("--");
+            html.push_str(&get_kind_class_name(kind));
+            html.push_str(": ");
+            html.push_str(palette[index % palette.len()]);
+            html.push(';');
+        }
+        html.push('}');
+
+        for kind in kinds {
+            let class_name = get_kind_class_name(kind);
+            html.push('.');
+            html.push_str(&class_name);
+            html.push_str(" { --kind-color: var(--");
+            html.push_str(&class_name);
+            html.push_str("); }");
+        }
+    }
+}
+
+pub(crate) fn get_kind_class_name(kind: Option<MessageKind>) -> String {
+    match kind {
+        Some(kind) => format!("kind_{}", kind.key()),
+        None => "kind_uncategorized".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_selected_theme_emits_its_css_variables() {
+        let mut css = String::new();
+        Theme::HighContrast.append_css_to(&mut css);
+
+        assert!(css.contains("--client-color: #000000;"));
+        assert!(css.contains("--server-color: #1A1A1A;"));
+        assert!(css.contains("--kind_life_cycle: #FFFF00;"));
+        assert!(css.contains(".kind_life_cycle { --kind-color: var(--kind_life_cycle); }"));
+        assert!(css.contains(".kind_uncategorized { --kind-color: var(--kind_uncategorized); }"));
+    }
+
+    #[test]
+    fn the_default_theme_leaves_messages_uncolored_by_kind() {
+        let mut css = String::new();
+        Theme::Default.append_css_to(&mut css);
+
+        assert!(css.contains("--client-color: #245B47;"));
+        assert!(!css.contains("--kind"));
+    }
+}
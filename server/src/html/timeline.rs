@@ -0,0 +1,270 @@
+use axum::{
+    extract::{Query, State},
+    response::Html,
+};
+use lsp_server::Message;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+use crate::{
+    AppState,
+    error::AppError,
+    html::{
+        chat_view::append_message_url_to,
+        stylesheet::Stylesheet,
+        theme::{Theme, get_kind_class_name},
+    },
+    message::{Conversation, classify},
+    session::MessageWithTimeStamp,
+    utils::{get_duration_string, get_iso_string},
+};
+
+/// The width of the time axis, in pixels.
+const TIMELINE_WIDTH: f64 = 1200.0;
+/// Room on the left of the axis for the lane labels.
+const LABEL_WIDTH: f64 = 110.0;
+const LANE_HEIGHT: f64 = 16.0;
+/// Room above the lanes for the offsets along the time axis.
+const AXIS_HEIGHT: f64 = 20.0;
+/// How many offsets are marked along the time axis, besides the start.
+const AXIS_MARKS: u32 = 10;
+/// Bars are never drawn narrower than this, so that instant responses can still be hovered.
+const MIN_BAR_WIDTH: f64 = 2.0;
+
+#[derive(Deserialize)]
+pub(crate) struct GetSessionTimelineParams {
+    session_id: i64,
+    theme: Option<Theme>,
+}
+
+/// A request drawn as a bar from when it was sent until it was answered, or until the end of the
+/// session when it never was.
+struct TimelineBar<'a> {
+    request: &'a MessageWithTimeStamp,
+    response: Option<&'a MessageWithTimeStamp>,
+    end: OffsetDateTime,
+}
+
+/// Lays a session's messages out along a time axis, so that overlapping requests and gaps
+/// between messages can be seen at a glance. Each request is a bar that ends at its response,
+/// stacked into as many lanes as there were requests in flight at once, and notifications are
+/// ticks in a lane of their own. Every bar and tick links to its message in the chat.
+pub(crate) async fn get_session_timeline(
+    State(state): State<AppState>,
+    Query(request): Query<GetSessionTimelineParams>,
+) -> Result<Html<String>, AppError> {
+    sqlx::query!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        request.session_id
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(|err| AppError::session_lookup(request.session_id, err))?;
+
+    let conversation = crate::session::get_all_messages_for_session_in_chronological_order(
+        &state.db,
+        request.session_id,
+    )
+    .await
+    .map_err(|_| AppError::session_messages(request.session_id))?;
+
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE=html>");
+    html.push_str("<html>");
+
+    html.push_str("<head>");
+    html.push_str("<meta charset=\"UTF-8\"/>");
+    html.push_str("<title>LSP Analyzer</title>");
+    html.push_str("</head>");
+
+    html.push_str("<body>");
+    Stylesheet::Sessions.append_to(&mut html, &state.config);
+    html.push_str("<style>");
+    request
+        .theme
+        .unwrap_or(state.config.default_theme)
+        .append_css_to(&mut html);
+    html.push_str("</style>");
+
+    html.push_str("<h1>Session ");
+    html.push_str(&request.session_id.to_string());
+    html.push_str(" Timeline</h1>");
+    html.push_str("<a href=\"/session?session_id=");
+    html.push_str(&request.session_id.to_string());
+    html.push_str("\">Back to session</a>");
+
+    append_timeline_to(&mut html, request.session_id, &conversation);
+
+    html.push_str("</body>");
+    html.push_str("</html>");
+
+    Ok(Html(html))
+}
+
+fn append_timeline_to(html: &mut String, session_id: i64, conversation: &Conversation) {
+    let (Some(first), Some(last)) = (
+        conversation.messages().first(),
+        conversation.messages().last(),
+    ) else {
+        html.push_str("<p>This session has no messages.</p>");
+        return;
+    };
+    let start = first.time_stamp;
+    let end = last.time_stamp;
+    let total = end - start;
+
+    html.push_str("<p>");
+    html.push_str(&get_iso_string(&start));
+    html.push_str(" to ");
+    html.push_str(&get_iso_string(&end));
+    html.push_str(" (");
+    html.push_str(&get_duration_string(&total));
+    html.push_str(")</p>");
+
+    let bars = conversation
+        .pair_requests_with_responses()
+        .into_iter()
+        .map(|pair| TimelineBar {
+            request: pair.request,
+            response: pair.response,
+            end: pair.response.map_or(end, |response| response.time_stamp),
+        })
+        .collect::<Vec<_>>();
+    let lanes = assign_lanes(&bars);
+    let lane_count = lanes.iter().max().map_or(0, |lane| lane + 1);
+
+    let x_of = |time_stamp: OffsetDateTime| {
+        let offset = if total.is_zero() {
+            0.0
+        } else {
+            (time_stamp - start) / total * TIMELINE_WIDTH
+        };
+        LABEL_WIDTH + offset
+    };
+    // Notifications take the first lane, and the requests are stacked below them.
+    let y_of = |lane: usize| AXIS_HEIGHT + lane as f64 * LANE_HEIGHT;
+    let height = y_of(lane_count + 1);
+
+    html.push_str("<svg class=\"timeline\" width=\"");
+    html.push_str(&(LABEL_WIDTH + TIMELINE_WIDTH).to_string());
+    html.push_str("\" height=\"");
+    html.push_str(&height.to_string());
+    html.push_str("\">");
+
+    for mark in 0..=AXIS_MARKS {
+        let offset = total * mark / AXIS_MARKS;
+        let x = x_of(start + offset);
+        html.push_str(&format!(
+            "<line class=\"axis_mark\" x1=\"{x:.1}\" y1=\"{AXIS_HEIGHT}\" x2=\"{x:.1}\" y2=\"{height}\"/>"
+        ));
+        html.push_str(&format!(
+            "<text class=\"axis_label\" x=\"{x:.1}\" y=\"{:.1}\">",
+            AXIS_HEIGHT - 6.0
+        ));
+        html.push_str(&get_duration_string(&offset));
+        html.push_str("</text>");
+    }
+
+    html.push_str(&format!(
+        "<text class=\"lane_label\" x=\"0\" y=\"{:.1}\">Notifications</text>",
+        y_of(0) + LANE_HEIGHT - 4.0
+    ));
+    if lane_count > 0 {
+        html.push_str(&format!(
+            "<text class=\"lane_label\" x=\"0\" y=\"{:.1}\">Requests</text>",
+            y_of(1) + LANE_HEIGHT - 4.0
+        ));
+    }
+
+    for message_with_time_stamp in conversation.messages() {
+        let Message::Notification(notification) = &message_with_time_stamp.message else {
+            continue;
+        };
+        let x = x_of(message_with_time_stamp.time_stamp);
+        append_link_open_to(html, session_id, message_with_time_stamp, conversation);
+        html.push_str(&format!(
+            "<line class=\"tick\" x1=\"{x:.1}\" y1=\"{:.1}\" x2=\"{x:.1}\" y2=\"{:.1}\">",
+            y_of(0) + 2.0,
+            y_of(1) - 2.0
+        ));
+        html.push_str("<title>");
+        html.push_str(&html_escape::encode_text(&notification.method));
+        html.push_str(" at ");
+        html.push_str(&get_iso_string(&message_with_time_stamp.time_stamp));
+        html.push_str("</title>");
+        html.push_str("</line>");
+        html.push_str("</a>");
+    }
+
+    for (bar, lane) in bars.iter().zip(lanes) {
+        let Message::Request(request) = &bar.request.message else {
+            continue;
+        };
+        let x = x_of(bar.request.time_stamp);
+        let width = (x_of(bar.end) - x).max(MIN_BAR_WIDTH);
+        append_link_open_to(html, session_id, bar.request, conversation);
+        html.push_str("<rect class=\"bar");
+        if bar.response.is_none() {
+            html.push_str(" unanswered");
+        }
+        html.push_str(&format!(
+            "\" x=\"{x:.1}\" y=\"{:.1}\" width=\"{width:.1}\" height=\"{:.1}\">",
+            y_of(lane + 1) + 2.0,
+            LANE_HEIGHT - 4.0
+        ));
+        html.push_str("<title>");
+        html.push_str(&html_escape::encode_text(&request.method));
+        match bar.response {
+            Some(response) => {
+                html.push_str(" answered in ");
+                html.push_str(&get_duration_string(
+                    &(response.time_stamp - bar.request.time_stamp),
+                ));
+            }
+            None => html.push_str(" never answered"),
+        }
+        html.push_str("</title>");
+        html.push_str("</rect>");
+        html.push_str("</a>");
+    }
+
+    html.push_str("</svg>");
+}
+
+/// Puts each bar in the first lane that's free by the time its request is sent, so that
+/// requests only share a lane when they weren't in flight at the same time.
+fn assign_lanes(bars: &[TimelineBar]) -> Vec<usize> {
+    let mut lane_ends = Vec::<OffsetDateTime>::new();
+    bars.iter()
+        .map(|bar| {
+            let start = bar.request.time_stamp;
+            match lane_ends.iter().position(|lane_end| *lane_end < start) {
+                Some(lane) => {
+                    lane_ends[lane] = bar.end;
+                    lane
+                }
+                None => {
+                    lane_ends.push(bar.end);
+                    lane_ends.len() - 1
+                }
+            }
+        })
+        .collect()
+}
+
+/// Opens a link to the message in the chat, colored by the message's kind.
+fn append_link_open_to(
+    html: &mut String,
+    session_id: i64,
+    message_with_time_stamp: &MessageWithTimeStamp,
+    conversation: &Conversation,
+) {
+    html.push_str("<a class=\"");
+    html.push_str(&get_kind_class_name(classify(
+        &message_with_time_stamp.message,
+        conversation,
+    )));
+    html.push_str("\" href=\"");
+    append_message_url_to(html, session_id, message_with_time_stamp.seq);
+    html.push_str("\">");
+}
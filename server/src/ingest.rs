@@ -0,0 +1,1388 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{BufReader, ErrorKind},
+    sync::Arc,
+    time::Instant,
+};
+
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{
+        State, WebSocketUpgrade,
+        ws::{CloseFrame, Message as WsMessage, WebSocket, close_code},
+    },
+    http::{HeaderMap, Response, StatusCode, header::SEC_WEBSOCKET_PROTOCOL},
+};
+use lsp_server::{Message as LspMessage, RequestId};
+use lsp_types::{
+    CancelParams, InitializeParams, InitializeResult, NumberOrString,
+    notification::{Cancel, Exit, Notification},
+    request::{Initialize, Request},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, value::RawValue};
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt},
+    net::{UnixListener, UnixStream},
+};
+use tracing::{error, info, info_span};
+
+use crate::{
+    AppState,
+    compression::{compress_payload, decompress_frame, is_gzipped},
+    error::AppError,
+    live::LiveEvent,
+    message::{Conversation, MessageKind, classify},
+    metrics::MessageType,
+    session::{MessageSource, MessageWithTimeStamp, ProxyTimeStamps, get_stored_request_id},
+};
+
+#[derive(Deserialize)]
+struct WrappedLspMessage {
+    source: MessageSource,
+    #[serde(flatten)]
+    proxy_time_stamps: ProxyTimeStamps,
+    #[serde(flatten)]
+    msg: LspMessage,
+}
+
+/// How a connection's messages are laid out, which collectors declare with a WebSocket
+/// subprotocol.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum WireFormat {
+    /// Each message is tried as a `WrappedLspMessage` and then as a bare message, for collectors
+    /// that don't request a subprotocol and for connections that have none.
+    #[default]
+    Sniffed,
+    /// Every message is a `WrappedLspMessage`.
+    Wrapped,
+    /// Every message is a bare `LspMessage`, whose direction is inferred from its method.
+    Bare,
+}
+
+/// The subprotocols that collectors can request, along with the format each one declares.
+const WIRE_FORMAT_SUBPROTOCOLS: &[(WireFormat, &str)] = &[
+    (WireFormat::Wrapped, "lls.wrapped.v1"),
+    (WireFormat::Bare, "lls.bare.v1"),
+];
+
+impl WireFormat {
+    fn from_subprotocol(subprotocol: &str) -> Option<Self> {
+        WIRE_FORMAT_SUBPROTOCOLS
+            .iter()
+            .find(|(_, name)| *name == subprotocol)
+            .map(|(format, _)| *format)
+    }
+
+    /// Parses the JSON content of one message.
+    fn parse(
+        self,
+        content: &[u8],
+    ) -> Result<(LspMessage, Option<MessageSource>, ProxyTimeStamps), serde_json::Error> {
+        let parse_wrapped = || {
+            serde_json::from_slice::<WrappedLspMessage>(content)
+                .map(|wrapped| (wrapped.msg, Some(wrapped.source), wrapped.proxy_time_stamps))
+        };
+        let parse_bare = || {
+            serde_json::from_slice::<LspMessage>(content)
+                .map(|msg| (msg, None, ProxyTimeStamps::default()))
+        };
+        match self {
+            WireFormat::Sniffed => parse_wrapped().or_else(|_| parse_bare()),
+            WireFormat::Wrapped => parse_wrapped(),
+            WireFormat::Bare => parse_bare(),
+        }
+    }
+}
+
+/// Accepts a collector's connection. Collectors declare the format of their messages by
+/// requesting one of `WIRE_FORMAT_SUBPROTOCOLS`, and those that request none have each message's
+/// format sniffed. Connections that only request unknown subprotocols are closed with a reason.
+pub(crate) async fn handle_ws(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Response<Body> {
+    let ws = ws.protocols(WIRE_FORMAT_SUBPROTOCOLS.iter().map(|(_, name)| *name));
+    let format = match ws.selected_protocol() {
+        Some(subprotocol) => subprotocol
+            .to_str()
+            .ok()
+            .and_then(WireFormat::from_subprotocol),
+        None if headers.contains_key(SEC_WEBSOCKET_PROTOCOL) => None,
+        None => Some(WireFormat::Sniffed),
+    };
+    ws.on_upgrade(move |socket| async move {
+        match format {
+            Some(format) => handle_ws_upgrade(socket, state, format).await,
+            None => reject_subprotocols(socket).await,
+        }
+    })
+}
+
+async fn reject_subprotocols(mut socket: WebSocket) {
+    let expected = WIRE_FORMAT_SUBPROTOCOLS
+        .iter()
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(" or ");
+    info!("Rejected a connection that requested no known subprotocol");
+    // If it errors then the socket was already closed.
+    socket
+        .send(WsMessage::Close(Some(CloseFrame {
+            code: close_code::PROTOCOL,
+            reason: format!("unknown subprotocol, expected {expected} or none").into(),
+        })))
+        .await
+        .ok();
+}
+
+async fn handle_ws_upgrade(mut socket: WebSocket, state: AppState, format: WireFormat) {
+    let mut session = match LoggingSession::start(&state).await {
+        Ok(session) => session,
+        Err(err) => {
+            error!("Failed to get a session_id. Error: {err}");
+            // Close the socket. If it errors then the socket was already closed.
+            socket.send(WsMessage::Close(None)).await.ok();
+            return;
+        }
+    };
+    session.format = format;
+
+    let session_span = info_span!("session", session_id = session.id);
+    let _session_span_handle = session_span.enter();
+
+    state.metrics.record_websocket_opened();
+    let liveness = &state.config.websocket_liveness;
+    let mut ping_interval = tokio::time::interval(liveness.ping_interval);
+    let mut last_frame_time = OffsetDateTime::now_utc();
+    let mut last_frame_instant = Instant::now();
+    loop {
+        let msg = tokio::select! {
+            msg = socket.recv() => msg,
+            _ = ping_interval.tick() => {
+                // Clients that crash without closing the connection never send another frame,
+                // so the connection would otherwise stay open forever.
+                if last_frame_instant.elapsed() >= liveness.timeout {
+                    info!("Timed out waiting for a frame from the client");
+                    end_session_at(&state, session.id, last_frame_time, true).await;
+                    break;
+                }
+                // If the ping can't be sent then the next recv reports why.
+                socket.send(WsMessage::Ping(Bytes::new())).await.ok();
+                continue;
+            }
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+
+        let now = OffsetDateTime::now_utc();
+        last_frame_time = now;
+        last_frame_instant = Instant::now();
+
+        let msg = match msg {
+            Err(err) => {
+                error!(
+                    "Encountered an error in the websocket connection. Error: {}",
+                    err
+                );
+                // client disconnected
+                break;
+            }
+            Ok(msg) => msg,
+        };
+
+        match &msg {
+            WsMessage::Text(utf8_bytes) => session.log_frame(utf8_bytes.as_bytes(), now).await,
+            WsMessage::Binary(bytes) => session.log_binary_frame(bytes, now).await,
+            WsMessage::Ping(_) | WsMessage::Pong(_) => continue,
+            WsMessage::Close(_) => break,
+        }
+    }
+    state.metrics.record_websocket_closed();
+}
+
+/// Accepts connections on a Unix domain socket, logging each one as its own session.
+pub(crate) async fn serve_uds(listener: UnixListener, state: AppState) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_uds_connection(stream, state.clone()));
+            }
+            Err(err) => error!("Failed to accept a unix socket connection. Error: {err}"),
+        }
+    }
+}
+
+async fn handle_uds_connection(stream: UnixStream, state: AppState) {
+    let mut session = match LoggingSession::start(&state).await {
+        Ok(session) => session,
+        Err(err) => {
+            // Dropping the stream closes the connection.
+            error!("Failed to get a session_id. Error: {err}");
+            return;
+        }
+    };
+
+    let session_span = info_span!("session", session_id = session.id);
+    let _session_span_handle = session_span.enter();
+
+    let mut reader = tokio::io::BufReader::new(stream);
+    loop {
+        match read_frame(&mut reader, state.config.max_frame_size).await {
+            Ok(Some(frame)) => session.log_frame(&frame, OffsetDateTime::now_utc()).await,
+            // client disconnected
+            Ok(None) => break,
+            Err(err) => {
+                error!(
+                    "Encountered an error in the unix socket connection. Error: {}",
+                    err
+                );
+                break;
+            }
+        }
+    }
+}
+
+/// Reads one `Content-Length` framed message, headers included, from a byte stream. Returns
+/// `None` once the stream ends cleanly between messages. Frames larger than `max_frame_size`
+/// are refused with an error before their content is read.
+pub(crate) async fn read_frame(
+    reader: &mut (impl AsyncBufRead + Unpin),
+    max_frame_size: usize,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let too_large = || {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!("the frame is larger than {max_frame_size} bytes"),
+        )
+    };
+    let mut frame = Vec::new();
+    let mut content_length = None;
+    loop {
+        let line_start = frame.len();
+        let remaining = max_frame_size.saturating_sub(line_start) as u64;
+        if (&mut *reader)
+            .take(remaining)
+            .read_until(b'\n', &mut frame)
+            .await?
+            == 0
+        {
+            if remaining == 0 {
+                return Err(too_large());
+            }
+            if frame.is_empty() {
+                return Ok(None);
+            }
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        let line = &frame[line_start..];
+        if line == b"\r\n" {
+            break;
+        }
+
+        if let Some((name, value)) = str::from_utf8(line)
+            .ok()
+            .and_then(|line| line.split_once(':'))
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            "missing a valid Content-Length header",
+        )
+    })?;
+    let content_start = frame.len();
+    let frame_size = content_start
+        .checked_add(content_length)
+        .filter(|frame_size| *frame_size <= max_frame_size)
+        .ok_or_else(too_large)?;
+    frame.resize(frame_size, 0);
+    reader.read_exact(&mut frame[content_start..]).await?;
+    Ok(Some(frame))
+}
+
+/// The session created for one logging connection, along with the state needed to apply the
+/// method filter and sampling across its messages. The session is ended when this is dropped,
+/// however the connection closes.
+pub(crate) struct LoggingSession {
+    pub(crate) id: i64,
+    state: AppState,
+    filter: IngestFilter,
+    format: WireFormat,
+}
+
+/// Tracks which of a session's messages are left out of the database, and the kinds of the
+/// requests that were logged.
+#[derive(Default)]
+pub(crate) struct IngestFilter {
+    /// Requests excluded by the method filter, so that their responses are excluded too.
+    dropped_request_ids: HashSet<RequestId>,
+    dropped_message_count: usize,
+    /// Where each sampled method is in its current cycle of `rate` notifications.
+    sampled_method_counts: HashMap<String, u32>,
+    sampled_out_count: usize,
+    /// The kind of each logged request awaiting its response, so that the response is counted
+    /// as the same kind.
+    request_kinds: HashMap<RequestId, Option<MessageKind>>,
+}
+
+impl IngestFilter {
+    /// Classifies a message as it's logged, which only knows the requests logged before it.
+    /// Responses and cancellations take the kind of their request, as they do in the chat.
+    fn classify(&mut self, msg: &LspMessage) -> Option<MessageKind> {
+        match msg {
+            LspMessage::Response(resp) => self.request_kinds.remove(&resp.id).flatten(),
+            LspMessage::Notification(not) if not.method == Cancel::METHOD => {
+                serde_json::from_value::<CancelParams>(not.params.clone())
+                    .ok()
+                    .and_then(|params| {
+                        let id = match params.id {
+                            NumberOrString::Number(num) => RequestId::from(num),
+                            NumberOrString::String(str) => RequestId::from(str),
+                        };
+                        self.request_kinds.get(&id).copied().flatten()
+                    })
+                    .or(Some(MessageKind::Custom))
+            }
+            LspMessage::Request(req) => {
+                let kind = classify(msg, &Conversation::default());
+                self.request_kinds.insert(req.id.clone(), kind);
+                kind
+            }
+            LspMessage::Notification(_) => classify(msg, &Conversation::default()),
+        }
+    }
+}
+
+/// Inserts a new, open session and returns its id.
+async fn create_session(state: &AppState) -> Result<i64, sqlx::Error> {
+    let sampling = state.config.sampling.as_ref();
+    let session_id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO sessions (start_time_stamp, end_time_stamp, sample_rate, sampled_methods) VALUES ($1, NULL, $2, $3) RETURNING id;",
+    )
+    .bind(OffsetDateTime::now_utc())
+    .bind(sampling.map(|sampling| sampling.rate as i32))
+    .bind(sampling.map(|sampling| {
+        let mut methods = sampling.methods.iter().cloned().collect::<Vec<_>>();
+        methods.sort_unstable();
+        methods
+    }))
+    .fetch_one(&state.db)
+    .await;
+
+    match &session_id {
+        Ok(_) => state.metrics.record_session_created(),
+        Err(_) => state.metrics.record_insert_error(),
+    }
+    session_id
+}
+
+impl LoggingSession {
+    pub(crate) async fn start(state: &AppState) -> Result<Self, sqlx::Error> {
+        // acquire a session from the database
+        let id = create_session(state).await?;
+
+        state
+            .active_sessions
+            .lock()
+            .expect("the active session lock to not be poisoned")
+            .insert(id);
+
+        Ok(Self {
+            id,
+            state: state.clone(),
+            filter: IngestFilter::default(),
+            format: WireFormat::default(),
+        })
+    }
+
+    /// Parses a framed LSP message in this session's `WireFormat` and logs it. A frame may also
+    /// hold a JSON-RPC batch of messages.
+    async fn log_frame(&mut self, frame: &[u8], received_time: OffsetDateTime) {
+        // The content follows the blank line that ends the frame's headers.
+        let content = frame
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .map(|header_length| &frame[header_length + 4..]);
+
+        if let Some(batch) =
+            content.and_then(|content| serde_json::from_slice::<Vec<&RawValue>>(content).ok())
+        {
+            self.log_batch(batch, received_time).await;
+            return;
+        }
+
+        let (msg, source, proxy_time_stamps) = match content {
+            Some(content) => match self.format.parse(content) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.state.metrics.record_malformed_message();
+                    error!(
+                        "Malformed lsp_message. Contents: {}",
+                        String::from_utf8_lossy(frame)
+                    );
+                    self.log_malformed(frame, received_time, err.to_string())
+                        .await;
+                    return;
+                }
+            },
+            // Without the blank line the headers never ended, which reading the frame reports.
+            None => match LspMessage::read(&mut BufReader::new(frame)) {
+                Err(err) => {
+                    self.state.metrics.record_malformed_message();
+                    // Binary frames aren't guaranteed to be UTF-8.
+                    error!(
+                        "Malformed lsp_message. Contents: {}",
+                        String::from_utf8_lossy(frame)
+                    );
+                    self.log_malformed(frame, received_time, err.to_string())
+                        .await;
+                    return;
+                }
+                Ok(None) => return,
+                Ok(Some(parsed)) => (parsed, None, ProxyTimeStamps::default()),
+            },
+        };
+
+        let metadata = MessageMetadata {
+            received_time,
+            source,
+            proxy_time_stamps,
+            byte_size: content.and_then(|content| i32::try_from(content.len()).ok()),
+            raw: content
+                .filter(|_| self.state.config.store_raw)
+                .map(<[u8]>::to_vec),
+            batch_id: None,
+        };
+        self.log(msg, metadata).await;
+    }
+
+    /// Logs a binary WebSocket frame, which collectors may gzip to save bandwidth. Frames that
+    /// don't start with the gzip magic bytes are logged as they are.
+    async fn log_binary_frame(&mut self, frame: &[u8], received_time: OffsetDateTime) {
+        if !is_gzipped(frame) {
+            self.log_frame(frame, received_time).await;
+            return;
+        }
+
+        match decompress_frame(frame, self.state.config.max_frame_size) {
+            Ok(frame) => self.log_frame(&frame, received_time).await,
+            Err(err) => {
+                self.state.metrics.record_malformed_message();
+                error!("Failed to decompress a gzipped frame. Error: {err}");
+                self.log_malformed(frame, received_time, format!("invalid gzip: {err}"))
+                    .await;
+            }
+        }
+    }
+
+    /// Logs each message of a JSON-RPC batch with the time the batch arrived, under a shared
+    /// batch id.
+    async fn log_batch(&mut self, batch: Vec<&RawValue>, received_time: OffsetDateTime) {
+        let batch_id = sqlx::query_scalar!("SELECT nextval('message_batch_seq');")
+            .fetch_one(&self.state.db)
+            .await
+            .inspect_err(|err| error!("Failed to allocate a batch id. Error: {err}"))
+            .ok()
+            .flatten();
+
+        for element in batch {
+            let content = element.get();
+            let (msg, source, proxy_time_stamps) = match self.format.parse(content.as_bytes()) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.state.metrics.record_malformed_message();
+                    error!(
+                        session_id = self.id,
+                        "Malformed lsp_message in batch. Error: {err}. Contents: {content}"
+                    );
+                    self.log_malformed(content.as_bytes(), received_time, err.to_string())
+                        .await;
+                    continue;
+                }
+            };
+
+            let metadata = MessageMetadata {
+                received_time,
+                source,
+                proxy_time_stamps,
+                byte_size: i32::try_from(content.len()).ok(),
+                raw: self
+                    .state
+                    .config
+                    .store_raw
+                    .then(|| content.as_bytes().to_vec()),
+                batch_id,
+            };
+            self.log(msg, metadata).await;
+        }
+    }
+
+    /// Stores a frame that couldn't be parsed, so that it still shows up in the session.
+    pub(crate) async fn log_malformed(
+        &self,
+        raw_bytes: &[u8],
+        received_time: OffsetDateTime,
+        parse_error: String,
+    ) {
+        let insert = sqlx::query!(
+            "INSERT INTO malformed_messages (session_id, raw_bytes, time_stamp, parse_error) VALUES ($1, $2, $3, $4)",
+            self.id,
+            raw_bytes,
+            received_time,
+            parse_error
+        )
+        .execute(&self.state.db)
+        .await;
+
+        if let Err(err) = insert {
+            self.state.metrics.record_insert_error();
+            error!("Failed to store a malformed message. Error: {err}");
+        }
+    }
+
+    pub(crate) async fn log(&mut self, msg: LspMessage, metadata: MessageMetadata) {
+        let is_exit = matches!(&msg, LspMessage::Notification(not) if not.method == Exit::METHOD);
+        log_message(&self.state, msg, Some(self.id), metadata, &mut self.filter).await;
+
+        // A clean shutdown ends the session even if the connection lingers afterwards.
+        if is_exit {
+            end_session(&self.state, self.id).await;
+        }
+    }
+}
+
+impl Drop for LoggingSession {
+    fn drop(&mut self) {
+        self.state
+            .active_sessions
+            .lock()
+            .expect("the active session lock to not be poisoned")
+            .remove(&self.id);
+
+        if self.filter.dropped_message_count > 0 {
+            info!(
+                session_id = self.id,
+                "Dropped {} messages by method filter", self.filter.dropped_message_count
+            );
+        }
+        if self.filter.sampled_out_count > 0 {
+            info!(
+                session_id = self.id,
+                "Left {} notifications out of the sample", self.filter.sampled_out_count
+            );
+        }
+
+        let state = self.state.clone();
+        let id = self.id;
+        self.state
+            .tasks
+            .spawn(async move { end_session(&state, id).await });
+    }
+}
+
+/// Records that a session ended now. A session that already has an end time keeps it, so the
+/// time of an `exit` notification isn't replaced when its connection closes later.
+async fn end_session(state: &AppState, session_id: i64) {
+    end_session_at(state, session_id, OffsetDateTime::now_utc(), false).await;
+}
+
+/// Records when a session ended, and whether that was because its connection timed out, unless
+/// it had already ended.
+async fn end_session_at(
+    state: &AppState,
+    session_id: i64,
+    end_time_stamp: OffsetDateTime,
+    timed_out: bool,
+) {
+    let update = sqlx::query!(
+        "UPDATE sessions SET end_time_stamp = $1, timed_out = $2 WHERE id = $3 AND end_time_stamp IS NULL",
+        end_time_stamp,
+        timed_out,
+        session_id
+    )
+    .execute(&state.db)
+    .await;
+
+    match update {
+        Ok(update) if update.rows_affected() > 0 => {
+            state.live_events.publish(LiveEvent::Ended { session_id });
+        }
+        Ok(_) => {}
+        Err(err) => error!("Failed to write the end_time_stamp. Message: {}", err),
+    }
+}
+
+/// Ends the sessions whose connections are still open, since they can't log anything once the
+/// server has stopped.
+pub(crate) async fn end_active_sessions(state: &AppState) {
+    let active_sessions = state
+        .active_sessions
+        .lock()
+        .expect("the active session lock to not be poisoned")
+        .iter()
+        .copied()
+        .collect::<Vec<_>>();
+
+    for session_id in active_sessions {
+        end_session(state, session_id).await;
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct CreatedSession {
+    pub(crate) session_id: i64,
+}
+
+/// Starts a session for clients that log messages one request at a time through `POST /log`
+/// instead of holding a connection open. The session is ended by the idle sweep, if enabled.
+pub(crate) async fn handle_create_session(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<CreatedSession>), AppError> {
+    let session_id = create_session(&state).await.map_err(|err| {
+        error!("Failed to create a session. Error: {err}");
+        AppError::internal("failed to create a session")
+    })?;
+
+    Ok((StatusCode::CREATED, Json(CreatedSession { session_id })))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LogMessageBody {
+    session_id: i64,
+    /// Left out for messages whose direction isn't known, like some of those in an export.
+    #[serde(default)]
+    source: Option<MessageSource>,
+    #[serde(flatten)]
+    proxy_time_stamps: ProxyTimeStamps,
+    #[serde(flatten)]
+    msg: LspMessage,
+}
+
+/// Logs a single message to an existing session. Each message is filtered on its own, so
+/// sampling and dropping the responses of filtered requests only apply to connected sessions.
+/// Logging an `exit` notification ends the session, like it does on a connection.
+pub(crate) async fn handle_log(
+    State(state): State<AppState>,
+    Json(body): Json<LogMessageBody>,
+) -> Result<StatusCode, AppError> {
+    let received_time = OffsetDateTime::now_utc();
+
+    let session = sqlx::query_scalar!(
+        "SELECT id FROM sessions WHERE id = $1 LIMIT 1;",
+        body.session_id
+    )
+    .fetch_optional(&state.db)
+    .await;
+    match session {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return Err(AppError::not_found(format!(
+                "session {} not found",
+                body.session_id
+            )));
+        }
+        Err(err) => {
+            error!(
+                "Failed to look up session {}. Error: {err}",
+                body.session_id
+            );
+            return Err(AppError::internal(format!(
+                "failed to look up session {}",
+                body.session_id
+            )));
+        }
+    }
+
+    let metadata = MessageMetadata {
+        received_time,
+        source: body.source,
+        proxy_time_stamps: body.proxy_time_stamps,
+        byte_size: None,
+        raw: None,
+        batch_id: None,
+    };
+    let is_exit = matches!(&body.msg, LspMessage::Notification(not) if not.method == Exit::METHOD);
+    let status = log_message(
+        &state,
+        body.msg,
+        Some(body.session_id),
+        metadata,
+        &mut IngestFilter::default(),
+    )
+    .await;
+    if status.is_server_error() {
+        return Err(AppError::internal(format!(
+            "failed to log the message to session {}",
+            body.session_id
+        )));
+    }
+    if is_exit {
+        end_session_at(&state, body.session_id, received_time, false).await;
+    }
+    Ok(status)
+}
+
+/// What the server knows about a message besides its contents.
+pub(crate) struct MessageMetadata {
+    pub(crate) received_time: OffsetDateTime,
+    /// Which side of the connection sent the message, when the collector reported it.
+    pub(crate) source: Option<MessageSource>,
+    pub(crate) proxy_time_stamps: ProxyTimeStamps,
+    /// The size of the message's JSON content.
+    pub(crate) byte_size: Option<i32>,
+    /// The message's content exactly as received, when raw storage is enabled.
+    pub(crate) raw: Option<Vec<u8>>,
+    /// Shared by every message that arrived in the same JSON-RPC batch.
+    pub(crate) batch_id: Option<i64>,
+}
+
+/// Writes a message to the database. Every transport logs its messages through here. Messages
+/// excluded by the configured method filter or left out of the sample are counted in `filter`
+/// and acknowledged without being created.
+pub(crate) async fn log_message(
+    state: &AppState,
+    msg: LspMessage,
+    session_id: Option<i64>,
+    metadata: MessageMetadata,
+    filter: &mut IngestFilter,
+) -> StatusCode {
+    let is_allowed = match &msg {
+        LspMessage::Request(req) => {
+            let is_allowed = state.config.method_filter.allows(&req.method);
+            if !is_allowed {
+                filter.dropped_request_ids.insert(req.id.clone());
+            }
+            is_allowed
+        }
+        LspMessage::Notification(not) => state.config.method_filter.allows(&not.method),
+        LspMessage::Response(resp) => !filter.dropped_request_ids.remove(&resp.id),
+    };
+
+    if !is_allowed {
+        filter.dropped_message_count += 1;
+        state.metrics.record_dropped_message();
+        return StatusCode::OK;
+    }
+
+    if let (LspMessage::Notification(not), Some(sampling)) = (&msg, &state.config.sampling)
+        && sampling.samples(&not.method)
+    {
+        // Keep the first notification of each method, then every rate-th one after it.
+        let position = filter
+            .sampled_method_counts
+            .entry(not.method.clone())
+            .or_default();
+        let is_sampled = *position == 0;
+        *position = (*position + 1) % sampling.rate;
+        if !is_sampled {
+            filter.sampled_out_count += 1;
+            state.metrics.record_sampled_out_message();
+            return StatusCode::OK;
+        }
+    }
+
+    let db = &state.db;
+    let message_type = MessageType::of(&msg);
+    let kind = filter.classify(&msg);
+    let MessageMetadata {
+        received_time,
+        source,
+        proxy_time_stamps,
+        byte_size,
+        raw,
+        batch_id,
+    } = metadata;
+    // Messages posted to `/log` arrive without a frame to measure, so they're measured by the
+    // JSON they serialize back to.
+    let byte_size = byte_size.or_else(|| {
+        serde_json::to_vec(&msg)
+            .ok()
+            .and_then(|json| i32::try_from(json.len()).ok())
+    });
+    // Only copied when someone is tailing, since most messages are never watched live.
+    let live_message = match session_id {
+        Some(session_id) if state.live_events.has_subscribers() => Some((
+            session_id,
+            MessageWithTimeStamp {
+                time_stamp: received_time,
+                // Filled in once the message is stored.
+                seq: 0,
+                proxy_time_stamps,
+                byte_size,
+                batch_id,
+                source,
+                message: msg.clone(),
+            },
+        )),
+        _ => None,
+    };
+    match msg {
+        LspMessage::Request(req) => {
+            let compressed_payload = compress_payload_for_storage(state, Some(&req.params));
+            let seq = sqlx::query_scalar!(
+                "INSERT INTO requests (request_id, session_id, method, params, time_stamp, source, sent_time_stamp, delivered_time_stamp, byte_size, raw, batch_id, compressed_payload) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING seq;",
+                get_stored_request_id(&req.id),
+                session_id,
+                req.method.clone(),
+                compressed_payload.is_none().then_some(&req.params),
+                received_time,
+                source.map(|source| source as i32),
+                proxy_time_stamps.sent_time_stamp,
+                proxy_time_stamps.delivered_time_stamp,
+                byte_size,
+                raw,
+                batch_id,
+                compressed_payload
+            )
+                .fetch_one(db)
+                .await;
+
+            let seq = match seq {
+                Ok(seq) => seq,
+                Err(err) => {
+                    error!("Failed to log a request to the database. Error: {err}");
+                    state.metrics.record_insert_error();
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+            };
+            state
+                .metrics
+                .record_ingested_message(message_type, kind, source);
+            publish_live_message(state, live_message, seq);
+
+            if let Some(session_id) = session_id
+                && req.method == Initialize::METHOD
+            {
+                record_client_identity(db, session_id, &req.params).await;
+            }
+
+            StatusCode::CREATED
+        }
+        LspMessage::Notification(not) => {
+            // Notifications without params, like `exit`, are stored without any.
+            let params = Some(not.params).filter(|params| !params.is_null());
+            let compressed_payload = compress_payload_for_storage(state, params.as_ref());
+            let seq = sqlx::query_scalar!(
+                "INSERT INTO notifications (session_id, method, params, time_stamp, source, byte_size, raw, batch_id, compressed_payload) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING seq;",
+                session_id,
+                not.method,
+                params.filter(|_| compressed_payload.is_none()),
+                received_time,
+                source.map(|source| source as i32),
+                byte_size,
+                raw,
+                batch_id,
+                compressed_payload
+            )
+                .fetch_one(db)
+                .await;
+
+            let seq = match seq {
+                Ok(seq) => seq,
+                Err(err) => {
+                    error!("Failed to log a notification to the database. Error: {err}");
+                    state.metrics.record_insert_error();
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+            };
+            state
+                .metrics
+                .record_ingested_message(message_type, kind, source);
+            publish_live_message(state, live_message, seq);
+
+            StatusCode::CREATED
+        }
+        LspMessage::Response(resp) => {
+            let is_err;
+            let error_code;
+            let error_message;
+            let error_data;
+            let result;
+            if let Some(err) = resp.error {
+                is_err = true;
+                error_code = Some(err.code);
+                error_message = Some(err.message);
+                error_data = err.data;
+                result = None;
+            } else if let Some(res) = &resp.result {
+                is_err = false;
+                error_code = None;
+                error_message = None;
+                error_data = None;
+                result = Some(res);
+            } else {
+                is_err = false;
+                error_code = None;
+                error_message = None;
+                error_data = None;
+                result = None;
+            }
+
+            let compressed_payload = compress_payload_for_storage(state, result);
+            let seq = sqlx::query_scalar!(
+                "INSERT INTO responses (request_id, session_id, is_error, result, error_code, error_message, error_data, time_stamp, source, sent_time_stamp, delivered_time_stamp, byte_size, raw, batch_id, compressed_payload) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15) RETURNING seq;",
+                get_stored_request_id(&resp.id),
+                session_id,
+                is_err,
+                result.filter(|_| compressed_payload.is_none()),
+                error_code,
+                error_message,
+                error_data,
+                received_time,
+                source.map(|source| source as i32),
+                proxy_time_stamps.sent_time_stamp,
+                proxy_time_stamps.delivered_time_stamp,
+                byte_size,
+                raw,
+                batch_id,
+                compressed_payload
+            )
+                .fetch_one(db)
+                .await;
+
+            let seq = match seq {
+                Ok(seq) => seq,
+                Err(err) => {
+                    error!("Failed to log a response to the database. Error: {err}");
+                    state.metrics.record_insert_error();
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+            };
+            state
+                .metrics
+                .record_ingested_message(message_type, kind, source);
+            publish_live_message(state, live_message, seq);
+
+            if let (Some(session_id), Some(result)) = (session_id, result) {
+                record_server_identity(db, session_id, &resp.id, result).await;
+            }
+
+            StatusCode::CREATED
+        }
+    }
+}
+
+/// Sends a stored message to the streams tailing its session.
+fn publish_live_message(
+    state: &AppState,
+    live_message: Option<(i64, MessageWithTimeStamp)>,
+    seq: i64,
+) {
+    if let Some((session_id, mut message)) = live_message {
+        message.seq = seq;
+        state.live_events.publish(LiveEvent::Logged {
+            session_id,
+            message: Arc::new(message),
+        });
+    }
+}
+
+/// Gzips a message's params or result to be stored in their place, when payload compression is
+/// enabled and they're large enough. Payloads that fail to compress are stored as they are.
+fn compress_payload_for_storage(state: &AppState, payload: Option<&Value>) -> Option<Vec<u8>> {
+    let threshold = state.config.compression_threshold?;
+    compress_payload(payload?, threshold)
+        .inspect_err(|err| error!("Failed to compress a payload. Error: {err}"))
+        .ok()
+        .flatten()
+}
+
+/// Records which client started a session, and the workspace it opened, from its `initialize`
+/// request.
+async fn record_client_identity(db: &PgPool, session_id: i64, params: &Value) {
+    let Ok(params) = serde_json::from_value::<InitializeParams>(params.clone()) else {
+        return;
+    };
+
+    let workspace_root = params
+        .workspace_folders
+        .as_ref()
+        .and_then(|folders| folders.first())
+        .map(|folder| folder.uri.to_string());
+    // Older clients only send the deprecated root fields.
+    #[allow(deprecated)]
+    let workspace_root = workspace_root
+        .or_else(|| params.root_uri.as_ref().map(|uri| uri.to_string()))
+        .or(params.root_path);
+    let (client_name, client_version) = match params.client_info {
+        Some(client_info) => (Some(client_info.name), client_info.version),
+        None => (None, None),
+    };
+
+    let result = sqlx::query!(
+        "UPDATE sessions SET client_name = $2, client_version = $3, workspace_root = $4 WHERE id = $1;",
+        session_id,
+        client_name,
+        client_version,
+        workspace_root
+    )
+    .execute(db)
+    .await;
+    if let Err(err) = result {
+        error!(
+            session_id,
+            "Failed to record the session's client. Error: {err}"
+        );
+    }
+}
+
+/// Records which server a session talked to, from its response to the `initialize` request.
+async fn record_server_identity(db: &PgPool, session_id: i64, id: &RequestId, result: &Value) {
+    // Only initialize results have capabilities, so other responses aren't parsed.
+    if result.get("capabilities").is_none() {
+        return;
+    }
+    let Ok(InitializeResult {
+        server_info: Some(server_info),
+        ..
+    }) = serde_json::from_value::<InitializeResult>(result.clone())
+    else {
+        return;
+    };
+
+    let result = sqlx::query!(
+        "UPDATE sessions SET server_name = $2, server_version = $3
+        WHERE id = $1 AND EXISTS (SELECT 1 FROM requests WHERE session_id = $1 AND request_id = $4 AND method = $5);",
+        session_id,
+        server_info.name,
+        server_info.version,
+        get_stored_request_id(id),
+        Initialize::METHOD
+    )
+    .execute(db)
+    .await;
+    if let Err(err) = result {
+        error!(
+            session_id,
+            "Failed to record the session's server. Error: {err}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use axum::{
+        extract::{Query, RawQuery},
+        response::Html,
+    };
+    use flate2::{Compression, write::GzEncoder};
+    use serde_json::json;
+    use sqlx::PgPool;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::config::{MethodFilter, SamplingConfig};
+
+    const HOVER: &str = r#"{"jsonrpc":"2.0","id":1,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.rs"},"position":{"line":3,"character":7}}}"#;
+
+    fn frame(content: &str) -> Vec<u8> {
+        format!("Content-Length: {}\r\n\r\n{content}", content.len()).into_bytes()
+    }
+
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn get_logged_hover(state: &AppState, session_id: i64) -> (String, String, Value) {
+        let row = sqlx::query!(
+            "SELECT request_id, method, params FROM requests WHERE session_id = $1;",
+            session_id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        (row.request_id, row.method, row.params.unwrap())
+    }
+
+    #[sqlx::test]
+    async fn gzipped_binary_frames_are_decompressed_before_parsing(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+
+        session
+            .log_binary_frame(&gzip(&frame(HOVER)), OffsetDateTime::now_utc())
+            .await;
+
+        let (request_id, method, params) = get_logged_hover(&state, session.id).await;
+        assert_eq!(request_id, "1");
+        assert_eq!(method, "textDocument/hover");
+        assert_eq!(
+            params,
+            json!({
+                "textDocument": { "uri": "file:///a.rs" },
+                "position": { "line": 3, "character": 7 },
+            })
+        );
+    }
+
+    #[sqlx::test]
+    async fn binary_frames_without_the_gzip_magic_are_logged_as_they_are(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+
+        session
+            .log_binary_frame(&frame(HOVER), OffsetDateTime::now_utc())
+            .await;
+
+        let (_, method, _) = get_logged_hover(&state, session.id).await;
+        assert_eq!(method, "textDocument/hover");
+    }
+
+    #[sqlx::test]
+    async fn invalid_gzip_frames_are_kept_as_malformed(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+        let frame = [0x1f, 0x8b, 0, 1, 2, 3];
+
+        session
+            .log_binary_frame(&frame, OffsetDateTime::now_utc())
+            .await;
+
+        let raw_bytes = sqlx::query_scalar!(
+            "SELECT raw_bytes FROM malformed_messages WHERE session_id = $1;",
+            session.id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(raw_bytes.as_deref(), Some(&frame[..]));
+    }
+
+    #[sqlx::test]
+    async fn denied_methods_are_not_stored(db: PgPool) {
+        let state = AppState::for_tests(db, |config| {
+            config.method_filter =
+                MethodFilter::Deny(HashSet::from(["textDocument/hover".to_string()]));
+        });
+        let mut session = LoggingSession::start(&state).await.unwrap();
+
+        for content in [
+            HOVER,
+            r#"{"jsonrpc":"2.0","id":1,"result":null}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/completion","params":{}}"#,
+            r#"{"jsonrpc":"2.0","id":2,"result":[]}"#,
+        ] {
+            session
+                .log_frame(&frame(content), OffsetDateTime::now_utc())
+                .await;
+        }
+
+        let methods = sqlx::query_scalar!(
+            "SELECT method FROM requests WHERE session_id = $1;",
+            session.id
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(methods, ["textDocument/completion"]);
+        // The denied request's response is left out with it.
+        let response_ids = sqlx::query_scalar!(
+            "SELECT request_id FROM responses WHERE session_id = $1;",
+            session.id
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(response_ids, [Some("2".to_string())]);
+    }
+
+    #[sqlx::test]
+    async fn a_message_sent_over_a_unix_socket_is_stored(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let path = std::env::temp_dir().join(format!("lls-test-{}.sock", std::process::id()));
+        std::fs::remove_file(&path).ok();
+        let listener = UnixListener::bind(&path).unwrap();
+        tokio::spawn(serve_uds(listener, state.clone()));
+
+        let mut stream = UnixStream::connect(&path).await.unwrap();
+        stream.write_all(&frame(HOVER)).await.unwrap();
+        stream.shutdown().await.unwrap();
+        drop(stream);
+        std::fs::remove_file(&path).ok();
+
+        // The connection is handled on its own task, so wait for it to store the message.
+        let mut stored = Vec::new();
+        for _ in 0..50 {
+            stored = sqlx::query_scalar!("SELECT method FROM requests;")
+                .fetch_all(&state.db)
+                .await
+                .unwrap();
+            if !stored.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(stored, ["textDocument/hover"]);
+    }
+
+    #[sqlx::test]
+    async fn a_batch_is_grouped_in_the_chat_with_its_count(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+        session
+            .log_frame(
+                &frame(&format!(
+                    r#"[{HOVER},{{"jsonrpc":"2.0","method":"initialized","params":{{}}}},{{"jsonrpc":"2.0","method":"$/setTrace","params":{{"value":"off"}}}}]"#
+                )),
+                OffsetDateTime::now_utc(),
+            )
+            .await;
+        session
+            .log_frame(
+                &frame(r#"{"jsonrpc":"2.0","id":1,"result":null}"#),
+                OffsetDateTime::now_utc(),
+            )
+            .await;
+
+        let query = format!("session_id={}", session.id);
+        let uri = format!("/session?{query}").parse().unwrap();
+        let (_, _, Html(html)) = crate::html::get_session(
+            State(state),
+            Query::try_from_uri(&uri).unwrap(),
+            RawQuery(Some(query)),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(html.matches("<div class=\"batch\">").count(), 1);
+        assert!(html.contains("<span class=\"batch_label\">batch of 3</span>"));
+    }
+
+    #[sqlx::test]
+    async fn one_in_n_sampled_notifications_is_stored(db: PgPool) {
+        let state = AppState::for_tests(db, |config| {
+            config.sampling = Some(SamplingConfig {
+                rate: 10,
+                methods: HashSet::from(["$/progress".to_string()]),
+            });
+        });
+        let mut session = LoggingSession::start(&state).await.unwrap();
+
+        for index in 0..100 {
+            let progress = format!(
+                r#"{{"jsonrpc":"2.0","method":"$/progress","params":{{"token":1,"value":{{"kind":"report","percentage":{index}}}}}}}"#
+            );
+            session
+                .log_frame(&frame(&progress), OffsetDateTime::now_utc())
+                .await;
+            if index % 20 == 0 {
+                session
+                    .log_frame(
+                        &frame(r#"{"jsonrpc":"2.0","method":"textDocument/didSave","params":{"textDocument":{"uri":"file:///a.rs"}}}"#),
+                        OffsetDateTime::now_utc(),
+                    )
+                    .await;
+            }
+        }
+
+        let counts = sqlx::query!(
+            r#"SELECT method, COUNT(*) AS "count!" FROM notifications WHERE session_id = $1 GROUP BY method ORDER BY method;"#,
+            session.id
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.method, row.count))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                ("$/progress".to_string(), 10),
+                ("textDocument/didSave".to_string(), 5),
+            ]
+        );
+        let sample_rate = sqlx::query_scalar!(
+            "SELECT sample_rate FROM sessions WHERE id = $1;",
+            session.id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(sample_rate, Some(10));
+    }
+
+    #[sqlx::test]
+    async fn both_entries_of_a_two_element_batch_are_stored(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+        let received_time = OffsetDateTime::now_utc();
+
+        session
+            .log_frame(
+                &frame(&format!(
+                    r#"[{HOVER},{{"jsonrpc":"2.0","method":"textDocument/didSave","params":{{"textDocument":{{"uri":"file:///a.rs"}}}}}}]"#
+                )),
+                received_time,
+            )
+            .await;
+
+        let request = sqlx::query!(
+            "SELECT method, time_stamp, batch_id FROM requests WHERE session_id = $1;",
+            session.id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        let notification = sqlx::query!(
+            "SELECT method, time_stamp, batch_id FROM notifications WHERE session_id = $1;",
+            session.id
+        )
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(request.method, "textDocument/hover");
+        assert_eq!(notification.method, "textDocument/didSave");
+        assert_eq!(request.time_stamp, notification.time_stamp);
+        assert!(request.batch_id.is_some());
+        assert_eq!(request.batch_id, notification.batch_id);
+    }
+
+    #[sqlx::test]
+    async fn ingested_messages_are_counted_by_type_and_kind(db: PgPool) {
+        let state = AppState::for_tests(db, |_| {});
+        let mut session = LoggingSession::start(&state).await.unwrap();
+
+        for content in [
+            HOVER,
+            r#"{"jsonrpc":"2.0","id":1,"result":null}"#,
+            r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/hover","params":{"textDocument":{"uri":"file:///a.rs"},"position":{"line":0,"character":0}}}"#,
+            r#"{"jsonrpc":"2.0","method":"$/cancelRequest","params":{"id":2}}"#,
+            r#"{"jsonrpc":"2.0","method":"textDocument/didClose","params":{"textDocument":{"uri":"file:///a.rs"}}}"#,
+            r#"{"jsonrpc":"2.0","id":3,"result":null}"#,
+        ] {
+            session
+                .log_frame(&frame(content), OffsetDateTime::now_utc())
+                .await;
+        }
+
+        let text = state.metrics.to_prometheus_text();
+        for line in [
+            r#"lls_messages_ingested_total{type="request",source="unknown"} 2"#,
+            r#"lls_messages_ingested_total{type="response",source="unknown"} 2"#,
+            r#"lls_messages_ingested_total{type="notification",source="unknown"} 2"#,
+            r#"lls_messages_ingested_by_kind_total{kind="hover",source="unknown"} 4"#,
+            r#"lls_messages_ingested_by_kind_total{kind="document_synchronization",source="unknown"} 1"#,
+            r#"lls_messages_ingested_by_kind_total{kind="uncategorized",source="unknown"} 1"#,
+            r#"lls_messages_ingested_by_kind_total{kind="completion",source="unknown"} 0"#,
+        ] {
+            assert!(text.contains(line), "{line} isn't in:\n{text}");
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::session::MessageWithTimeStamp;
+
+/// How many events a slow subscriber may fall behind by before it starts missing them.
+const LIVE_EVENT_CAPACITY: usize = 1024;
+
+/// What happened to a live session, as published to anyone tailing it.
+#[derive(Clone)]
+pub(crate) enum LiveEvent {
+    Logged {
+        session_id: i64,
+        /// Shared, since every subscriber receives its own copy of each event.
+        message: Arc<MessageWithTimeStamp>,
+    },
+    Ended {
+        session_id: i64,
+    },
+}
+
+/// Fans the messages of every live session out to the streams tailing them.
+#[derive(Clone)]
+pub(crate) struct LiveEvents(broadcast::Sender<LiveEvent>);
+
+impl Default for LiveEvents {
+    fn default() -> Self {
+        Self(broadcast::Sender::new(LIVE_EVENT_CAPACITY))
+    }
+}
+
+impl LiveEvents {
+    /// Whether anyone is tailing a session, so messages only need to be copied when they'll be
+    /// read.
+    pub(crate) fn has_subscribers(&self) -> bool {
+        self.0.receiver_count() > 0
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<LiveEvent> {
+        self.0.subscribe()
+    }
+
+    pub(crate) fn publish(&self, event: LiveEvent) {
+        // Sending only fails when there are no subscribers, and then there's no one to tell.
+        self.0.send(event).ok();
+    }
+}
@@ -1,34 +1,90 @@
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    os::unix::fs::FileTypeExt,
+    sync::{Arc, Mutex},
+};
+
 use axum::{
-    Router,
-    routing::{any, get},
+    Router, middleware,
+    routing::{any, get, post},
 };
 use sqlx::{PgPool, postgres::PgPoolOptions};
-use tokio::net::TcpListener;
+use tokio::{
+    net::{TcpListener, UnixListener},
+    signal::unix::{SignalKind, signal},
+};
 use tracing_subscriber::{
     EnvFilter,
     {layer::SubscriberExt, util::SubscriberInitExt},
 };
 
-use crate::error_logging::PostgresLayer;
+use crate::{
+    config::Config,
+    error_logging::{PostgresLayer, StdoutLayer},
+    live::LiveEvents,
+    metrics::Metrics,
+    tasks::TaskTracker,
+};
 
+mod api;
+mod auth;
+mod capabilities;
+mod compression;
+mod config;
+mod document;
+mod error;
 mod error_logging;
 mod html;
-mod language_logging;
+mod ingest;
+mod live;
 mod message;
+mod metrics;
+mod retention;
 mod session;
+mod spec;
+mod sweep;
+mod tasks;
 mod utils;
 
 #[derive(Clone)]
 struct AppState {
     db: PgPool,
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    /// Sessions that currently have an open logging connection.
+    active_sessions: Arc<Mutex<HashSet<i64>>>,
+    /// Messages and session ends, published as they happen for live tails.
+    live_events: LiveEvents,
+    /// Background writes that shutting down waits for.
+    tasks: TaskTracker,
+}
+
+#[cfg(test)]
+impl AppState {
+    /// A state over a database set up by `sqlx::test`, configured as if no `LLS_` variables were
+    /// set apart from the changes `configure` makes.
+    fn for_tests(db: PgPool, configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::from_env().expect("the default configuration to be valid");
+        configure(&mut config);
+        Self {
+            db,
+            config: Arc::new(config),
+            metrics: Arc::new(Metrics::default()),
+            active_sessions: Arc::new(Mutex::new(HashSet::new())),
+            live_events: LiveEvents::default(),
+            tasks: TaskTracker::default(),
+        }
+    }
 }
 
 #[tokio::main]
 pub async fn main() {
-    println!("Starting Up...");
+    eprintln!("Starting Up...");
 
     // First, parse the .env file for our environment setup.
     dotenvy::dotenv().ok();
+    let config = Config::from_env().unwrap_or_else(|err| panic!("Invalid configuration. {err}"));
 
     // We create a single connection pool for SQLx that's shared across the whole application.
     // This saves us from opening a new connection for every API call, which is wasteful.
@@ -40,7 +96,7 @@ pub async fn main() {
         //
         // If you're deploying your application with multiple replicas, then the total
         // across all replicas should not exceed the Postgres connection limit.
-        .max_connections(10)
+        .max_connections(config.max_db_connections)
         .connect(&database_url)
         .await
         .unwrap_or_else(|err| panic!("Could not connect to dabase_url. Error: \n{}", err));
@@ -51,31 +107,168 @@ pub async fn main() {
         .await
         .unwrap_or_else(|err| panic!("Failed to migrate the database. Error: \n{}", err));
 
+    let tasks = TaskTracker::default();
+    let metrics = Arc::new(Metrics::default());
     tracing_subscriber::registry()
         .with(
             EnvFilter::from_default_env()
                 // this directive prevent sqlx from infinitely logggin its own events.
                 .add_directive("lls".parse().unwrap()),
         )
-        .with(PostgresLayer::from(pool.clone()))
+        .with(PostgresLayer::new(
+            pool.clone(),
+            tasks.clone(),
+            metrics.clone(),
+            &config,
+        ))
+        .with(config.stdout_logs.then_some(StdoutLayer))
         .init();
 
-    let router = Router::new()
+    let state = AppState {
+        db: pool,
+        config: Arc::new(config),
+        metrics,
+        active_sessions: Arc::new(Mutex::new(HashSet::new())),
+        live_events: LiveEvents::default(),
+        tasks,
+    };
+
+    let viewer_routes = Router::new()
         .route("/", get(html::session_search::get_sessions))
-        .route("/ws", any(language_logging::handle_ws))
         .route("/session", get(html::get_session))
-        // FUTURE: handle regular POST requests. Need to create an API to retrieve a session ID first.
-        // .route("/log", post(handle_log))
-        .with_state(AppState { db: pool })
+        .route("/session.json", get(api::conversation::get_session_json))
+        .route("/session/diff", get(html::diff::get_message_diff))
+        .route("/session/export", get(api::export::export_session))
+        .route("/session/stream", get(api::stream::get_session_stream))
+        .route("/stats", get(html::latency_report::get_latency_report))
+        .route("/errors", get(html::error_report::get_error_report))
+        .route("/session/stats", get(html::stats::get_session_stats))
+        .route(
+            "/session/timeline",
+            get(html::timeline::get_session_timeline),
+        )
+        .route(
+            "/session/summary",
+            get(html::method_summary::get_method_summary),
+        )
+        .route("/session/star", post(html::star::post_session_star))
+        .route(
+            "/session/{id}/latencies.csv",
+            get(api::latencies::get_session_latencies_csv),
+        )
+        .route("/session/logs", get(html::logs::get_session_logs))
+        .route("/session/logs/entry", get(html::logs::get_log))
+        .route(
+            "/api/session/{id}/summary",
+            get(api::summary::get_session_summary),
+        )
+        .route(
+            "/api/session/{id}/messages",
+            get(api::messages::get_messages),
+        )
+        .route(
+            "/api/session/{id}/messages/{seq}",
+            get(api::message::get_message_json),
+        )
+        .route(
+            "/api/session/{id}/messages/{seq}/replay",
+            get(api::message::get_message_replay),
+        )
+        .route("/api/session/{id}/at", get(api::at::get_messages_at))
+        .route(
+            "/api/session/{id}/document",
+            get(api::document::get_document),
+        )
+        .route(
+            "/api/session/{id}/test_case",
+            get(api::test_case::get_session_test_case),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_viewer_token,
+        ));
+
+    let ingest_auth = middleware::from_fn_with_state(state.clone(), auth::require_ingest_token);
+    let ingest_routes = Router::new()
+        .route("/ws", any(ingest::handle_ws))
+        .route("/log", post(ingest::handle_log))
+        .route_layer(ingest_auth.clone());
+
+    let router = Router::new()
+        .merge(viewer_routes)
+        .merge(ingest_routes)
+        .route("/health", get(api::health::get_health))
+        .route("/metrics", get(api::metrics::get_metrics))
+        .route("/static/chat.css", get(html::stylesheet::get_chat_css))
+        .route(
+            "/static/sessions.css",
+            get(html::stylesheet::get_sessions_css),
+        )
+        // Deleting sessions checks the admin token itself.
+        .route(
+            "/sessions",
+            post(ingest::handle_create_session)
+                .route_layer(ingest_auth)
+                .delete(api::cleanup::delete_sessions),
+        )
+        // Replaying runs a command, so it checks the admin token itself.
+        .route("/session/replay", post(api::replay::replay_session))
+        // Compacting can't be undone, so it checks the admin token itself.
+        .route(
+            "/api/session/{id}/compact",
+            post(api::compact::compact_session),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            error::render_errors,
+        ))
+        .with_state(state.clone())
         .into_make_service();
 
-    let port: u16 = 8080;
-    let tcp_listener = TcpListener::bind(&format!("[::]:{port}"))
+    if let Some(uds_path) = &state.config.uds_path {
+        // A socket left behind by a previous run would otherwise make the bind fail.
+        if std::fs::metadata(uds_path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+            std::fs::remove_file(uds_path).ok();
+        }
+
+        let uds_listener = UnixListener::bind(uds_path)
+            .unwrap_or_else(|err| panic!("failed to bind to {}. Error: {err}", uds_path.display()));
+        eprintln!("Listening on: {}", uds_path.display());
+        tokio::spawn(ingest::serve_uds(uds_listener, state.clone()));
+    }
+
+    tokio::spawn(sweep::sweep_idle_sessions_periodically(state.clone()));
+    tokio::spawn(retention::enforce_retention_periodically(state.clone()));
+
+    let address = SocketAddr::new(state.config.bind_addr, state.config.port);
+    let tcp_listener = TcpListener::bind(address)
         .await
-        .expect(&format!("failed to bind to [::]:{port}"));
-    println!("Listening on: [::]:{port}");
+        .unwrap_or_else(|err| panic!("failed to bind to {address}. Error: {err}"));
+    eprintln!("Listening on: {address}");
 
     axum::serve(tcp_listener, router)
+        .with_graceful_shutdown(shutdown_signal())
         .await
         .expect("failed to start service");
+
+    ingest::end_active_sessions(&state).await;
+    // Logs written while the server was stopping may still be being inserted.
+    eprintln!(
+        "Waiting for {} background writes to finish...",
+        state.tasks.running()
+    );
+    state.tasks.wait().await;
+    eprintln!("Shut down");
+}
+
+/// Resolves once the process is asked to stop, either by ctrl-c or by the `SIGTERM` that
+/// orchestrators send before killing it.
+async fn shutdown_signal() {
+    let mut terminate =
+        signal(SignalKind::terminate()).expect("failed to listen for the terminate signal");
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result.expect("failed to listen for ctrl-c"),
+        _ = terminate.recv() => {}
+    }
+    eprintln!("Shutting down...");
 }
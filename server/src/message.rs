@@ -1,8 +1,13 @@
-use std::{collections::HashMap, vec::IntoIter};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    vec::IntoIter,
+};
 
-use lsp_server::{Message, Request, RequestId};
+use lsp_server::{Message, Notification as LspNotification, Request, RequestId};
 use lsp_types::{
-    CancelParams, NumberOrString, ProgressParams, WorkDoneProgressParams,
+    CancelParams, ClientInfo, DidChangeTextDocumentParams, DidOpenTextDocumentParams,
+    NumberOrString, PositionEncodingKind, ProgressParams, PublishDiagnosticsParams, ServerInfo,
+    WorkDoneProgressParams,
     notification::{
         Cancel, DidChangeConfiguration, DidChangeNotebookDocument, DidChangeTextDocument,
         DidChangeWatchedFiles, DidChangeWorkspaceFolders, DidCloseNotebookDocument,
@@ -31,13 +36,23 @@ use lsp_types::{
         WorkspaceSymbolRequest, WorkspaceSymbolResolve,
     },
 };
+use serde_json::Value;
+use time::{Duration, OffsetDateTime};
 
 use crate::session::{MessageSource, MessageWithTimeStamp};
 
+#[derive(Default)]
+pub(crate) struct UnmatchedMessages<'a> {
+    pub(crate) unanswered_requests: Vec<&'a MessageWithTimeStamp>,
+    pub(crate) unknown_responses: Vec<&'a MessageWithTimeStamp>,
+}
+
+#[derive(Default)]
 pub(crate) struct Conversation {
     messages: Vec<MessageWithTimeStamp>,
     requests: HashMap<RequestId, Request>,
-    progress_tokens: HashMap<NumberOrString, Request>,
+    /// The position in `messages` of the request that passed each `workDoneToken`.
+    progress_tokens: HashMap<NumberOrString, usize>,
 }
 
 impl Conversation {
@@ -48,6 +63,481 @@ impl Conversation {
     pub(crate) fn requests(&self) -> &HashMap<RequestId, Request> {
         &self.requests
     }
+
+    /// Looks up a request by an id taken from a message's params.
+    pub(crate) fn find_request(&self, id: &NumberOrString) -> Option<&Request> {
+        let id = match id {
+            NumberOrString::Number(num) => RequestId::from(*num),
+            NumberOrString::String(str) => RequestId::from(str.clone()),
+        };
+        self.requests.get(&id)
+    }
+
+    /// Resolves the request that a `$/cancelRequest` notification targets.
+    pub(crate) fn get_cancelled_request(&self, cancel: &LspNotification) -> Option<&Request> {
+        serde_json::from_value::<CancelParams>(cancel.params.clone())
+            .ok()
+            .and_then(|cancel_params| self.find_request(&cancel_params.id))
+    }
+
+    /// Resolves the request that started the work a `$/progress` notification reports on, by the
+    /// `workDoneToken` it passed. Tokens the server created itself have no such request.
+    pub(crate) fn get_progress_request(
+        &self,
+        progress: &LspNotification,
+    ) -> Option<&MessageWithTimeStamp> {
+        serde_json::from_value::<ProgressParams>(progress.params.clone())
+            .ok()
+            .and_then(|progress_params| self.progress_tokens.get(&progress_params.token))
+            .map(|index| &self.messages[*index])
+    }
+
+    /// The seqs of the messages that make up the operation of the request with this id: the
+    /// request, its response, the `$/progress` notifications reporting on its work, and any
+    /// `$/cancelRequest` that targets it.
+    pub(crate) fn get_request_lifecycle(&self, id: &RequestId) -> HashSet<i64> {
+        self.messages
+            .iter()
+            .filter(|message| match &message.message {
+                Message::Request(request) => request.id == *id,
+                Message::Response(response) => response.id == *id,
+                Message::Notification(notification) => match notification.method.as_str() {
+                    Cancel::METHOD => self
+                        .get_cancelled_request(notification)
+                        .is_some_and(|request| request.id == *id),
+                    Progress::METHOD => self
+                        .get_progress_request(notification)
+                        .is_some_and(|request| {
+                            matches!(&request.message, Message::Request(request) if request.id == *id)
+                        }),
+                    _ => false,
+                },
+            })
+            .map(|message| message.seq)
+            .collect()
+    }
+
+    fn get_initialize_request(&self) -> Option<&Request> {
+        self.messages
+            .iter()
+            .find_map(|message| match &message.message {
+                Message::Request(request) if request.method == Initialize::METHOD => Some(request),
+                _ => None,
+            })
+    }
+
+    /// The `clientInfo` the client sent in its `initialize` request, if any.
+    pub(crate) fn get_client_info(&self) -> Option<ClientInfo> {
+        self.get_initialize_request()
+            .and_then(|request| request.params.get("clientInfo"))
+            .and_then(|client_info| serde_json::from_value(client_info.clone()).ok())
+    }
+
+    /// The `serverInfo` the server sent in its response to `initialize`, if any.
+    pub(crate) fn get_server_info(&self) -> Option<ServerInfo> {
+        let initialize_id = &self.get_initialize_request()?.id;
+        self.messages
+            .iter()
+            .find_map(|message| match &message.message {
+                Message::Response(response) if &response.id == initialize_id => {
+                    response.result.as_ref()
+                }
+                _ => None,
+            })
+            .and_then(|result| result.get("serverInfo"))
+            .and_then(|server_info| serde_json::from_value(server_info.clone()).ok())
+    }
+
+    /// The `capabilities` the server sent in its response to `initialize`, if it was logged.
+    pub(crate) fn get_server_capabilities(&self) -> Option<&Value> {
+        let initialize_id = &self.get_initialize_request()?.id;
+        self.messages
+            .iter()
+            .find_map(|message| match &message.message {
+                Message::Response(response) if &response.id == initialize_id => {
+                    response.result.as_ref()
+                }
+                _ => None,
+            })
+            .and_then(|result| result.get("capabilities"))
+    }
+
+    /// The position encoding the server picked in its response to `initialize`. The spec
+    /// defaults to UTF-16 when none was negotiated.
+    pub(crate) fn get_position_encoding(&self) -> PositionEncodingKind {
+        self.get_server_capabilities()
+            .and_then(|capabilities| capabilities.get("positionEncoding"))
+            .and_then(|encoding| serde_json::from_value(encoding.clone()).ok())
+            .unwrap_or(PositionEncodingKind::UTF16)
+    }
+
+    /// Pairs every request in the conversation with the first response sent for it, in the
+    /// order the requests were made. Requests that never received a response are included
+    /// with a `response` of `None`, and any responses after the first are kept as duplicates.
+    pub(crate) fn pair_requests_with_responses(&self) -> Vec<RequestResponsePair<'_>> {
+        let mut pairs = Vec::new();
+        let mut pair_indexes = HashMap::new();
+
+        for message in self.messages.iter() {
+            match &message.message {
+                Message::Request(request) => {
+                    pair_indexes.insert(&request.id, pairs.len());
+                    pairs.push(RequestResponsePair {
+                        request: message,
+                        response: None,
+                        duplicate_responses: Vec::new(),
+                    });
+                }
+                Message::Response(response) => {
+                    if let Some(index) = pair_indexes.get(&response.id) {
+                        let pair: &mut RequestResponsePair = &mut pairs[*index];
+                        if pair.response.is_none() {
+                            pair.response = Some(message);
+                        } else {
+                            pair.duplicate_responses.push(message);
+                        }
+                    }
+                }
+                Message::Notification(_) => {}
+            }
+        }
+
+        pairs
+    }
+
+    /// The `seq` of every response that answered a request which had already been answered.
+    pub(crate) fn get_duplicate_responses(&self) -> HashSet<i64> {
+        self.pair_requests_with_responses()
+            .iter()
+            .flat_map(|pair| pair.duplicate_responses.iter())
+            .map(|response| response.seq)
+            .collect()
+    }
+
+    /// The requests that no response answered, and the responses that don't answer any request
+    /// in the conversation, each in the order they were logged.
+    pub(crate) fn get_unmatched_messages(&self) -> UnmatchedMessages<'_> {
+        let answered_ids = self
+            .messages
+            .iter()
+            .filter_map(|message| match &message.message {
+                Message::Response(response) => Some(&response.id),
+                Message::Request(_) | Message::Notification(_) => None,
+            })
+            .collect::<HashSet<_>>();
+
+        let mut unmatched = UnmatchedMessages::default();
+        for message in self.messages.iter() {
+            match &message.message {
+                Message::Request(request) if !answered_ids.contains(&request.id) => {
+                    unmatched.unanswered_requests.push(message);
+                }
+                Message::Response(response) if !self.requests.contains_key(&response.id) => {
+                    unmatched.unknown_responses.push(message);
+                }
+                _ => {}
+            }
+        }
+        unmatched
+    }
+
+    /// The `count` answered requests with the longest latency, slowest first.
+    pub(crate) fn get_slowest_requests(&self, count: usize) -> Vec<RequestResponsePair<'_>> {
+        let mut pairs = self
+            .pair_requests_with_responses()
+            .into_iter()
+            .filter(|pair| pair.response.is_some())
+            .collect::<Vec<_>>();
+        pairs.sort_by_key(|pair| std::cmp::Reverse(pair.latency()));
+        pairs.truncate(count);
+        pairs
+    }
+
+    /// Maps each cancelled request's id to the first `$/cancelRequest` notification that
+    /// targeted it. Cancellations for ids that don't match any request are returned separately.
+    pub(crate) fn get_cancellations(
+        &self,
+    ) -> (
+        HashMap<RequestId, &MessageWithTimeStamp>,
+        Vec<&MessageWithTimeStamp>,
+    ) {
+        let mut cancellations = HashMap::new();
+        let mut unknown_cancellations = Vec::new();
+
+        for message in self.messages.iter() {
+            let Message::Notification(notification) = &message.message else {
+                continue;
+            };
+
+            if notification.method != Cancel::METHOD {
+                continue;
+            }
+
+            match self.get_cancelled_request(notification) {
+                Some(request) => {
+                    cancellations.entry(request.id.clone()).or_insert(message);
+                }
+                None => unknown_cancellations.push(message),
+            }
+        }
+
+        (cancellations, unknown_cancellations)
+    }
+
+    /// Measures how long after each cancelled request its `$/cancelRequest` arrived, and
+    /// whether the server still responded after the cancellation.
+    pub(crate) fn analyze_cancellations(&self) -> CancellationAnalysis<'_> {
+        let (cancellations, unknown_cancellations) = self.get_cancellations();
+
+        let cancelled = self
+            .pair_requests_with_responses()
+            .into_iter()
+            .filter_map(|pair| {
+                let Message::Request(request) = &pair.request.message else {
+                    return None;
+                };
+
+                cancellations
+                    .get(&request.id)
+                    .map(|cancel| CancelledRequest {
+                        request: pair.request,
+                        cancel,
+                        response: pair.response,
+                    })
+            })
+            .collect();
+
+        CancellationAnalysis {
+            cancelled,
+            unknown_cancellations,
+        }
+    }
+
+    /// Links each `textDocument/publishDiagnostics` notification, keyed by its `seq`, to the
+    /// most recent `didChange` (or `didOpen`) that preceded it for the same document.
+    /// Diagnostics published before the document was opened or edited have no entry.
+    pub(crate) fn correlate_diagnostics_with_edits(&self) -> HashMap<i64, DiagnosticTrigger<'_>> {
+        let mut latest_triggers = HashMap::new();
+        let mut correlations = HashMap::new();
+
+        for message in self.messages.iter() {
+            let Message::Notification(notification) = &message.message else {
+                continue;
+            };
+
+            match notification.method.as_str() {
+                DidOpenTextDocument::METHOD => {
+                    if let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(
+                        notification.params.clone(),
+                    ) {
+                        latest_triggers.insert(
+                            params.text_document.uri.to_string(),
+                            DiagnosticTrigger::Open(message),
+                        );
+                    }
+                }
+                DidChangeTextDocument::METHOD => {
+                    if let Ok(params) = serde_json::from_value::<DidChangeTextDocumentParams>(
+                        notification.params.clone(),
+                    ) {
+                        latest_triggers.insert(
+                            params.text_document.uri.to_string(),
+                            DiagnosticTrigger::Edit(message),
+                        );
+                    }
+                }
+                PublishDiagnostics::METHOD => {
+                    if let Ok(params) = serde_json::from_value::<PublishDiagnosticsParams>(
+                        notification.params.clone(),
+                    ) && let Some(trigger) = latest_triggers.get(params.uri.as_str())
+                    {
+                        correlations.insert(message.seq, *trigger);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        correlations
+    }
+
+    /// The number of diagnostics in each `textDocument/publishDiagnostics` notification, grouped
+    /// by document in the order they were published.
+    pub(crate) fn get_diagnostic_counts_by_document(&self) -> BTreeMap<String, Vec<usize>> {
+        let mut counts = BTreeMap::<String, Vec<usize>>::new();
+        for message in self.messages.iter() {
+            if let Message::Notification(notification) = &message.message
+                && notification.method == PublishDiagnostics::METHOD
+                && let Ok(params) =
+                    serde_json::from_value::<PublishDiagnosticsParams>(notification.params.clone())
+            {
+                counts
+                    .entry(params.uri.to_string())
+                    .or_default()
+                    .push(params.diagnostics.len());
+            }
+        }
+        counts
+    }
+
+    /// Splits the session into lifecycle phases at the first `initialize`, `initialized`,
+    /// `shutdown` and `exit` messages. Phases are contiguous from `start` until `end` (or the
+    /// last message when the session is still open). Missing lifecycle messages just mean the
+    /// session never reached the later phases.
+    pub(crate) fn get_protocol_phases(
+        &self,
+        start: OffsetDateTime,
+        end: Option<OffsetDateTime>,
+    ) -> Vec<ProtocolPhaseSpan> {
+        // The lifecycle methods are unique, so matching on the method name alone is enough.
+        let first_time_stamp_of = |method: &str| {
+            self.messages
+                .iter()
+                .find(|message| match &message.message {
+                    Message::Request(request) => request.method == method,
+                    Message::Notification(notification) => notification.method == method,
+                    Message::Response(_) => false,
+                })
+                .map(|message| message.time_stamp)
+        };
+
+        let transitions = [
+            (
+                ProtocolPhase::Initializing,
+                first_time_stamp_of(Initialize::METHOD),
+            ),
+            (
+                ProtocolPhase::Active,
+                first_time_stamp_of(Initialized::METHOD),
+            ),
+            (
+                ProtocolPhase::ShuttingDown,
+                first_time_stamp_of(Shutdown::METHOD),
+            ),
+            (ProtocolPhase::Exited, first_time_stamp_of(Exit::METHOD)),
+        ];
+
+        let end = end
+            .or_else(|| self.messages.last().map(|message| message.time_stamp))
+            .unwrap_or(start)
+            .max(start);
+
+        let mut phases = Vec::new();
+        let mut current = ProtocolPhaseSpan {
+            phase: ProtocolPhase::PreInitialize,
+            start,
+            end,
+        };
+        for (phase, time_stamp) in transitions {
+            // Out of order lifecycle messages don't move the session backwards.
+            let Some(time_stamp) = time_stamp.filter(|time_stamp| *time_stamp >= current.start)
+            else {
+                continue;
+            };
+
+            current.end = time_stamp;
+            phases.push(current);
+            current = ProtocolPhaseSpan {
+                phase,
+                start: time_stamp,
+                end,
+            };
+        }
+        phases.push(current);
+
+        phases
+    }
+}
+
+/// The stages of the LSP lifecycle a session moves through.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProtocolPhase {
+    PreInitialize,
+    Initializing,
+    Active,
+    ShuttingDown,
+    Exited,
+}
+
+impl ProtocolPhase {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            ProtocolPhase::PreInitialize => "pre-initialize",
+            ProtocolPhase::Initializing => "initializing",
+            ProtocolPhase::Active => "active",
+            ProtocolPhase::ShuttingDown => "shutting down",
+            ProtocolPhase::Exited => "exited",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ProtocolPhaseSpan {
+    pub(crate) phase: ProtocolPhase,
+    pub(crate) start: OffsetDateTime,
+    pub(crate) end: OffsetDateTime,
+}
+
+impl ProtocolPhaseSpan {
+    pub(crate) fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// The document notification that most recently preceded a set of published diagnostics.
+#[derive(Clone, Copy)]
+pub(crate) enum DiagnosticTrigger<'a> {
+    Open(&'a MessageWithTimeStamp),
+    Edit(&'a MessageWithTimeStamp),
+}
+
+pub(crate) struct RequestResponsePair<'a> {
+    pub(crate) request: &'a MessageWithTimeStamp,
+    pub(crate) response: Option<&'a MessageWithTimeStamp>,
+    /// Responses received after the first one. The spec allows exactly one response per
+    /// request, so any of these indicate a bug in the responder.
+    pub(crate) duplicate_responses: Vec<&'a MessageWithTimeStamp>,
+}
+
+impl RequestResponsePair<'_> {
+    /// The round trip time between the request and its response being received.
+    pub(crate) fn latency(&self) -> Option<Duration> {
+        self.response
+            .map(|response| response.time_stamp - self.request.time_stamp)
+    }
+
+    /// The time the responder spent on the request, from when the request was delivered to it
+    /// until it sent the response. Only known when a proxying collector reported both times,
+    /// since it excludes any time the requester spent before sending.
+    pub(crate) fn think_time(&self) -> Option<Duration> {
+        let delivered = self.request.proxy_time_stamps.delivered_time_stamp?;
+        let sent = self.response?.proxy_time_stamps.sent_time_stamp?;
+        Some(sent - delivered)
+    }
+}
+
+pub(crate) struct CancelledRequest<'a> {
+    pub(crate) request: &'a MessageWithTimeStamp,
+    pub(crate) cancel: &'a MessageWithTimeStamp,
+    pub(crate) response: Option<&'a MessageWithTimeStamp>,
+}
+
+impl CancelledRequest<'_> {
+    /// The time between the request being sent and the client cancelling it.
+    pub(crate) fn cancel_latency(&self) -> Duration {
+        self.cancel.time_stamp - self.request.time_stamp
+    }
+
+    /// Whether a response still arrived after the request was cancelled.
+    pub(crate) fn has_late_response(&self) -> bool {
+        self.response
+            .is_some_and(|response| response.time_stamp >= self.cancel.time_stamp)
+    }
+}
+
+pub(crate) struct CancellationAnalysis<'a> {
+    pub(crate) cancelled: Vec<CancelledRequest<'a>>,
+    pub(crate) unknown_cancellations: Vec<&'a MessageWithTimeStamp>,
 }
 
 impl From<Vec<MessageWithTimeStamp>> for Conversation {
@@ -55,7 +545,7 @@ impl From<Vec<MessageWithTimeStamp>> for Conversation {
         let mut requests = HashMap::new();
         let mut progress_tokens = HashMap::new();
 
-        for msg in value.iter() {
+        for (index, msg) in value.iter().enumerate() {
             match &msg.message {
                 Message::Request(request) => {
                     requests.insert(request.id.clone(), request.clone());
@@ -102,10 +592,9 @@ impl From<Vec<MessageWithTimeStamp>> for Conversation {
                         | ExecuteCommand::METHOD => {
                             if let Ok(params) = serde_json::from_value::<WorkDoneProgressParams>(
                                 request.params.clone(),
-                            ) {
-                                if let Some(token) = params.work_done_token {
-                                    progress_tokens.insert(token, request.clone());
-                                }
+                            ) && let Some(token) = params.work_done_token
+                            {
+                                progress_tokens.insert(token, index);
                             }
                         }
                         _ => {}
@@ -138,7 +627,7 @@ impl<'a> IntoIterator for &'a Conversation {
     type Item = &'a MessageWithTimeStamp;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&self.messages).into_iter()
+        self.messages.iter()
     }
 }
 
@@ -147,7 +636,7 @@ impl<'a> IntoIterator for &'a mut Conversation {
     type Item = &'a mut MessageWithTimeStamp;
 
     fn into_iter(self) -> Self::IntoIter {
-        (&mut self.messages).into_iter()
+        self.messages.iter_mut()
     }
 }
 
@@ -157,56 +646,25 @@ pub(crate) fn get_source(
 ) -> Option<MessageSource> {
     match message {
         Message::Request(request) => get_request_source(request),
-        Message::Response(response) => containing_conversation
-            .requests
-            .get(&response.id)
-            .map(|source| {
-                get_request_source(source)
-                    .as_ref()
-                    .map(MessageSource::other)
-            })
-            .flatten(),
-        Message::Notification(notification) => match notification.method.as_str() {
-            Cancel::METHOD => serde_json::from_value::<CancelParams>(notification.params.clone())
-                .ok()
-                .map(|cancel_params| match cancel_params.id {
-                    NumberOrString::Number(num) => containing_conversation
-                        .requests
-                        .get(&RequestId::from(num))
-                        .or_else(|| {
-                            containing_conversation
-                                .requests
-                                .get(&RequestId::from(num.to_string()))
-                        }),
-                    NumberOrString::String(str) => containing_conversation
-                        .requests
-                        .get(&RequestId::from(str.clone()))
-                        .or_else(|| {
-                            str.parse::<i32>()
-                                .ok()
-                                .map(|id| {
-                                    containing_conversation.requests.get(&RequestId::from(id))
-                                })
-                                .flatten()
-                        }),
+        Message::Response(response) => {
+            containing_conversation
+                .requests
+                .get(&response.id)
+                .and_then(|source| {
+                    get_request_source(source)
+                        .as_ref()
+                        .map(MessageSource::other)
                 })
-                .flatten()
-                .map(get_request_source)
-                .flatten(),
-            Progress::METHOD => {
-                serde_json::from_value::<ProgressParams>(notification.params.clone())
-                    .ok()
-                    .map(|progress_params| {
-                        containing_conversation
-                            .progress_tokens
-                            .get(&progress_params.token)
-                            .map(get_request_source)
-                            .flatten()
-                            .as_ref()
-                            .map(MessageSource::other)
-                    })
-                    .flatten()
-            }
+        }
+        Message::Notification(notification) => match notification.method.as_str() {
+            Cancel::METHOD => containing_conversation
+                .get_cancelled_request(notification)
+                .and_then(get_request_source),
+            Progress::METHOD => containing_conversation
+                .get_progress_request(notification)
+                .and_then(|request| request.get_source(containing_conversation))
+                .as_ref()
+                .map(MessageSource::other),
             SetTrace::METHOD => Some(MessageSource::Client),
             LogTrace::METHOD => Some(MessageSource::Server),
             Initialized::METHOD => Some(MessageSource::Client),
@@ -311,52 +769,19 @@ pub fn classify(message: &Message, containing_conversation: &Conversation) -> Op
         Message::Response(response) => containing_conversation
             .requests
             .get(&response.id)
-            .map(classify_request)
-            .flatten(),
+            .and_then(classify_request),
         Message::Notification(notification) => {
             match notification.method.as_str() {
-                Cancel::METHOD => {
-                    serde_json::from_value::<CancelParams>(notification.params.clone())
-                        .ok()
-                        .map(|cancel_params| match cancel_params.id {
-                            NumberOrString::Number(num) => containing_conversation
-                                .requests
-                                .get(&RequestId::from(num))
-                                .or_else(|| {
-                                    containing_conversation
-                                        .requests
-                                        .get(&RequestId::from(num.to_string()))
-                                }),
-                            NumberOrString::String(str) => containing_conversation
-                                .requests
-                                .get(&RequestId::from(str.clone()))
-                                .or_else(|| {
-                                    str.parse::<i32>()
-                                        .ok()
-                                        .map(|id| {
-                                            containing_conversation
-                                                .requests
-                                                .get(&RequestId::from(id))
-                                        })
-                                        .flatten()
-                                }),
-                        })
-                        .flatten()
-                        .map(classify_request)
-                        .flatten()
-                }
-                Progress::METHOD => {
-                    serde_json::from_value::<ProgressParams>(notification.params.clone())
-                        .ok()
-                        .map(|progress_params| {
-                            containing_conversation
-                                .progress_tokens
-                                .get(&progress_params.token)
-                                .map(classify_request)
-                        })
-                        .flatten()
-                        .flatten()
-                }
+                // Cancellations and progress are filed with the request they're for, when it
+                // can be found.
+                Cancel::METHOD => containing_conversation
+                    .get_cancelled_request(notification)
+                    .and_then(classify_request)
+                    .or(Some(MessageKind::Custom)),
+                Progress::METHOD => containing_conversation
+                    .get_progress_request(notification)
+                    .and_then(|request| classify(&request.message, containing_conversation))
+                    .or(Some(MessageKind::Custom)),
                 SetTrace::METHOD => Some(MessageKind::Lifecycle),
                 LogTrace::METHOD => Some(MessageKind::Lifecycle),
                 Initialized::METHOD => Some(MessageKind::Lifecycle),
@@ -385,6 +810,7 @@ pub fn classify(message: &Message, containing_conversation: &Conversation) -> Op
                 | LogMessage::METHOD
                 | WorkDoneProgressCancel::METHOD => Some(MessageKind::Workspace),
                 TelemetryEvent::METHOD => Some(MessageKind::Telemetry),
+                method if is_custom_method(method) => Some(MessageKind::Custom),
                 _ => None,
             }
         }
@@ -457,12 +883,169 @@ fn classify_request(request: &Request) -> Option<MessageKind> {
         }
         ApplyWorkspaceEdit::METHOD => Some(MessageKind::Workspace),
         WorkDoneProgressCreate::METHOD => Some(MessageKind::Lifecycle),
+        method if is_custom_method(method) => Some(MessageKind::Custom),
         _ => None,
     }
 }
 
+/// Methods starting with `$/` are protocol or implementation dependent, and may be ignored by
+/// the receiver.
+fn is_custom_method(method: &str) -> bool {
+    method.starts_with("$/")
+}
+
+/// A one-line description of what a standard LSP method does, for display alongside it.
+pub(crate) fn get_method_description(method: &str) -> Option<&'static str> {
+    let description = match method {
+        Initialize::METHOD => {
+            "The first request from the client, exchanging capabilities with the server."
+        }
+        Initialized::METHOD => {
+            "Sent by the client once it has received the initialize result, before any other request."
+        }
+        Shutdown::METHOD => {
+            "Asks the server to shut down without exiting, so the client can still read its response."
+        }
+        Exit::METHOD => "Asks the server to exit its process.",
+        RegisterCapability::METHOD => "Asks the client to dynamically register for a capability.",
+        UnregisterCapability::METHOD => {
+            "Asks the client to unregister a previously registered capability."
+        }
+        SetTrace::METHOD => "Changes the server's trace setting.",
+        LogTrace::METHOD => "Logs a trace of the server's execution, as configured by $/setTrace.",
+        Cancel::METHOD => "Cancels a request that is still in flight.",
+        Progress::METHOD => {
+            "Reports progress on a long running operation against a progress token."
+        }
+        WorkDoneProgressCreate::METHOD => {
+            "Asks the client to create a work done progress indicator."
+        }
+        WorkDoneProgressCancel::METHOD => {
+            "Tells the server the user cancelled a work done progress."
+        }
+        DidOpenTextDocument::METHOD => {
+            "Tells the server a document was opened, handing it the document's contents."
+        }
+        DidChangeTextDocument::METHOD => "Tells the server a document's contents changed.",
+        WillSaveTextDocument::METHOD => "Tells the server a document is about to be saved.",
+        WillSaveWaitUntil::METHOD => {
+            "Asks the server for edits to apply to a document before it is saved."
+        }
+        DidSaveTextDocument::METHOD => "Tells the server a document was saved.",
+        DidCloseTextDocument::METHOD => {
+            "Tells the server a document was closed, so the file on disk is its source of truth again."
+        }
+        DidOpenNotebookDocument::METHOD => "Tells the server a notebook document was opened.",
+        DidChangeNotebookDocument::METHOD => "Tells the server a notebook document changed.",
+        DidSaveNotebookDocument::METHOD => "Tells the server a notebook document was saved.",
+        DidCloseNotebookDocument::METHOD => "Tells the server a notebook document was closed.",
+        DidChangeConfiguration::METHOD => {
+            "Tells the server the client's configuration settings changed."
+        }
+        DidChangeWatchedFiles::METHOD => "Tells the server that files it watches changed on disk.",
+        DidChangeWorkspaceFolders::METHOD => {
+            "Tells the server workspace folders were added or removed."
+        }
+        DidCreateFiles::METHOD => "Tells the server files were created from within the client.",
+        DidDeleteFiles::METHOD => "Tells the server files were deleted from within the client.",
+        WillCreateFiles::METHOD => "Asks the server for edits to apply before files are created.",
+        WillRenameFiles::METHOD => "Asks the server for edits to apply before files are renamed.",
+        WorkspaceConfiguration::METHOD => "Asks the client for configuration settings.",
+        WorkspaceFoldersRequest::METHOD => "Asks the client for its current workspace folders.",
+        ApplyWorkspaceEdit::METHOD => "Asks the client to apply an edit across the workspace.",
+        ExecuteCommand::METHOD => {
+            "Asks the server to run a command, typically one offered by a code action or code lens."
+        }
+        ShowMessage::METHOD => "Asks the client to show a message to the user.",
+        ShowMessageRequest::METHOD => {
+            "Asks the client to show a message to the user with actions to choose from."
+        }
+        ShowDocument::METHOD => "Asks the client to show a document or URI.",
+        LogMessage::METHOD => "Asks the client to log a message.",
+        TelemetryEvent::METHOD => "Asks the client to log a telemetry event.",
+        PublishDiagnostics::METHOD => {
+            "Pushes the server's current diagnostics for a document to the client."
+        }
+        DocumentDiagnosticRequest::METHOD => {
+            "Pulls the diagnostics for a document from the server."
+        }
+        WorkspaceDiagnosticRequest::METHOD => {
+            "Pulls the diagnostics for the whole workspace from the server."
+        }
+        WorkspaceDiagnosticRefresh::METHOD => "Asks the client to pull diagnostics again.",
+        GotoDeclaration::METHOD => "Finds the declaration of the symbol at a position.",
+        GotoDefinition::METHOD => "Finds the definition of the symbol at a position.",
+        GotoTypeDefinition::METHOD => {
+            "Finds the definition of the type of the symbol at a position."
+        }
+        GotoImplementation::METHOD => "Finds the implementations of the symbol at a position.",
+        References::METHOD => "Finds every reference to the symbol at a position.",
+        CallHierarchyPrepare::METHOD => "Resolves the call hierarchy item at a position.",
+        CallHierarchyIncomingCalls::METHOD => "Finds the callers of a call hierarchy item.",
+        CallHierarchyOutgoingCalls::METHOD => "Finds the calls made by a call hierarchy item.",
+        TypeHierarchyPrepare::METHOD => "Resolves the type hierarchy item at a position.",
+        TypeHierarchySupertypes::METHOD => "Finds the supertypes of a type hierarchy item.",
+        TypeHierarchySubtypes::METHOD => "Finds the subtypes of a type hierarchy item.",
+        DocumentHighlightRequest::METHOD => {
+            "Finds the ranges in a document to highlight for the symbol at a position."
+        }
+        DocumentLinkRequest::METHOD => "Finds the links in a document.",
+        DocumentLinkResolve::METHOD => "Resolves the target of a document link.",
+        HoverRequest::METHOD => {
+            "Requests hover information (types, docs) for the symbol at a position."
+        }
+        CodeLensRequest::METHOD => "Finds the code lenses to show in a document.",
+        CodeLensResolve::METHOD => "Resolves the command of a code lens.",
+        CodeLensRefresh::METHOD => "Asks the client to request code lenses again.",
+        FoldingRangeRequest::METHOD => "Finds the ranges in a document that can be folded.",
+        SelectionRangeRequest::METHOD => {
+            "Finds the ranges to expand a selection through at positions."
+        }
+        DocumentSymbolRequest::METHOD => "Lists the symbols defined in a document.",
+        WorkspaceSymbolRequest::METHOD => "Searches for symbols across the workspace.",
+        WorkspaceSymbolResolve::METHOD => "Resolves the location of a workspace symbol.",
+        SemanticTokensFullRequest::METHOD => {
+            "Requests semantic highlighting tokens for a whole document."
+        }
+        SemanticTokensFullDeltaRequest::METHOD => {
+            "Requests the changes to a document's semantic tokens since the last result."
+        }
+        SemanticTokensRangeRequest::METHOD => {
+            "Requests semantic highlighting tokens for a range of a document."
+        }
+        SemanticTokensRefresh::METHOD => "Asks the client to request semantic tokens again.",
+        InlayHintRequest::METHOD => "Requests the inlay hints to show in a range of a document.",
+        InlayHintResolveRequest::METHOD => "Resolves the details of an inlay hint.",
+        InlayHintRefreshRequest::METHOD => "Asks the client to request inlay hints again.",
+        InlineValueRequest::METHOD => "Requests the inline values to show while debugging.",
+        InlineValueRefreshRequest::METHOD => "Asks the client to request inline values again.",
+        MonikerRequest::METHOD => {
+            "Requests the monikers identifying the symbol at a position across indexes."
+        }
+        Completion::METHOD => "Requests completion items at a position.",
+        ResolveCompletionItem::METHOD => "Resolves the details of a completion item.",
+        SignatureHelpRequest::METHOD => "Requests the signature of the call at a position.",
+        CodeActionRequest::METHOD => {
+            "Requests the code actions (fixes, refactors) available for a range."
+        }
+        CodeActionResolveRequest::METHOD => "Resolves the edit of a code action.",
+        DocumentColor::METHOD => "Finds the color references in a document.",
+        ColorPresentationRequest::METHOD => "Requests the ways a color can be written.",
+        Formatting::METHOD => "Requests edits that format a whole document.",
+        RangeFormatting::METHOD => "Requests edits that format a range of a document.",
+        OnTypeFormatting::METHOD => "Requests edits that format a document as the user types.",
+        Rename::METHOD => "Requests the workspace edit that renames the symbol at a position.",
+        PrepareRenameRequest::METHOD => "Checks whether the symbol at a position can be renamed.",
+        LinkedEditingRange::METHOD => {
+            "Finds the ranges that should be edited together with the one at a position."
+        }
+        _ => return None,
+    };
+    Some(description)
+}
+
 #[repr(u8)]
-#[derive(Clone, Copy, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
 pub(crate) enum MessageKind {
     Lifecycle,
     TextDocumentSynchronization,
@@ -497,121 +1080,366 @@ pub(crate) enum MessageKind {
     Rename,
     LinkedEditingRange,
     ExecuteCommand,
+    Custom,
 }
 
+/// The key that messages fitting none of the `MessageKind`s are given, like a kind's own key.
+pub(crate) const UNCATEGORIZED_KEY: &str = "uncategorized";
+
+/// Every kind with the key it's given in query strings and form ids, and the label it's shown
+/// with. The methods on `MessageKind` are all read from here, so a new kind only needs a row.
+const MESSAGE_KINDS: &[(MessageKind, &str, &str)] = &[
+    (MessageKind::Lifecycle, "life_cycle", "life cycle"),
+    (
+        MessageKind::TextDocumentSynchronization,
+        "document_synchronization",
+        "document synchronization",
+    ),
+    (
+        MessageKind::NotebookDocumentSynchronization,
+        "notebook_synchronization",
+        "notebook synchronization",
+    ),
+    (
+        MessageKind::WorkspaceSynchronization,
+        "workspace_synchronization",
+        "workspace synchronization",
+    ),
+    (MessageKind::Workspace, "workspace", "workspace"),
+    (MessageKind::Telemetry, "telemetry", "telemetry"),
+    (MessageKind::Declaration, "declaration", "declaration"),
+    (MessageKind::Definition, "definition", "definition"),
+    (
+        MessageKind::TypeDefinition,
+        "type_definition",
+        "type definition",
+    ),
+    (
+        MessageKind::Implementation,
+        "implementation",
+        "implementation",
+    ),
+    (MessageKind::References, "references", "references"),
+    (
+        MessageKind::CallHierarchy,
+        "call_hierarchy",
+        "call hierarchy",
+    ),
+    (
+        MessageKind::TypeHierarchy,
+        "type_hierarchy",
+        "type hierarchy",
+    ),
+    (
+        MessageKind::DocumentHighlight,
+        "document_highlight",
+        "document highlight",
+    ),
+    (MessageKind::DocumentLink, "document_link", "document link"),
+    (MessageKind::Hover, "hover", "hover"),
+    (MessageKind::CodeLens, "code_lens", "code lens"),
+    (MessageKind::FoldingRange, "folding_range", "folding range"),
+    (MessageKind::Selection, "selection", "selection"),
+    (MessageKind::Symbol, "symbol", "symbol"),
+    (
+        MessageKind::SemanticTokens,
+        "semantic_tokens",
+        "semantic tokens",
+    ),
+    (MessageKind::InlayHint, "inlay_hint", "inlay hint"),
+    (MessageKind::InlineValue, "inline_value", "inline value"),
+    (MessageKind::Moniker, "moniker", "moniker"),
+    (MessageKind::Completion, "completion", "completion"),
+    (MessageKind::Diagnostic, "diagnostic", "diagnostic"),
+    (
+        MessageKind::SignatureHelp,
+        "signature_help",
+        "signature help",
+    ),
+    (MessageKind::CodeAction, "code_action", "code action"),
+    (
+        MessageKind::DocumentColor,
+        "document_color",
+        "document color",
+    ),
+    (MessageKind::Formatting, "formatting", "formatting"),
+    (MessageKind::Rename, "rename", "rename"),
+    (
+        MessageKind::LinkedEditingRange,
+        "linked_editing_range",
+        "linked editing range",
+    ),
+    (
+        MessageKind::ExecuteCommand,
+        "execute_command",
+        "execute command",
+    ),
+    (MessageKind::Custom, "custom", "custom"),
+];
+
 impl MessageKind {
-    pub(crate) fn all() -> &'static [MessageKind] {
-        &[
-            MessageKind::Lifecycle,
-            MessageKind::TextDocumentSynchronization,
-            MessageKind::NotebookDocumentSynchronization,
-            MessageKind::WorkspaceSynchronization,
-            MessageKind::Workspace,
-            MessageKind::Telemetry,
-            MessageKind::Declaration,
-            MessageKind::Definition,
-            MessageKind::TypeDefinition,
-            MessageKind::Implementation,
-            MessageKind::References,
-            MessageKind::CallHierarchy,
-            MessageKind::TypeHierarchy,
-            MessageKind::DocumentHighlight,
-            MessageKind::DocumentLink,
-            MessageKind::Hover,
-            MessageKind::CodeLens,
-            MessageKind::FoldingRange,
-            MessageKind::Selection,
-            MessageKind::Symbol,
-            MessageKind::SemanticTokens,
-            MessageKind::InlayHint,
-            MessageKind::InlineValue,
-            MessageKind::Moniker,
-            MessageKind::Completion,
-            MessageKind::Diagnostic,
-            MessageKind::SignatureHelp,
-            MessageKind::CodeAction,
-            MessageKind::DocumentColor,
-            MessageKind::Formatting,
-            MessageKind::Rename,
-            MessageKind::LinkedEditingRange,
-            MessageKind::ExecuteCommand,
-        ]
+    pub(crate) fn all() -> impl ExactSizeIterator<Item = MessageKind> + Clone {
+        MESSAGE_KINDS.iter().map(|(kind, _, _)| *kind)
+    }
+
+    fn entry(&self) -> &'static (MessageKind, &'static str, &'static str) {
+        MESSAGE_KINDS
+            .iter()
+            .find(|(kind, _, _)| kind == self)
+            .expect("every kind to have a row in MESSAGE_KINDS")
+    }
+
+    /// The key this kind is given in query strings and form ids, in snake case.
+    pub(crate) fn key(&self) -> &'static str {
+        self.entry().1
     }
 
     pub(crate) fn as_str(&self) -> &'static str {
-        match self {
-            MessageKind::Lifecycle => "life cycle",
-            MessageKind::TextDocumentSynchronization => "document synchronization",
-            MessageKind::NotebookDocumentSynchronization => "notebook synchronization",
-            MessageKind::WorkspaceSynchronization => "workspace synchronization",
-            MessageKind::Workspace => "workspace",
-            MessageKind::Telemetry => "telemetry",
-            MessageKind::Declaration => "declaration",
-            MessageKind::Definition => "definition",
-            MessageKind::TypeDefinition => "type definition",
-            MessageKind::Implementation => "implementation",
-            MessageKind::References => "references",
-            MessageKind::CallHierarchy => "call hierarchy",
-            MessageKind::TypeHierarchy => "type hierarchy",
-            MessageKind::DocumentHighlight => "document highlight",
-            MessageKind::DocumentLink => "document link",
-            MessageKind::Hover => "hover",
-            MessageKind::CodeLens => "code lens",
-            MessageKind::FoldingRange => "folding range",
-            MessageKind::Selection => "selection",
-            MessageKind::Symbol => "symbol",
-            MessageKind::SemanticTokens => "semantic tokens",
-            MessageKind::InlayHint => "inlay hint",
-            MessageKind::InlineValue => "inline value",
-            MessageKind::Moniker => "moniker",
-            MessageKind::Completion => "completion",
-            MessageKind::Diagnostic => "diagnostic",
-            MessageKind::SignatureHelp => "signature help",
-            MessageKind::CodeAction => "code action",
-            MessageKind::DocumentColor => "document color",
-            MessageKind::Formatting => "formatting",
-            MessageKind::Rename => "rename",
-            MessageKind::LinkedEditingRange => "linked editing range",
-            MessageKind::ExecuteCommand => "execute command",
-        }
-    }
-
-    fn try_parse_str(str: &str) -> Option<Self> {
-        match str {
-            "life_cycle" => Some(MessageKind::Lifecycle),
-            "document_synchronization" => Some(MessageKind::TextDocumentSynchronization),
-            "notebook_synchronization" => Some(MessageKind::NotebookDocumentSynchronization),
-            "workspace_synchronization" => Some(MessageKind::WorkspaceSynchronization),
-            "workspace" => Some(MessageKind::Workspace),
-            "telemetry" => Some(MessageKind::Telemetry),
-            "declaration" => Some(MessageKind::Declaration),
-            "definition" => Some(MessageKind::Definition),
-            "type_definition" => Some(MessageKind::TypeDefinition),
-            "implementation" => Some(MessageKind::Implementation),
-            "references" => Some(MessageKind::References),
-            "call_hierarchy" => Some(MessageKind::CallHierarchy),
-            "type_hierarchy" => Some(MessageKind::TypeHierarchy),
-            "document_highlight" => Some(MessageKind::DocumentHighlight),
-            "document_link" => Some(MessageKind::DocumentLink),
-            "hover" => Some(MessageKind::Hover),
-            "code_lens" => Some(MessageKind::CodeLens),
-            "folding_range" => Some(MessageKind::FoldingRange),
-            "selection" => Some(MessageKind::Selection),
-            "symbol" => Some(MessageKind::Symbol),
-            "semantic_tokens" => Some(MessageKind::SemanticTokens),
-            "inlay_hint" => Some(MessageKind::InlayHint),
-            "inline_value" => Some(MessageKind::InlineValue),
-            "moniker" => Some(MessageKind::Moniker),
-            "completion" => Some(MessageKind::Completion),
-            "diagnostic" => Some(MessageKind::Diagnostic),
-            "signature_help" => Some(MessageKind::SignatureHelp),
-            "code_action" => Some(MessageKind::CodeAction),
-            "document_color" => Some(MessageKind::DocumentColor),
-            "formatting" => Some(MessageKind::Formatting),
-            "rename" => Some(MessageKind::Rename),
-            "linked_editing_range" => Some(MessageKind::LinkedEditingRange),
-            "execute_command" => Some(MessageKind::ExecuteCommand),
-            _ => None,
+        self.entry().2
+    }
+
+    pub(crate) fn try_parse_str(str: &str) -> Option<Self> {
+        MESSAGE_KINDS
+            .iter()
+            .find(|(_, key, _)| *key == str)
+            .map(|(kind, _, _)| *kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn cancel_latency_is_measured_from_the_request_to_its_cancel() {
+        let conversation = Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                0,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                150,
+                json!({ "jsonrpc": "2.0", "method": "$/cancelRequest", "params": { "id": 1 } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                200,
+                json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32800, "message": "cancelled" } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                4,
+                300,
+                json!({ "jsonrpc": "2.0", "method": "$/cancelRequest", "params": { "id": 9 } }),
+            ),
+        ]);
+
+        let analysis = conversation.analyze_cancellations();
+
+        assert_eq!(analysis.cancelled.len(), 1);
+        assert_eq!(analysis.cancelled[0].request.seq, 1);
+        assert_eq!(
+            analysis.cancelled[0].cancel_latency(),
+            Duration::milliseconds(150)
+        );
+        assert!(analysis.cancelled[0].has_late_response());
+        assert_eq!(analysis.unknown_cancellations.len(), 1);
+        assert_eq!(analysis.unknown_cancellations[0].seq, 4);
+    }
+
+    #[test]
+    fn think_time_is_measured_when_both_proxy_time_stamps_are_present() {
+        let at = |millis| OffsetDateTime::UNIX_EPOCH + Duration::milliseconds(millis);
+        let mut request = MessageWithTimeStamp::for_tests(
+            1,
+            0,
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "textDocument/hover", "params": {} }),
+        );
+        request.proxy_time_stamps.delivered_time_stamp = Some(at(20));
+        let mut response = MessageWithTimeStamp::for_tests(
+            2,
+            100,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+        );
+        response.proxy_time_stamps.sent_time_stamp = Some(at(70));
+
+        let mut pair = RequestResponsePair {
+            request: &request,
+            response: Some(&response),
+            duplicate_responses: Vec::new(),
+        };
+        assert_eq!(pair.latency(), Some(Duration::milliseconds(100)));
+        assert_eq!(pair.think_time(), Some(Duration::milliseconds(50)));
+
+        let unreported = MessageWithTimeStamp::for_tests(
+            2,
+            100,
+            json!({ "jsonrpc": "2.0", "id": 1, "result": null }),
+        );
+        pair.response = Some(&unreported);
+        assert_eq!(pair.think_time(), None);
+    }
+
+    #[test]
+    fn diagnostics_are_linked_to_the_edit_before_them() {
+        let diagnostics = |seq, millis, uri| {
+            MessageWithTimeStamp::for_tests(
+                seq,
+                millis,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                }),
+            )
+        };
+        let conversation = Conversation::from(vec![
+            diagnostics(1, 0, "file:///main.rs"),
+            MessageWithTimeStamp::for_tests(
+                2,
+                100,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didOpen",
+                    "params": { "textDocument": {
+                        "uri": "file:///main.rs",
+                        "languageId": "rust",
+                        "version": 1,
+                        "text": "",
+                    } },
+                }),
+            ),
+            diagnostics(3, 200, "file:///main.rs"),
+            MessageWithTimeStamp::for_tests(
+                4,
+                300,
+                json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/didChange",
+                    "params": {
+                        "textDocument": { "uri": "file:///main.rs", "version": 2 },
+                        "contentChanges": [{ "text": "fn main() {}" }],
+                    },
+                }),
+            ),
+            diagnostics(5, 400, "file:///main.rs"),
+            diagnostics(6, 500, "file:///lib.rs"),
+        ]);
+
+        let correlations = conversation.correlate_diagnostics_with_edits();
+
+        assert_eq!(correlations.len(), 2);
+        assert!(matches!(correlations[&3], DiagnosticTrigger::Open(open) if open.seq == 2));
+        assert!(matches!(correlations[&5], DiagnosticTrigger::Edit(edit) if edit.seq == 4));
+    }
+
+    #[test]
+    fn protocol_phases_span_a_full_lifecycle() {
+        let conversation = Conversation::from(vec![
+            MessageWithTimeStamp::for_tests(
+                1,
+                100,
+                json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                2,
+                300,
+                json!({ "jsonrpc": "2.0", "id": 1, "result": { "capabilities": {} } }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                3,
+                400,
+                json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                4,
+                2000,
+                json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            ),
+            MessageWithTimeStamp::for_tests(
+                5,
+                2050,
+                json!({ "jsonrpc": "2.0", "id": 2, "result": null }),
+            ),
+            MessageWithTimeStamp::for_tests(6, 2100, json!({ "jsonrpc": "2.0", "method": "exit" })),
+        ]);
+        let at = |millis| OffsetDateTime::UNIX_EPOCH + Duration::milliseconds(millis);
+
+        let phases = conversation.get_protocol_phases(at(0), Some(at(2200)));
+
+        let durations = phases
+            .iter()
+            .map(|span| (span.phase.as_str(), span.duration().whole_milliseconds()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            durations,
+            [
+                ("pre-initialize", 100),
+                ("initializing", 300),
+                ("active", 1600),
+                ("shutting down", 100),
+                ("exited", 100),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_slowest_answered_requests_come_first() {
+        let request = |seq, millis, id| {
+            MessageWithTimeStamp::for_tests(
+                seq,
+                millis,
+                json!({ "jsonrpc": "2.0", "id": id, "method": "textDocument/hover", "params": {} }),
+            )
+        };
+        let response = |seq, millis, id| {
+            MessageWithTimeStamp::for_tests(
+                seq,
+                millis,
+                json!({ "jsonrpc": "2.0", "id": id, "result": null }),
+            )
+        };
+        let conversation = Conversation::from(vec![
+            request(1, 0, 1),
+            request(2, 10, 2),
+            request(3, 20, 3),
+            request(4, 30, 4),
+            response(5, 50, 1),
+            response(6, 140, 3),
+            response(7, 310, 2),
+        ]);
+
+        let latencies = |count| {
+            conversation
+                .get_slowest_requests(count)
+                .iter()
+                .map(|pair| {
+                    (
+                        pair.request.seq,
+                        pair.latency().unwrap().whole_milliseconds(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(latencies(2), [(2, 300), (3, 120)]);
+        // The unanswered request isn't listed however many are asked for.
+        assert_eq!(latencies(10), [(2, 300), (3, 120), (1, 50)]);
+    }
+
+    #[test]
+    fn every_kind_round_trips_through_its_key() {
+        for kind in MessageKind::all() {
+            assert_eq!(MessageKind::try_parse_str(kind.key()), Some(kind));
         }
+
+        let keys = MessageKind::all()
+            .map(|kind| kind.key())
+            .collect::<HashSet<_>>();
+        assert_eq!(keys.len(), MessageKind::all().len());
+        assert_eq!(MessageKind::try_parse_str("life cycle"), None);
     }
 }
@@ -0,0 +1,243 @@
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicI64, AtomicU64, Ordering},
+};
+
+use lsp_server::Message as LspMessage;
+
+use crate::{
+    message::{MessageKind, UNCATEGORIZED_KEY},
+    session::MessageSource,
+};
+
+/// The JSON-RPC shape of an ingested message, which ingest can tell without the rest of the
+/// conversation.
+#[derive(Clone, Copy)]
+pub(crate) enum MessageType {
+    Request = 0,
+    Response = 1,
+    Notification = 2,
+}
+
+impl MessageType {
+    const ALL: [MessageType; 3] = [
+        MessageType::Request,
+        MessageType::Response,
+        MessageType::Notification,
+    ];
+
+    pub(crate) fn of(msg: &LspMessage) -> Self {
+        match msg {
+            LspMessage::Request(_) => MessageType::Request,
+            LspMessage::Response(_) => MessageType::Response,
+            LspMessage::Notification(_) => MessageType::Notification,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            MessageType::Request => "request",
+            MessageType::Response => "response",
+            MessageType::Notification => "notification",
+        }
+    }
+}
+
+/// The source label of messages from collectors that don't report one.
+const UNKNOWN_SOURCE: usize = 2;
+const SOURCE_LABELS: [&str; 3] = ["client", "server", "unknown"];
+
+/// Counters of ingested messages by `MessageKind`, followed by those of no kind, and then by
+/// source. There are too many kinds for an array to derive `Default`.
+struct KindCounters(Vec<[AtomicU64; 3]>);
+
+impl Default for KindCounters {
+    fn default() -> Self {
+        Self(
+            (0..=MessageKind::all().len())
+                .map(|_| Default::default())
+                .collect(),
+        )
+    }
+}
+
+impl KindCounters {
+    fn get(&self, kind: Option<MessageKind>) -> &[AtomicU64; 3] {
+        &self.0[kind.map_or(MessageKind::all().len(), |kind| kind as usize)]
+    }
+}
+
+/// Process-wide counters describing what the server has done since it started.
+#[derive(Default)]
+pub(crate) struct Metrics {
+    /// Sessions created, over every transport.
+    sessions_created: AtomicU64,
+    /// Messages written to the database, by `MessageType` and then by source.
+    ingested_messages: [[AtomicU64; 3]; 3],
+    /// Messages written to the database, by `MessageKind` and then by source.
+    ingested_messages_by_kind: KindCounters,
+    /// Frames that couldn't be parsed as an LSP message.
+    malformed_messages: AtomicU64,
+    /// Sessions and messages that failed to be written to the database.
+    insert_errors: AtomicU64,
+    /// Messages discarded at ingest by the configured method filter.
+    dropped_messages: AtomicU64,
+    /// Notifications of sampled methods that were left out of the sample.
+    sampled_out_messages: AtomicU64,
+    /// Logs that were never stored in Postgres, because the queue of logs to write was full or
+    /// every attempt to insert them failed.
+    dropped_logs: AtomicU64,
+    /// WebSocket connections currently being logged.
+    open_websocket_sessions: AtomicI64,
+}
+
+impl Metrics {
+    pub(crate) fn record_session_created(&self) {
+        self.sessions_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_ingested_message(
+        &self,
+        message_type: MessageType,
+        kind: Option<MessageKind>,
+        source: Option<MessageSource>,
+    ) {
+        let source = source.map_or(UNKNOWN_SOURCE, |source| source as usize);
+        self.ingested_messages[message_type as usize][source].fetch_add(1, Ordering::Relaxed);
+        self.ingested_messages_by_kind.get(kind)[source].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_malformed_message(&self) {
+        self.malformed_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_insert_error(&self) {
+        self.insert_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped_message(&self) {
+        self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_sampled_out_message(&self) {
+        self.sampled_out_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_dropped_log(&self) {
+        self.dropped_logs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_websocket_opened(&self) {
+        self.open_websocket_sessions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_websocket_closed(&self) {
+        self.open_websocket_sessions.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub(crate) fn to_prometheus_text(&self) -> String {
+        let mut text = String::new();
+
+        append_metric_header_to(
+            &mut text,
+            "lls_sessions_created_total",
+            "counter",
+            "Sessions created since the server started.",
+        );
+        writeln!(
+            text,
+            "lls_sessions_created_total {}",
+            self.sessions_created.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        append_metric_header_to(
+            &mut text,
+            "lls_messages_ingested_total",
+            "counter",
+            "Messages written to the database, by type and by the side that sent them.",
+        );
+        for message_type in MessageType::ALL {
+            for (source, source_label) in SOURCE_LABELS.iter().enumerate() {
+                writeln!(
+                    text,
+                    "lls_messages_ingested_total{{type=\"{}\",source=\"{source_label}\"}} {}",
+                    message_type.as_str(),
+                    self.ingested_messages[message_type as usize][source].load(Ordering::Relaxed)
+                )
+                .unwrap();
+            }
+        }
+
+        append_metric_header_to(
+            &mut text,
+            "lls_messages_ingested_by_kind_total",
+            "counter",
+            "Messages written to the database, by kind and by the side that sent them.",
+        );
+        let kinds = MessageKind::all().map(Some).chain(std::iter::once(None));
+        for kind in kinds {
+            let kind_label = kind.map_or(UNCATEGORIZED_KEY, |kind| kind.key());
+            for (source, source_label) in SOURCE_LABELS.iter().enumerate() {
+                writeln!(
+                    text,
+                    "lls_messages_ingested_by_kind_total{{kind=\"{kind_label}\",source=\"{source_label}\"}} {}",
+                    self.ingested_messages_by_kind.get(kind)[source].load(Ordering::Relaxed)
+                )
+                .unwrap();
+            }
+        }
+
+        for (name, help, counter) in [
+            (
+                "lls_malformed_messages_total",
+                "Frames that couldn't be parsed as an LSP message.",
+                &self.malformed_messages,
+            ),
+            (
+                "lls_database_insert_errors_total",
+                "Sessions and messages that failed to be written to the database.",
+                &self.insert_errors,
+            ),
+            (
+                "lls_dropped_messages_total",
+                "Messages discarded by the ingest method filter.",
+                &self.dropped_messages,
+            ),
+            (
+                "lls_sampled_out_messages_total",
+                "Notifications left out of the sample of their method.",
+                &self.sampled_out_messages,
+            ),
+            (
+                "lls_dropped_logs_total",
+                "Logs that were never stored, because their queue was full or their insert failed.",
+                &self.dropped_logs,
+            ),
+        ] {
+            append_metric_header_to(&mut text, name, "counter", help);
+            writeln!(text, "{name} {}", counter.load(Ordering::Relaxed)).unwrap();
+        }
+
+        append_metric_header_to(
+            &mut text,
+            "lls_open_websocket_sessions",
+            "gauge",
+            "WebSocket connections currently being logged.",
+        );
+        writeln!(
+            text,
+            "lls_open_websocket_sessions {}",
+            self.open_websocket_sessions.load(Ordering::Relaxed)
+        )
+        .unwrap();
+
+        text
+    }
+}
+
+fn append_metric_header_to(text: &mut String, name: &str, metric_type: &str, help: &str) {
+    writeln!(text, "# HELP {name} {help}").unwrap();
+    writeln!(text, "# TYPE {name} {metric_type}").unwrap();
+}
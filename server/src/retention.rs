@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use crate::{AppState, api::compact::compact_payloads, config::RetentionConfig};
+
+/// How often old payloads are looked for. Retention is measured in days, so this only bounds how
+/// long past its limit a payload can linger.
+const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Periodically frees payloads past their retention, for as long as the server runs.
+pub(crate) async fn enforce_retention_periodically(state: AppState) {
+    let Some(retention) = &state.config.retention else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(err) = enforce_retention(&state.db, retention).await {
+            error!("Failed to enforce payload retention. Error: {err}");
+        }
+    }
+}
+
+/// Drops the raw bytes of messages received before the raw retention, then compacts the
+/// sessions that ended before the payload retention.
+pub(crate) async fn enforce_retention(
+    db: &PgPool,
+    retention: &RetentionConfig,
+) -> Result<(), sqlx::Error> {
+    let now = OffsetDateTime::now_utc();
+
+    if let Some(raw_max_age) = retention.raw_max_age {
+        let cleared = drop_raw_bytes_received_before(db, now - raw_max_age).await?;
+        if cleared > 0 {
+            info!("Dropped the raw bytes of {cleared} messages");
+        }
+    }
+
+    if let Some(payload_max_age) = retention.payload_max_age {
+        let compacted = compact_sessions_ended_before(db, now - payload_max_age).await?;
+        if compacted > 0 {
+            info!("Compacted {compacted} sessions past their payload retention");
+        }
+    }
+
+    Ok(())
+}
+
+/// Clears the raw bytes of every message received before `cutoff`, leaving the parsed payloads.
+/// Returns the number of messages cleared.
+async fn drop_raw_bytes_received_before(
+    db: &PgPool,
+    cutoff: OffsetDateTime,
+) -> Result<u64, sqlx::Error> {
+    let mut cleared = 0;
+    cleared += sqlx::query!(
+        "UPDATE requests SET raw = NULL WHERE raw IS NOT NULL AND time_stamp < $1;",
+        cutoff
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    cleared += sqlx::query!(
+        "UPDATE responses SET raw = NULL WHERE raw IS NOT NULL AND time_stamp < $1;",
+        cutoff
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    cleared += sqlx::query!(
+        "UPDATE notifications SET raw = NULL WHERE raw IS NOT NULL AND time_stamp < $1;",
+        cutoff
+    )
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(cleared)
+}
+
+/// Compacts every session that ended before `cutoff` and hasn't been compacted yet. Returns the
+/// number of sessions compacted.
+async fn compact_sessions_ended_before(
+    db: &PgPool,
+    cutoff: OffsetDateTime,
+) -> Result<usize, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    let session_ids = sqlx::query_scalar!(
+        "SELECT id FROM sessions WHERE end_time_stamp < $1 AND NOT compacted FOR UPDATE;",
+        cutoff
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    if session_ids.is_empty() {
+        return Ok(0);
+    }
+
+    compact_payloads(&mut transaction, &session_ids).await?;
+    transaction.commit().await?;
+
+    Ok(session_ids.len())
+}
+
+/// Deletes every session that ended before `cutoff`, along with all of its messages and logs.
+/// Returns the number of sessions deleted.
+pub(crate) async fn delete_sessions_ended_before(
+    db: &PgPool,
+    cutoff: OffsetDateTime,
+) -> Result<u64, sqlx::Error> {
+    // Everything goes in one transaction so a failure part way through can't leave messages
+    // behind without their session.
+    let mut transaction = db.begin().await?;
+
+    let session_ids = sqlx::query_scalar!(
+        "SELECT id FROM sessions WHERE end_time_stamp < $1 FOR UPDATE;",
+        cutoff
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    if session_ids.is_empty() {
+        return Ok(0);
+    }
+
+    sqlx::query!(
+        "DELETE FROM requests WHERE session_id = ANY($1);",
+        &session_ids
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM responses WHERE session_id = ANY($1);",
+        &session_ids
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM notifications WHERE session_id = ANY($1);",
+        &session_ids
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM malformed_messages WHERE session_id = ANY($1);",
+        &session_ids
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!("DELETE FROM logs WHERE session_id = ANY($1);", &session_ids)
+        .execute(&mut *transaction)
+        .await?;
+    let deleted = sqlx::query!("DELETE FROM sessions WHERE id = ANY($1);", &session_ids)
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected();
+
+    transaction.commit().await?;
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[sqlx::test]
+    async fn raw_bytes_are_dropped_before_the_parsed_payloads(db: PgPool) {
+        let now = OffsetDateTime::now_utc();
+        let session_id = sqlx::query_scalar!(
+            "INSERT INTO sessions (start_time_stamp, end_time_stamp) VALUES ($1, $2) RETURNING id;",
+            now - 10 * DAY,
+            now - 2 * DAY
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        for (request_id, time_stamp) in [("old", now - 10 * DAY), ("recent", now - DAY)] {
+            sqlx::query!(
+                "INSERT INTO requests (request_id, session_id, method, params, time_stamp, raw) VALUES ($1, $2, 'textDocument/hover', '{\"position\":{}}', $3, 'raw frame');",
+                request_id,
+                session_id,
+                time_stamp
+            )
+            .execute(&db)
+            .await
+            .unwrap();
+        }
+
+        enforce_retention(
+            &db,
+            &RetentionConfig {
+                raw_max_age: Some(7 * DAY),
+                payload_max_age: Some(30 * DAY),
+            },
+        )
+        .await
+        .unwrap();
+
+        let requests = sqlx::query!(
+            r#"SELECT request_id, raw IS NOT NULL AS "has_raw!", params IS NOT NULL AS "has_params!" FROM requests ORDER BY time_stamp;"#
+        )
+        .fetch_all(&db)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.request_id, row.has_raw, row.has_params))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            requests,
+            [
+                ("old".to_string(), false, true),
+                ("recent".to_string(), true, true),
+            ]
+        );
+    }
+}
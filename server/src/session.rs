@@ -1,20 +1,96 @@
-use futures::future;
+use std::collections::HashMap;
+
 use lsp_server::{Message, Notification, Request, RequestId, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use sqlx::PgPool;
 use time::OffsetDateTime;
+use tracing::{error, warn};
 
-use crate::message::Conversation;
+use crate::{compression::decompress_payload, message::Conversation};
 
 #[derive(Clone)]
 pub(crate) struct MessageWithTimeStamp {
     pub(crate) time_stamp: OffsetDateTime,
+    /// The order in which the message was logged across all message tables. Breaks ties
+    /// between messages with the same `time_stamp`.
+    pub(crate) seq: i64,
+    pub(crate) proxy_time_stamps: ProxyTimeStamps,
+    /// The size of the message's JSON content as it was received, if it was recorded.
+    pub(crate) byte_size: Option<i32>,
+    /// Shared by messages that arrived together in one JSON-RPC batch.
+    pub(crate) batch_id: Option<i64>,
+    /// The side that sent the message, when the collector reported it.
+    pub(crate) source: Option<MessageSource>,
     pub(crate) message: Message,
 }
 
-#[derive(Clone, Copy)]
+impl MessageWithTimeStamp {
+    /// The side that sent the message. Messages logged without a source fall back to inferring
+    /// it from their method.
+    pub(crate) fn get_source(
+        &self,
+        containing_conversation: &Conversation,
+    ) -> Option<MessageSource> {
+        self.source
+            .or_else(|| crate::message::get_source(&self.message, containing_conversation))
+    }
+}
+
+#[cfg(test)]
+impl MessageWithTimeStamp {
+    /// A message logged `millis` milliseconds after the Unix epoch, parsed from its JSON, with
+    /// nothing else recorded about it.
+    pub(crate) fn for_tests(seq: i64, millis: i64, json: Value) -> Self {
+        Self {
+            time_stamp: OffsetDateTime::UNIX_EPOCH + time::Duration::milliseconds(millis),
+            seq,
+            proxy_time_stamps: ProxyTimeStamps::default(),
+            byte_size: None,
+            batch_id: None,
+            source: None,
+            message: serde_json::from_value(json).expect("the test message to be valid"),
+        }
+    }
+}
+
+/// Times reported by a proxying collector for when a message left its sender and when it was
+/// delivered to its receiver. Either may be missing when the collector can't observe it.
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+pub(crate) struct ProxyTimeStamps {
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) sent_time_stamp: Option<OffsetDateTime>,
+    #[serde(
+        default,
+        with = "time::serde::rfc3339::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) delivered_time_stamp: Option<OffsetDateTime>,
+}
+
+/// Which side of the connection sent a message. The discriminants are the ids of the `sources`
+/// table.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
 pub(crate) enum MessageSource {
-    Client,
-    Server,
+    Client = 0,
+    Server = 1,
+}
+
+impl TryFrom<i32> for MessageSource {
+    type Error = ();
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MessageSource::Client),
+            1 => Ok(MessageSource::Server),
+            _ => Err(()),
+        }
+    }
 }
 
 impl MessageSource {
@@ -30,90 +106,574 @@ pub(crate) async fn get_all_messages_for_session_in_chronological_order(
     db: &PgPool,
     session_id: i64,
 ) -> Result<Conversation, sqlx::Error> {
-    let requests = sqlx::query!(
-        "SELECT * FROM requests WHERE session_id = $1 ORDER BY time_stamp ASC",
+    let records = sqlx::query_as!(
+        MessageRecord,
+        r#"SELECT 'request'::TEXT AS "kind!", request_id AS "request_id?", method AS "method?", params, NULL::BOOLEAN AS "is_error?", NULL::JSON AS "result?", NULL::INTEGER AS "error_code?", NULL::TEXT AS "error_message?", time_stamp AS "time_stamp!", seq AS "seq!", sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+        FROM requests WHERE session_id = $1
+        UNION ALL
+        SELECT 'response'::TEXT, request_id, NULL::TEXT, NULL::JSON, is_error, result, error_code, error_message, time_stamp, seq, sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+        FROM responses WHERE session_id = $1
+        UNION ALL
+        SELECT 'notification'::TEXT, NULL::TEXT, method, params, NULL::BOOLEAN, NULL::JSON, NULL::INTEGER, NULL::TEXT, time_stamp, seq, NULL::TIMESTAMPTZ, NULL::TIMESTAMPTZ, byte_size, batch_id, source, compressed_payload
+        FROM notifications WHERE session_id = $1
+        ORDER BY 9 ASC, 10 ASC"#,
         session_id
     )
-    .fetch_all(db);
+    .fetch_all(db)
+    .await?;
 
-    let responses = sqlx::query!(
-        "SELECT * FROM responses WHERE session_id = $1 ORDER BY time_stamp ASC",
-        session_id
+    Ok(into_messages(records).into())
+}
+
+/// Looks up the time stamp of the message with this seq in a session, which is what a window
+/// of messages after it starts from.
+pub(crate) async fn get_message_time_stamp(
+    db: &PgPool,
+    session_id: i64,
+    seq: i64,
+) -> Result<Option<OffsetDateTime>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT time_stamp AS "time_stamp!" FROM requests WHERE session_id = $1 AND seq = $2
+        UNION ALL
+        SELECT time_stamp FROM responses WHERE session_id = $1 AND seq = $2
+        UNION ALL
+        SELECT time_stamp FROM notifications WHERE session_id = $1 AND seq = $2"#,
+        session_id,
+        seq
     )
-    .fetch_all(db);
+    .fetch_optional(db)
+    .await
+}
+
+/// Fetches at most `limit` messages of a session in chronological order, starting after the
+/// message with the given time stamp and seq, or from the first message when there's none.
+/// Messages are ordered by time stamp and then by seq, as they are when the whole session is
+/// fetched.
+pub(crate) async fn get_message_window_for_session(
+    db: &PgPool,
+    session_id: i64,
+    after: Option<(OffsetDateTime, i64)>,
+    limit: i64,
+) -> Result<Vec<MessageWithTimeStamp>, sqlx::Error> {
+    let (after_time_stamp, after_seq) = after.unzip();
+    let records = sqlx::query_as!(
+        MessageRecord,
+        r#"SELECT 'request'::TEXT AS "kind!", request_id AS "request_id?", method AS "method?", params, NULL::BOOLEAN AS "is_error?", NULL::JSON AS "result?", NULL::INTEGER AS "error_code?", NULL::TEXT AS "error_message?", time_stamp AS "time_stamp!", seq AS "seq!", sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+        FROM requests WHERE session_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR (time_stamp, seq) > ($2, $3))
+        UNION ALL
+        SELECT 'response'::TEXT, request_id, NULL::TEXT, NULL::JSON, is_error, result, error_code, error_message, time_stamp, seq, sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+        FROM responses WHERE session_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR (time_stamp, seq) > ($2, $3))
+        UNION ALL
+        SELECT 'notification'::TEXT, NULL::TEXT, method, params, NULL::BOOLEAN, NULL::JSON, NULL::INTEGER, NULL::TEXT, time_stamp, seq, NULL::TIMESTAMPTZ, NULL::TIMESTAMPTZ, byte_size, batch_id, source, compressed_payload
+        FROM notifications WHERE session_id = $1 AND ($2::TIMESTAMPTZ IS NULL OR (time_stamp, seq) > ($2, $3))
+        ORDER BY 9 ASC, 10 ASC LIMIT $4"#,
+        session_id,
+        after_time_stamp,
+        after_seq,
+        limit
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(into_messages(records))
+}
 
-    let notifications = sqlx::query!(
-        "SELECT * FROM notifications WHERE session_id = $1 ORDER BY time_stamp ASC",
+/// A frame that couldn't be parsed as an LSP message.
+pub(crate) struct MalformedMessage {
+    pub(crate) time_stamp: OffsetDateTime,
+    /// Shares the sequence of the message tables, so it orders among them.
+    pub(crate) seq: i64,
+    /// The frame exactly as it was received. `None` once the session has been compacted.
+    pub(crate) raw_bytes: Option<Vec<u8>>,
+    pub(crate) parse_error: String,
+}
+
+pub(crate) async fn get_malformed_messages_for_session(
+    db: &PgPool,
+    session_id: i64,
+) -> Result<Vec<MalformedMessage>, sqlx::Error> {
+    sqlx::query_as!(
+        MalformedMessage,
+        "SELECT time_stamp, seq, raw_bytes, parse_error
+        FROM malformed_messages WHERE session_id = $1 ORDER BY time_stamp ASC, seq ASC",
         session_id
     )
-    .fetch_all(db);
+    .fetch_all(db)
+    .await
+}
 
-    let (requests_result, responses_result, notifications_result) =
-        future::join3(requests, responses, notifications).await;
+/// A request logged in a different session than the response that answers it.
+pub(crate) struct ExternalRequest {
+    pub(crate) session_id: i64,
+    pub(crate) seq: i64,
+    pub(crate) method: String,
+}
 
-    let requests = requests_result?;
-    let responses = responses_result?;
-    let notifications = notifications_result?;
+/// Looks for requests with the given ids in the `session_count` sessions before `session_id`,
+/// preferring the most recent session when an id was used more than once.
+pub(crate) async fn find_requests_in_preceding_sessions(
+    db: &PgPool,
+    session_id: i64,
+    session_count: i64,
+    ids: &[&RequestId],
+) -> Result<HashMap<RequestId, ExternalRequest>, sqlx::Error> {
+    let stored_ids = ids
+        .iter()
+        .map(|id| get_stored_request_id(id))
+        .collect::<Vec<_>>();
 
-    let requests_ref = &requests;
-    let mut all_messages = responses
+    let records = sqlx::query!(
+        "SELECT DISTINCT ON (request_id) request_id, session_id, seq, method
+        FROM requests
+        WHERE session_id >= $1::BIGINT - $2::BIGINT AND session_id < $1 AND request_id = ANY($3)
+        ORDER BY request_id, session_id DESC, seq DESC",
+        session_id,
+        session_count,
+        &stored_ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(records
         .into_iter()
-        .map(|response_record| {
-            let id = RequestId::from(
-                requests_ref
-                    .iter()
-                    .find(|request| request.id == response_record.id)
-                    .expect("all responses to have a corresponding request")
+        .map(|record| {
+            (
+                parse_stored_request_id(record.request_id),
+                ExternalRequest {
+                    session_id: record.session_id,
+                    seq: record.seq,
+                    method: record.method,
+                },
+            )
+        })
+        .collect())
+}
+
+/// The text a request id is stored as, which is its JSON, so that string ids keep their quotes
+/// and can't be mistaken for numeric ones.
+pub(crate) fn get_stored_request_id(id: &RequestId) -> String {
+    id.to_string()
+}
+
+/// Reads a stored request id back with the JSON type it was logged with.
+fn parse_stored_request_id(stored_id: String) -> RequestId {
+    serde_json::from_str(&stored_id).unwrap_or_else(|_| RequestId::from(stored_id))
+}
+
+/// A row of the union of the requests, responses and notifications of a session. `kind` says
+/// which table it came from, and the columns the other tables don't have are `NULL`.
+struct MessageRecord {
+    kind: String,
+    request_id: Option<String>,
+    method: Option<String>,
+    params: Option<Value>,
+    is_error: Option<bool>,
+    result: Option<Value>,
+    error_code: Option<i32>,
+    error_message: Option<String>,
+    time_stamp: OffsetDateTime,
+    seq: i64,
+    sent_time_stamp: Option<OffsetDateTime>,
+    delivered_time_stamp: Option<OffsetDateTime>,
+    byte_size: Option<i32>,
+    batch_id: Option<i64>,
+    source: Option<i32>,
+    /// The gzipped params or result, stored in their place when they were large.
+    compressed_payload: Option<Vec<u8>>,
+}
+
+impl From<MessageRecord> for MessageWithTimeStamp {
+    fn from(mut record: MessageRecord) -> Self {
+        if let Some(compressed_payload) = record.compressed_payload.take() {
+            let payload = decompress_payload(&compressed_payload)
+                .inspect_err(|err| {
+                    error!(
+                        "Failed to decompress the payload of the message at seq {}. Error: {err}",
+                        record.seq
+                    );
+                })
+                .ok();
+            match record.kind.as_str() {
+                "response" => record.result = payload,
+                _ => record.params = payload,
+            }
+        }
+
+        match record.kind.as_str() {
+            "request" => RequestRecord {
+                request_id: record
                     .request_id
-                    .clone(),
-            );
-            MessageWithTimeStamp {
-                time_stamp: response_record.time_stamp,
-                message: Message::Response(if response_record.is_error {
-                    Response::new_err(
-                        id,
-                        response_record
-                            .error_code
-                            .expect("error_code to have a value when is_error is true"),
-                        response_record.error_message.unwrap_or_default(),
-                    )
-                } else {
-                    Response::new_ok(id, response_record.result)
-                }),
+                    .expect("request_id to have a value for requests"),
+                method: record.method.expect("method to have a value for requests"),
+                params: record.params,
+                time_stamp: record.time_stamp,
+                seq: record.seq,
+                sent_time_stamp: record.sent_time_stamp,
+                delivered_time_stamp: record.delivered_time_stamp,
+                byte_size: record.byte_size,
+                batch_id: record.batch_id,
+                source: record.source,
             }
-        })
-        .collect::<Vec<_>>();
+            .into(),
+            "response" => ResponseRecord {
+                request_id: record.request_id,
+                is_error: record
+                    .is_error
+                    .expect("is_error to have a value for responses"),
+                result: record.result,
+                error_code: record.error_code,
+                error_message: record.error_message,
+                time_stamp: record.time_stamp,
+                seq: record.seq,
+                sent_time_stamp: record.sent_time_stamp,
+                delivered_time_stamp: record.delivered_time_stamp,
+                byte_size: record.byte_size,
+                batch_id: record.batch_id,
+                source: record.source,
+            }
+            .into(),
+            "notification" => NotificationRecord {
+                method: record
+                    .method
+                    .expect("method to have a value for notifications"),
+                params: record.params,
+                time_stamp: record.time_stamp,
+                seq: record.seq,
+                byte_size: record.byte_size,
+                batch_id: record.batch_id,
+                source: record.source,
+            }
+            .into(),
+            kind => unreachable!("Unknown message kind {kind}"),
+        }
+    }
+}
 
-    all_messages.extend(
-        requests
-            .into_iter()
-            .map(|request_record| MessageWithTimeStamp {
-                time_stamp: request_record.time_stamp,
-                message: Message::Request(Request::new(
-                    RequestId::from(request_record.request_id),
-                    request_record.method,
-                    request_record.params,
-                )),
-            }),
-    );
-
-    all_messages.extend(
-        notifications
-            .into_iter()
-            .map(|notification| MessageWithTimeStamp {
-                time_stamp: notification.time_stamp,
-                message: Message::Notification(Notification::new(
-                    notification.method,
-                    notification.params,
-                )),
+struct RequestRecord {
+    request_id: String,
+    method: String,
+    /// `None` once the session has been compacted.
+    params: Option<Value>,
+    time_stamp: OffsetDateTime,
+    seq: i64,
+    sent_time_stamp: Option<OffsetDateTime>,
+    delivered_time_stamp: Option<OffsetDateTime>,
+    byte_size: Option<i32>,
+    batch_id: Option<i64>,
+    source: Option<i32>,
+}
+
+impl From<RequestRecord> for MessageWithTimeStamp {
+    fn from(request_record: RequestRecord) -> Self {
+        MessageWithTimeStamp {
+            time_stamp: request_record.time_stamp,
+            seq: request_record.seq,
+            proxy_time_stamps: ProxyTimeStamps {
+                sent_time_stamp: request_record.sent_time_stamp,
+                delivered_time_stamp: request_record.delivered_time_stamp,
+            },
+            byte_size: request_record.byte_size,
+            batch_id: request_record.batch_id,
+            source: request_record
+                .source
+                .and_then(|source| MessageSource::try_from(source).ok()),
+            message: Message::Request(Request::new(
+                parse_stored_request_id(request_record.request_id),
+                request_record.method,
+                request_record.params,
+            )),
+        }
+    }
+}
+
+struct ResponseRecord {
+    /// The id of the request this responds to. Responses logged before it was stored have none.
+    request_id: Option<String>,
+    is_error: bool,
+    result: Option<Value>,
+    error_code: Option<i32>,
+    error_message: Option<String>,
+    time_stamp: OffsetDateTime,
+    seq: i64,
+    sent_time_stamp: Option<OffsetDateTime>,
+    delivered_time_stamp: Option<OffsetDateTime>,
+    byte_size: Option<i32>,
+    batch_id: Option<i64>,
+    source: Option<i32>,
+}
+
+impl From<ResponseRecord> for MessageWithTimeStamp {
+    fn from(response_record: ResponseRecord) -> Self {
+        let id = match response_record.request_id {
+            Some(request_id) => parse_stored_request_id(request_id),
+            None => {
+                // Rendered as a response to an unknown request rather than guessing which it
+                // answers.
+                warn!(
+                    "The response at seq {} has no stored request_id",
+                    response_record.seq
+                );
+                RequestId::from(String::new())
+            }
+        };
+        MessageWithTimeStamp {
+            time_stamp: response_record.time_stamp,
+            seq: response_record.seq,
+            proxy_time_stamps: ProxyTimeStamps {
+                sent_time_stamp: response_record.sent_time_stamp,
+                delivered_time_stamp: response_record.delivered_time_stamp,
+            },
+            byte_size: response_record.byte_size,
+            batch_id: response_record.batch_id,
+            source: response_record
+                .source
+                .and_then(|source| MessageSource::try_from(source).ok()),
+            message: Message::Response(if response_record.is_error {
+                Response::new_err(
+                    id,
+                    response_record
+                        .error_code
+                        .expect("error_code to have a value when is_error is true"),
+                    response_record.error_message.unwrap_or_default(),
+                )
+            } else {
+                Response::new_ok(id, response_record.result)
             }),
-    );
+        }
+    }
+}
 
-    all_messages.sort_by(|message_with_time_stamp1, message_with_time_stamp2| {
-        message_with_time_stamp1
-            .time_stamp
-            .cmp(&message_with_time_stamp2.time_stamp)
-    });
+struct NotificationRecord {
+    method: String,
+    params: Option<Value>,
+    time_stamp: OffsetDateTime,
+    seq: i64,
+    byte_size: Option<i32>,
+    batch_id: Option<i64>,
+    /// `None` for notifications logged before collectors reported a source, which were all
+    /// recorded as sent by the client whichever side sent them.
+    source: Option<i32>,
+}
+
+impl From<NotificationRecord> for MessageWithTimeStamp {
+    fn from(notification: NotificationRecord) -> Self {
+        MessageWithTimeStamp {
+            time_stamp: notification.time_stamp,
+            seq: notification.seq,
+            proxy_time_stamps: ProxyTimeStamps::default(),
+            byte_size: notification.byte_size,
+            batch_id: notification.batch_id,
+            source: notification
+                .source
+                .and_then(|source| MessageSource::try_from(source).ok()),
+            message: Message::Notification(Notification::new(
+                notification.method,
+                notification.params,
+            )),
+        }
+    }
+}
 
-    Ok(all_messages.into())
+fn into_messages<T: Into<MessageWithTimeStamp>>(records: Vec<T>) -> Vec<MessageWithTimeStamp> {
+    records.into_iter().map(Into::into).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_session(db: &PgPool) -> i64 {
+        sqlx::query_scalar!("INSERT INTO sessions (start_time_stamp) VALUES (NOW()) RETURNING id;")
+            .fetch_one(db)
+            .await
+            .unwrap()
+    }
+
+    /// Logs a request, response or notification to the session, depending on the index, and
+    /// returns its seq.
+    async fn insert_message(
+        db: &PgPool,
+        session_id: i64,
+        index: i64,
+        time_stamp: OffsetDateTime,
+    ) -> i64 {
+        match index % 3 {
+            0 => sqlx::query_scalar!(
+                "INSERT INTO requests (request_id, session_id, method, params, time_stamp) VALUES ($1, $2, 'textDocument/hover', '{}', $3) RETURNING seq;",
+                index.to_string(),
+                session_id,
+                time_stamp
+            )
+            .fetch_one(db)
+            .await
+            .unwrap(),
+            1 => sqlx::query_scalar!(
+                "INSERT INTO responses (request_id, session_id, is_error, result, time_stamp) VALUES ($1, $2, false, 'null', $3) RETURNING seq;",
+                index.to_string(),
+                session_id,
+                time_stamp
+            )
+            .fetch_one(db)
+            .await
+            .unwrap(),
+            _ => sqlx::query_scalar!(
+                "INSERT INTO notifications (session_id, method, params, time_stamp) VALUES ($1, 'textDocument/didSave', '{}', $2) RETURNING seq;",
+                session_id,
+                time_stamp
+            )
+            .fetch_one(db)
+            .await
+            .unwrap(),
+        }
+    }
+
+    #[sqlx::test]
+    async fn messages_are_merged_by_time_stamp_and_then_by_seq(db: PgPool) {
+        let session_id = insert_session(&db).await;
+        let at = |seconds| OffsetDateTime::UNIX_EPOCH + time::Duration::seconds(seconds);
+
+        // Inserted out of order, with a notification, a request and a response sharing the time
+        // stamp of 2s, so that only their seqs order them. The notification is inserted first
+        // even though notifications are read last.
+        let tied_notification = insert_message(&db, session_id, 2, at(2)).await;
+        let first_request = insert_message(&db, session_id, 0, at(0)).await;
+        let last_response = insert_message(&db, session_id, 1, at(3)).await;
+        let tied_request = insert_message(&db, session_id, 3, at(2)).await;
+        let early_notification = insert_message(&db, session_id, 5, at(1)).await;
+        let tied_response = insert_message(&db, session_id, 4, at(2)).await;
+
+        let conversation = get_all_messages_for_session_in_chronological_order(&db, session_id)
+            .await
+            .unwrap();
+
+        let merged = conversation
+            .messages()
+            .iter()
+            .map(|message| {
+                let kind = match message.message {
+                    Message::Request(_) => "request",
+                    Message::Response(_) => "response",
+                    Message::Notification(_) => "notification",
+                };
+                (message.time_stamp, message.seq, kind)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            merged,
+            [
+                (at(0), first_request, "request"),
+                (at(1), early_notification, "notification"),
+                (at(2), tied_notification, "notification"),
+                (at(2), tied_request, "request"),
+                (at(2), tied_response, "response"),
+                (at(3), last_response, "response"),
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn a_session_is_fetched_through_the_time_stamp_indexes(db: PgPool) {
+        let session_id = insert_session(&db).await;
+        let other_session_id = insert_session(&db).await;
+        for index in 0..30i64 {
+            let time_stamp = OffsetDateTime::UNIX_EPOCH + time::Duration::seconds(index);
+            insert_message(&db, session_id, index, time_stamp).await;
+            insert_message(&db, other_session_id, index, time_stamp).await;
+        }
+        sqlx::query!("ANALYZE requests, responses, notifications;")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        // A Seq Scan is cheapest for this few rows, so it's ruled out to see which index the
+        // planner picks once the tables are large.
+        let mut conn = db.acquire().await.unwrap();
+        sqlx::query!("SET enable_seqscan = off;")
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let plan = sqlx::query_scalar::<_, String>(
+            r#"EXPLAIN SELECT 'request'::TEXT AS "kind!", request_id AS "request_id?", method AS "method?", params, NULL::BOOLEAN AS "is_error?", NULL::JSON AS "result?", NULL::INTEGER AS "error_code?", NULL::TEXT AS "error_message?", time_stamp AS "time_stamp!", seq AS "seq!", sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+            FROM requests WHERE session_id = $1
+            UNION ALL
+            SELECT 'response'::TEXT, request_id, NULL::TEXT, NULL::JSON, is_error, result, error_code, error_message, time_stamp, seq, sent_time_stamp, delivered_time_stamp, byte_size, batch_id, source, compressed_payload
+            FROM responses WHERE session_id = $1
+            UNION ALL
+            SELECT 'notification'::TEXT, NULL::TEXT, method, params, NULL::BOOLEAN, NULL::JSON, NULL::INTEGER, NULL::TEXT, time_stamp, seq, NULL::TIMESTAMPTZ, NULL::TIMESTAMPTZ, byte_size, batch_id, source, compressed_payload
+            FROM notifications WHERE session_id = $1
+            ORDER BY 9 ASC, 10 ASC"#,
+        )
+        .bind(session_id)
+        .fetch_all(&mut *conn)
+        .await
+        .unwrap()
+        .join("\n");
+
+        for index in [
+            "requests_session_id_time_stamp_seq_idx",
+            "responses_session_id_time_stamp_seq_idx",
+            "notifications_session_id_time_stamp_seq_idx",
+        ] {
+            assert!(plan.contains(index), "{index} isn't used by:\n{plan}");
+        }
+    }
+
+    #[sqlx::test]
+    async fn a_windowed_fetch_returns_the_messages_that_follow_one(db: PgPool) {
+        let session_id = insert_session(&db).await;
+        let other_session_id = insert_session(&db).await;
+        // Logged out of order, and two at each time stamp, so that the windows follow time
+        // stamps rather than seqs and break ties by seq.
+        for index in 0..10i64 {
+            let time_stamp = OffsetDateTime::UNIX_EPOCH + time::Duration::seconds((index * 3) % 5);
+            insert_message(&db, session_id, index, time_stamp).await;
+            insert_message(&db, other_session_id, index, time_stamp).await;
+        }
+        let messages = get_all_messages_for_session_in_chronological_order(&db, session_id)
+            .await
+            .unwrap()
+            .messages()
+            .iter()
+            .map(|message| (message.time_stamp, message.seq))
+            .collect::<Vec<_>>();
+
+        let window = get_message_window_for_session(&db, session_id, Some(messages[2]), 4)
+            .await
+            .unwrap();
+        let window = window
+            .iter()
+            .map(|message| (message.time_stamp, message.seq))
+            .collect::<Vec<_>>();
+        assert_eq!(window, messages[3..7]);
+
+        let mut paged = Vec::new();
+        let mut after = None;
+        loop {
+            let window = get_message_window_for_session(&db, session_id, after, 3)
+                .await
+                .unwrap();
+            let Some(last) = window.last() else {
+                break;
+            };
+            after = Some((last.time_stamp, last.seq));
+            paged.extend(
+                window
+                    .iter()
+                    .map(|message| (message.time_stamp, message.seq)),
+            );
+        }
+        assert_eq!(paged, messages);
+        assert_eq!(
+            get_message_time_stamp(&db, session_id, messages[5].1)
+                .await
+                .unwrap(),
+            Some(messages[5].0)
+        );
+        assert_eq!(
+            get_message_time_stamp(&db, other_session_id, messages[5].1)
+                .await
+                .unwrap(),
+            None
+        );
+    }
 }
@@ -0,0 +1,181 @@
+use lsp_server::Request;
+use lsp_types::request::{
+    ApplyWorkspaceEdit, CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls,
+    CallHierarchyPrepare, CodeActionRequest, CodeActionResolveRequest, CodeLensRequest,
+    CodeLensResolve, ColorPresentationRequest, Completion, DocumentColor,
+    DocumentDiagnosticRequest, DocumentHighlightRequest, DocumentLinkRequest, DocumentLinkResolve,
+    DocumentSymbolRequest, ExecuteCommand, FoldingRangeRequest, Formatting, GotoDeclaration,
+    GotoDefinition, GotoImplementation, GotoTypeDefinition, HoverRequest, Initialize,
+    InlayHintRequest, InlayHintResolveRequest, InlineValueRequest, LinkedEditingRange,
+    MonikerRequest, OnTypeFormatting, PrepareRenameRequest, RangeFormatting, References,
+    RegisterCapability, Rename, Request as LspRequest, ResolveCompletionItem,
+    SelectionRangeRequest, SemanticTokensFullDeltaRequest, SemanticTokensFullRequest,
+    SemanticTokensRangeRequest, ShowDocument, ShowMessageRequest, SignatureHelpRequest,
+    TypeHierarchyPrepare, TypeHierarchySubtypes, TypeHierarchySupertypes, UnregisterCapability,
+    WillCreateFiles, WillRenameFiles, WillSaveWaitUntil, WorkDoneProgressCreate,
+    WorkspaceConfiguration, WorkspaceDiagnosticRequest, WorkspaceSymbolRequest,
+    WorkspaceSymbolResolve,
+};
+use serde_json::Value;
+
+/// How a request's params line up with the params struct `lsp_types` defines for its method.
+pub(crate) struct SpecComparison {
+    /// The top-level fields the spec recognized.
+    pub(crate) recognized_fields: Vec<String>,
+    /// Top-level fields the spec doesn't define for the method.
+    pub(crate) unexpected_fields: Vec<String>,
+    /// Why the params couldn't be read as the method's params, such as a missing required field.
+    pub(crate) error: Option<String>,
+}
+
+impl SpecComparison {
+    pub(crate) fn matches(&self) -> bool {
+        self.error.is_none() && self.unexpected_fields.is_empty()
+    }
+}
+
+/// Compares a request's params against the spec for its method. Returns `None` for methods
+/// without params to compare, including custom methods.
+pub(crate) fn compare_request_with_spec(request: &Request) -> Option<SpecComparison> {
+    macro_rules! compare_with_any_of {
+        ($($request_type:ty),* $(,)?) => {
+            $(
+                if request.method == <$request_type as LspRequest>::METHOD {
+                    return Some(compare_params::<<$request_type as LspRequest>::Params>(
+                        &request.params,
+                    ));
+                }
+            )*
+        };
+    }
+
+    compare_with_any_of!(
+        Initialize,
+        RegisterCapability,
+        UnregisterCapability,
+        GotoDeclaration,
+        GotoDefinition,
+        GotoTypeDefinition,
+        GotoImplementation,
+        References,
+        CallHierarchyPrepare,
+        CallHierarchyIncomingCalls,
+        CallHierarchyOutgoingCalls,
+        TypeHierarchyPrepare,
+        TypeHierarchySupertypes,
+        TypeHierarchySubtypes,
+        DocumentHighlightRequest,
+        DocumentLinkRequest,
+        DocumentLinkResolve,
+        HoverRequest,
+        CodeLensRequest,
+        CodeLensResolve,
+        FoldingRangeRequest,
+        SelectionRangeRequest,
+        DocumentSymbolRequest,
+        SemanticTokensFullRequest,
+        SemanticTokensFullDeltaRequest,
+        SemanticTokensRangeRequest,
+        InlayHintRequest,
+        InlayHintResolveRequest,
+        InlineValueRequest,
+        MonikerRequest,
+        Completion,
+        ResolveCompletionItem,
+        DocumentDiagnosticRequest,
+        WorkspaceDiagnosticRequest,
+        SignatureHelpRequest,
+        CodeActionRequest,
+        CodeActionResolveRequest,
+        DocumentColor,
+        ColorPresentationRequest,
+        Formatting,
+        RangeFormatting,
+        OnTypeFormatting,
+        Rename,
+        PrepareRenameRequest,
+        LinkedEditingRange,
+        WorkspaceSymbolRequest,
+        WorkspaceSymbolResolve,
+        WillCreateFiles,
+        WillRenameFiles,
+        WillSaveWaitUntil,
+        ExecuteCommand,
+        WorkspaceConfiguration,
+        ApplyWorkspaceEdit,
+        ShowMessageRequest,
+        ShowDocument,
+        WorkDoneProgressCreate,
+    );
+
+    None
+}
+
+/// Reads `params` as `P` and writes it back out. Fields the spec knows about survive the round
+/// trip, so any field that doesn't is one the spec doesn't define.
+fn compare_params<P>(params: &Value) -> SpecComparison
+where
+    P: serde::de::DeserializeOwned + serde::Serialize,
+{
+    let present_fields = params.as_object();
+
+    let round_tripped = match serde_json::from_value::<P>(params.clone()) {
+        Ok(typed) => serde_json::to_value(typed).unwrap_or_default(),
+        Err(err) => {
+            return SpecComparison {
+                recognized_fields: Vec::new(),
+                unexpected_fields: Vec::new(),
+                error: Some(err.to_string()),
+            };
+        }
+    };
+
+    let mut recognized_fields = Vec::new();
+    let mut unexpected_fields = Vec::new();
+    for (field, value) in present_fields.into_iter().flatten() {
+        // Optional fields sent as null are dropped when they're written back out.
+        if round_tripped.get(field).is_some() || value.is_null() {
+            recognized_fields.push(field.clone());
+        } else {
+            unexpected_fields.push(field.clone());
+        }
+    }
+
+    SpecComparison {
+        recognized_fields,
+        unexpected_fields,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn a_hover_request_missing_its_position_is_flagged() {
+        let request = Request::new(
+            1.into(),
+            "textDocument/hover".to_string(),
+            json!({ "textDocument": { "uri": "file:///main.rs" } }),
+        );
+
+        let comparison = compare_request_with_spec(&request).unwrap();
+
+        assert!(!comparison.matches());
+        assert!(
+            comparison
+                .error
+                .is_some_and(|error| error.contains("missing field `position`"))
+        );
+    }
+
+    #[test]
+    fn custom_methods_are_not_compared() {
+        let request = Request::new(1.into(), "custom/request".to_string(), json!({}));
+
+        assert!(compare_request_with_spec(&request).is_none());
+    }
+}
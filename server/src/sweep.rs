@@ -0,0 +1,114 @@
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+use crate::{AppState, live::LiveEvent};
+
+/// Periodically ends sessions that have gone quiet, for as long as the server runs.
+pub(crate) async fn sweep_idle_sessions_periodically(state: AppState) {
+    let Some(session_sweep) = &state.config.session_sweep else {
+        return;
+    };
+
+    let mut interval = tokio::time::interval(session_sweep.interval);
+    loop {
+        interval.tick().await;
+        match sweep_idle_sessions(&state).await {
+            Ok(0) => {}
+            Ok(swept) => info!("Swept {swept} idle sessions"),
+            Err(err) => error!("Failed to sweep idle sessions. Error: {err}"),
+        }
+    }
+}
+
+/// Ends every live session without a connection whose last message (or start, if it has no
+/// messages) is older than the configured idle timeout. The session's `end_time_stamp` is set
+/// to that last activity rather than the time of the sweep. Returns the number of sessions
+/// ended.
+pub(crate) async fn sweep_idle_sessions(state: &AppState) -> Result<u64, sqlx::Error> {
+    let Some(session_sweep) = &state.config.session_sweep else {
+        return Ok(0);
+    };
+
+    let idle_since = OffsetDateTime::now_utc() - session_sweep.idle_timeout;
+    let active_sessions = state
+        .active_sessions
+        .lock()
+        .expect("the active session lock to not be poisoned")
+        .iter()
+        .copied()
+        .collect::<Vec<_>>();
+
+    let swept_session_ids = sqlx::query_scalar!(
+        "WITH last_activity AS (
+            SELECT sessions.id, GREATEST(
+                sessions.start_time_stamp,
+                (SELECT MAX(time_stamp) FROM requests WHERE session_id = sessions.id),
+                (SELECT MAX(time_stamp) FROM responses WHERE session_id = sessions.id),
+                (SELECT MAX(time_stamp) FROM notifications WHERE session_id = sessions.id)
+            ) AS time_stamp
+            FROM sessions
+            WHERE end_time_stamp IS NULL AND NOT (id = ANY($2))
+        )
+        UPDATE sessions SET end_time_stamp = last_activity.time_stamp
+        FROM last_activity
+        WHERE sessions.id = last_activity.id AND last_activity.time_stamp < $1
+        RETURNING sessions.id;",
+        idle_since,
+        &active_sessions
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    for &session_id in &swept_session_ids {
+        state.live_events.publish(LiveEvent::Ended { session_id });
+    }
+
+    Ok(swept_session_ids.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use sqlx::PgPool;
+
+    use super::*;
+    use crate::config::SessionSweepConfig;
+
+    #[sqlx::test]
+    async fn a_stale_session_is_ended_by_a_sweep(db: PgPool) {
+        let state = AppState::for_tests(db, |config| {
+            config.session_sweep = Some(SessionSweepConfig {
+                idle_timeout: Duration::from_secs(60 * 60),
+                interval: Duration::from_secs(60),
+            });
+        });
+        let insert_session = async |age: &str| {
+            sqlx::query_scalar!(
+                "INSERT INTO sessions (start_time_stamp)
+                VALUES (NOW() - $1::TEXT::INTERVAL) RETURNING id;",
+                age
+            )
+            .fetch_one(&state.db)
+            .await
+            .unwrap()
+        };
+        let stale = insert_session("2 hours").await;
+        insert_session("1 minute").await;
+        let connected = insert_session("2 hours").await;
+        state.active_sessions.lock().unwrap().insert(connected);
+
+        assert_eq!(sweep_idle_sessions(&state).await.unwrap(), 1);
+
+        let ended = sqlx::query!(
+            "SELECT id, end_time_stamp = start_time_stamp AS \"ended_at_last_activity!\"
+            FROM sessions WHERE end_time_stamp IS NOT NULL;"
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(ended.len(), 1);
+        assert_eq!(ended[0].id, stale);
+        assert!(ended[0].ended_at_last_activity);
+    }
+}
@@ -0,0 +1,67 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use tokio::sync::Notify;
+
+/// Counts the background tasks that write to the database, so that shutting down can wait for
+/// them instead of dropping their writes.
+#[derive(Clone, Default)]
+pub(crate) struct TaskTracker(Arc<TaskCount>);
+
+#[derive(Default)]
+struct TaskCount {
+    running: AtomicUsize,
+    idle: Notify,
+}
+
+/// Marks a task as finished when it's dropped, so that tasks that panic are counted too.
+pub(crate) struct RunningTask(Arc<TaskCount>);
+
+impl Drop for RunningTask {
+    fn drop(&mut self) {
+        if self.0.running.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
+
+impl TaskTracker {
+    pub(crate) fn spawn(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let running_task = self.track();
+        tokio::spawn(async move {
+            task.await;
+            drop(running_task);
+        });
+    }
+
+    /// Counts a write that isn't a task of its own, like a queued one, as running until the
+    /// returned guard is dropped.
+    pub(crate) fn track(&self) -> RunningTask {
+        self.0.running.fetch_add(1, Ordering::AcqRel);
+        RunningTask(self.0.clone())
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.0.running.load(Ordering::Acquire)
+    }
+
+    /// Waits until no tasks are running, including any spawned while waiting.
+    pub(crate) async fn wait(&self) {
+        loop {
+            let idle = self.0.idle.notified();
+            tokio::pin!(idle);
+            // Registers for the notification before checking, so that the last task finishing in
+            // between isn't missed.
+            idle.as_mut().enable();
+            if self.running() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
@@ -1,14 +1,135 @@
-use time::OffsetDateTime;
+use std::sync::LazyLock;
 
+use serde::{Deserialize, Deserializer, de::Error};
+use time::{
+    Duration, OffsetDateTime, PrimitiveDateTime, UtcOffset,
+    format_description::{self, BorrowedFormatItem, well_known::Rfc3339},
+};
+
+static ISO_FORMAT: LazyLock<Vec<BorrowedFormatItem<'static>>> = LazyLock::new(|| {
+    format_description::parse("[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3]Z")
+        .expect("the ISO 8601 format description to be valid")
+});
+
+/// The `IMF-fixdate` format of HTTP dates, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+static HTTP_DATE_FORMAT: LazyLock<Vec<BorrowedFormatItem<'static>>> = LazyLock::new(|| {
+    format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT",
+    )
+    .expect("the HTTP date format description to be valid")
+});
+
+/// Formats a time stamp as ISO 8601 in UTC with millisecond precision, e.g.
+/// `2024-03-07T14:22:10.412Z`, so that it sorts and reads the same in every timezone.
 pub(crate) fn get_iso_string(time_stamp: &OffsetDateTime) -> String {
+    time_stamp
+        .to_offset(UtcOffset::UTC)
+        .format(&ISO_FORMAT)
+        .expect("a UTC time stamp to always be representable in ISO 8601")
+}
+
+pub(crate) fn get_time_of_day_string(time_stamp: &OffsetDateTime) -> String {
     format!(
-        "{}/{:02}/{:04} at {:02}:{:02}:{:02}.{:03}",
+        "{:02}:{:02}:{:02}",
+        time_stamp.hour(),
+        time_stamp.minute(),
+        time_stamp.second()
+    )
+}
+
+pub(crate) fn get_duration_string(duration: &Duration) -> String {
+    let millis = duration.as_seconds_f64() * 1000.0;
+    if millis.abs() < 1000.0 {
+        format!("{:.1} ms", millis)
+    } else {
+        format!("{:.3} s", millis / 1000.0)
+    }
+}
+
+pub(crate) fn get_byte_size_string(bytes: i64) -> String {
+    if bytes < 1024 {
+        format!("{bytes} B")
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KiB", bytes as f64 / 1024.0)
+    } else if bytes < 1024 * 1024 * 1024 {
+        format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} GiB", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+    }
+}
+
+pub(crate) fn get_rfc3339_string(time_stamp: &OffsetDateTime) -> String {
+    time_stamp
+        .format(&Rfc3339)
+        .expect("a UTC time stamp to always be representable in RFC 3339")
+}
+
+/// Formats a time stamp the way HTTP headers like `Last-Modified` expect it, which drops the
+/// fractions of a second.
+pub(crate) fn get_http_date_string(time_stamp: &OffsetDateTime) -> String {
+    time_stamp
+        .to_offset(UtcOffset::UTC)
+        .format(&HTTP_DATE_FORMAT)
+        .expect("a UTC time stamp to always be representable as an HTTP date")
+}
+
+/// Parses an HTTP date in the `IMF-fixdate` format that every current client sends.
+pub(crate) fn parse_http_date(value: &str) -> Option<OffsetDateTime> {
+    PrimitiveDateTime::parse(value, &HTTP_DATE_FORMAT)
+        .ok()
+        .map(PrimitiveDateTime::assume_utc)
+}
+
+/// Formats a time stamp in UTC the way `<input type="datetime-local">` expects its value.
+pub(crate) fn get_datetime_local_string(time_stamp: &OffsetDateTime) -> String {
+    let time_stamp = time_stamp.to_offset(UtcOffset::UTC);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}",
+        time_stamp.year(),
         time_stamp.month() as i32,
         time_stamp.day(),
-        time_stamp.year(),
         time_stamp.hour(),
         time_stamp.minute(),
         time_stamp.second(),
         time_stamp.millisecond()
     )
 }
+
+/// Parses an RFC 3339 time stamp, or the offset-less value of a `datetime-local` input, which is
+/// taken to be in UTC.
+pub(crate) fn parse_time_stamp(value: &str) -> Option<OffsetDateTime> {
+    if let Ok(time_stamp) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Some(time_stamp);
+    }
+
+    // Browsers leave off the seconds and fractions of a second when they're zero.
+    [
+        "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond]",
+        "[year]-[month]-[day]T[hour]:[minute]:[second]",
+        "[year]-[month]-[day]T[hour]:[minute]",
+    ]
+    .into_iter()
+    .find_map(|format| {
+        let format = format_description::parse(format)
+            .expect("the datetime-local format descriptions to be valid");
+        PrimitiveDateTime::parse(value, &format).ok()
+    })
+    .map(PrimitiveDateTime::assume_utc)
+}
+
+/// Deserializes the id of a row, like a session's, rejecting the ids no row can have since ids
+/// start at one. They're refused with a clear error instead of being looked up.
+pub(crate) fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    let id = i64::deserialize(deserializer)?;
+    if id < 1 {
+        return Err(D::Error::custom(format!("{id} is not a valid id")));
+    }
+    Ok(id)
+}
+
+/// Like `deserialize_id`, for ids that may be left out. Fields using it need `#[serde(default)]`.
+pub(crate) fn deserialize_optional_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i64>, D::Error> {
+    deserialize_id(deserializer).map(Some)
+}